@@ -2,13 +2,69 @@ use anyhow::{Context, Result};
 use chrono::Local;
 use sha2::{Digest, Sha256};
 use sqlx::types::chrono::NaiveDateTime;
-use sqlx::{Pool, Sqlite, sqlite::SqlitePoolOptions};
+use sqlx::{Pool, QueryBuilder, Sqlite, sqlite::SqlitePoolOptions};
 use std::path::PathBuf;
 use tracing::{debug, instrument};
 use uuid::Uuid;
 
 use crate::types::*;
 
+// 会话令牌在数据库中的表示。令牌本身是高熵的随机字符串，不需要加盐
+fn hash_token(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+// 按项目列表的筛选条件追加 WHERE 子句
+fn push_item_filter(query: &mut QueryBuilder<'_, Sqlite>, filter: &ItemFilter) {
+    query.push(" WHERE 1 = 1");
+    if let Some(creator) = &filter.creator {
+        query.push(" AND creator = ").push_bind(creator.clone());
+    }
+    if let Some(group_id) = &filter.group_id {
+        query.push(" AND group_id = ").push_bind(group_id.clone());
+    }
+    if let Some(item_type) = filter.item_type {
+        query.push(" AND item_type = ").push_bind(item_type);
+    }
+    if let Some(available) = filter.available {
+        query.push(" AND available = ").push_bind(available);
+    }
+    if let Some(img) = filter.img {
+        query.push(" AND img = ").push_bind(img);
+    }
+    if let Some(tag) = &filter.tag {
+        query
+            .push(
+                " AND EXISTS (SELECT 1 FROM item_tags JOIN tags ON tags.id = item_tags.tag_id \
+                 WHERE item_tags.item_id = items.id AND tags.name = ",
+            )
+            .push_bind(tag.clone())
+            .push(")");
+    }
+    match filter.password {
+        Some(true) => query.push(" AND password_hash IS NOT NULL"),
+        Some(false) => query.push(" AND password_hash IS NULL"),
+        None => query,
+    };
+    match filter.expiry {
+        Some(true) => query.push(" AND expires_at IS NOT NULL"),
+        Some(false) => query.push(" AND expires_at IS NULL"),
+        None => query,
+    };
+    if let Some(created_after) = filter.created_after {
+        query.push(" AND created_at >= ").push_bind(created_after);
+    }
+    if let Some(created_before) = filter.created_before {
+        query.push(" AND created_at < ").push_bind(created_before);
+    }
+    if let Some(expires_after) = filter.expires_after {
+        query.push(" AND expires_at >= ").push_bind(expires_after);
+    }
+    if let Some(expires_before) = filter.expires_before {
+        query.push(" AND expires_at < ").push_bind(expires_before);
+    }
+}
+
 // 数据库访问器
 #[derive(Clone)]
 pub struct DatabaseAccessor {
@@ -63,7 +119,7 @@ impl DatabaseAccessor {
         avatar: Option<String>,
     ) -> Result<User> {
         let now = Local::now().naive_local();
-        let password = format!("{:x}", Sha256::digest(password.as_bytes()));
+        let password = crate::util::hash_password(password).await?;
         let user = sqlx::query_as!(
             User,
             r#"
@@ -85,19 +141,267 @@ impl DatabaseAccessor {
         Ok(user)
     }
 
-    pub async fn admin_user_exists(&self) -> Result<bool> {
+    // 用户自行注册（通过邀请或开放注册），密码应已经过哈希
+    pub async fn register_user(
+        &self,
+        id: &str,
+        name: &str,
+        email: &str,
+        password_hash: &str,
+        descriptor: i64,
+        status: &str,
+    ) -> Result<User> {
+        let now = Local::now().naive_local();
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            INSERT INTO users (id, name, email, password, created_at, descriptor, status)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING *
+            "#,
+            id,
+            name,
+            email,
+            password_hash,
+            now,
+            descriptor,
+            status
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(user)
+    }
+
+    pub async fn set_user_status(&self, id: &str, status: &str) -> Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET status = $1
+            WHERE id = $2
+            "#,
+            status,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // 创建来自外部身份源的用户，这类用户没有可用的本地密码
+    pub async fn create_external_user(
+        &self,
+        id: &str,
+        name: &str,
+        email: &str,
+        descriptor: i64,
+        auth_source: &str,
+        external_id: &str,
+    ) -> Result<User> {
+        let now = Local::now().naive_local();
+        let password = format!("!{}", auth_source);
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            INSERT INTO users (id, name, email, password, created_at, descriptor, auth_source, external_id)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING *
+            "#,
+            id,
+            name,
+            email,
+            password,
+            now,
+            descriptor,
+            auth_source,
+            external_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(user)
+    }
+
+    pub async fn get_user_by_external_id(
+        &self,
+        auth_source: &str,
+        external_id: &str,
+    ) -> Result<Option<User>> {
         let user = sqlx::query_as!(
             User,
             r#"
             SELECT * FROM users
-            WHERE id = $1
+            WHERE auth_source = $1 AND external_id = $2
             "#,
-            "00000000-0000-0000-0000-000000000000"
+            auth_source,
+            external_id
         )
         .fetch_optional(&self.pool)
         .await?;
-        Ok(user.is_some())
+        Ok(user)
+    }
+
+    // 把已有的用户关联到外部身份，之后按外部 ID 登录并同步信息
+    pub async fn link_external_user(
+        &self,
+        id: &str,
+        auth_source: &str,
+        external_id: &str,
+        name: &str,
+        descriptor: i64,
+    ) -> Result<User> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET auth_source = $1, external_id = $2, name = $3, descriptor = $4
+            WHERE id = $5
+            RETURNING *
+            "#,
+            auth_source,
+            external_id,
+            name,
+            descriptor,
+            id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(user)
+    }
+
+    // 同步外部身份源提供的用户信息
+    pub async fn update_external_user(
+        &self,
+        id: &str,
+        name: &str,
+        email: &str,
+        descriptor: i64,
+    ) -> Result<User> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET name = $1, email = $2, descriptor = $3
+            WHERE id = $4
+            RETURNING *
+            "#,
+            name,
+            email,
+            descriptor,
+            id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(user)
+    }
+
+    pub async fn owner_exists(&self) -> Result<bool> {
+        Ok(self.count_owners().await? > 0)
+    }
+
+    pub async fn count_owners(&self) -> Result<i64> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) as "count!: i64" FROM users
+            WHERE owner = TRUE
+            "#
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(count)
+    }
+
+    // 按创建时间排序，第一个通常是初始化时创建的管理员
+    pub async fn get_owners(&self) -> Result<Vec<User>> {
+        let users = sqlx::query_as!(
+            User,
+            r#"
+            SELECT * FROM users
+            WHERE owner = TRUE
+            ORDER BY created_at
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(users)
+    }
+
+    pub async fn grant_owner(&self, id: &str) -> Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET owner = TRUE
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // 取消所有者身份，是最后一个所有者时不做修改并返回 false
+    pub async fn revoke_owner(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            UPDATE users
+            SET owner = FALSE
+            WHERE id = $1 AND owner = TRUE
+              AND (SELECT COUNT(*) FROM users WHERE owner = TRUE) > 1
+            "#,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // 将所有者身份从一个用户转移给另一个用户
+    pub async fn transfer_owner(&self, from: &str, to: &str) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET owner = TRUE
+            WHERE id = $1
+            "#,
+            to
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET owner = FALSE
+            WHERE id = $1
+            "#,
+            from
+        )
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    // 设置用户自己的配额，字段为空时使用默认配额
+    pub async fn update_user_quota(&self, id: &str, quota: &QuotaConfig) -> Result<User> {
+        let user = sqlx::query_as!(
+            User,
+            r#"
+            UPDATE users
+            SET quota_storage = $1, quota_items = $2, quota_file_size = $3, quota_expiry = $4
+            WHERE id = $5
+            RETURNING *
+            "#,
+            quota.storage,
+            quota.items,
+            quota.file_size,
+            quota.expiry,
+            id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(user)
     }
+
     pub async fn change_user_password(&self, id: &str, password: &str) -> Result<User> {
         let user = sqlx::query_as!(
             User,
@@ -182,229 +486,1722 @@ impl DatabaseAccessor {
         Ok(users)
     }
 
-    pub async fn create_item(
+    pub async fn create_session(
         &self,
-        short_path: &str,
-        item_type: ItemType,
-        data: &str,
-        expires_at: Option<NaiveDateTime>,
-        max_visits: Option<i64>,
-        password_hash: Option<&str>,
-        extra_data: Option<&str>,
-        creator: Option<&str>,
-    ) -> anyhow::Result<Item> {
-        // 因为 Spectra 不是分布式的，使用 UUID v7 是一个十分经济、完全保证唯一并且有序的选择
+        token: &str,
+        user_id: &str,
+        temporary: bool,
+        remember: bool,
+        client: &ClientInfo,
+    ) -> Result<Session> {
         let id = Uuid::now_v7().to_string();
+        let token_hash = hash_token(token);
         let now = Local::now().naive_local();
-        let item = sqlx::query_as!(
-            Item,
+        let expires_at = now + Session::lifetime(temporary, remember);
+        let session = sqlx::query_as!(
+            Session,
             r#"
-            INSERT INTO items (id, short_path, item_type, data, expires_at, max_visits, visits, password_hash, created_at, extra_data, creator, available)
-            VALUES ($1, $2, $3, $4, $5, $6, 0, $7, $8, $9, $10, $11)
+            INSERT INTO sessions (id, token_hash, user_id, temporary, remember, created_at, last_used_at, expires_at, ip_address, user_agent)
+            VALUES ($1, $2, $3, $4, $5, $6, $6, $7, $8, $9)
             RETURNING *
             "#,
             id,
-            short_path,
-            item_type,
-            data,
-            expires_at,
-            max_visits,
-            password_hash,
+            token_hash,
+            user_id,
+            temporary,
+            remember,
             now,
-            extra_data,
-            creator,
-            true,
+            expires_at,
+            client.ip_address,
+            client.user_agent
         )
         .fetch_one(&self.pool)
         .await?;
+        Ok(session)
+    }
 
-        Ok(item)
+    // 创建管理员模拟其他用户的会话，有效期较短且不会续期
+    pub async fn create_impersonation_session(
+        &self,
+        token: &str,
+        user_id: &str,
+        impersonator_id: &str,
+        client: &ClientInfo,
+    ) -> Result<Session> {
+        let id = Uuid::now_v7().to_string();
+        let token_hash = hash_token(token);
+        let now = Local::now().naive_local();
+        let expires_at = now + Session::impersonation_lifetime();
+        let session = sqlx::query_as!(
+            Session,
+            r#"
+            INSERT INTO sessions (id, token_hash, user_id, temporary, remember, created_at, last_used_at, expires_at, ip_address, user_agent, impersonator_id)
+            VALUES ($1, $2, $3, FALSE, FALSE, $4, $4, $5, $6, $7, $8)
+            RETURNING *
+            "#,
+            id,
+            token_hash,
+            user_id,
+            now,
+            expires_at,
+            client.ip_address,
+            client.user_agent,
+            impersonator_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(session)
     }
 
-    pub async fn get_item(&self, short_path: &str) -> anyhow::Result<Option<Item>> {
-        let item = sqlx::query_as!(
-            Item,
+    // 按令牌查找会话，不检查是否过期
+    pub async fn get_session(&self, token: &str) -> Result<Option<Session>> {
+        let token_hash = hash_token(token);
+        let session = sqlx::query_as!(
+            Session,
             r#"
-            SELECT * FROM items
-            WHERE short_path = $1
+            SELECT * FROM sessions
+            WHERE token_hash = $1
             "#,
-            short_path
+            token_hash
         )
         .fetch_optional(&self.pool)
         .await?;
-        Ok(item)
+        Ok(session)
     }
 
-    pub async fn get_user_items(
+    // 创建等待两步验证的会话，它不能用于访问其他接口
+    pub async fn create_pending_session(
         &self,
+        token: &str,
         user_id: &str,
-        offset: i64,
-        limit: i64,
-    ) -> anyhow::Result<Vec<Item>> {
-        let items = sqlx::query_as!(
-            Item,
+        remember: bool,
+        client: &ClientInfo,
+    ) -> Result<Session> {
+        let id = Uuid::now_v7().to_string();
+        let token_hash = hash_token(token);
+        let now = Local::now().naive_local();
+        let expires_at = now + Session::pending_lifetime();
+        let session = sqlx::query_as!(
+            Session,
+            r#"
+            INSERT INTO sessions (id, token_hash, user_id, temporary, remember, created_at, last_used_at, expires_at, pending, ip_address, user_agent)
+            VALUES ($1, $2, $3, FALSE, $4, $5, $5, $6, TRUE, $7, $8)
+            RETURNING *
+            "#,
+            id,
+            token_hash,
+            user_id,
+            remember,
+            now,
+            expires_at,
+            client.ip_address,
+            client.user_agent
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(session)
+    }
+
+    // 按令牌查找未过期的、等待两步验证的会话
+    pub async fn get_pending_session(&self, token: &str) -> Result<Option<Session>> {
+        let session = self.get_session(token).await?;
+        Ok(session.filter(|session| session.pending && !session.is_expired()))
+    }
+
+    // 按令牌查找未过期的会话，并顺带为其续期（滑动过期）
+    pub async fn get_active_session(&self, token: &str) -> Result<Option<Session>> {
+        let session = self.get_session(token).await?;
+        let Some(mut session) = session else {
+            return Ok(None);
+        };
+        if session.is_expired() || session.pending {
+            return Ok(None);
+        }
+        if !session.temporary && session.impersonator_id.is_none() {
+            let now = Local::now().naive_local();
+            let expires_at = now + Session::lifetime(false, session.remember);
+            sqlx::query!(
+                r#"
+                UPDATE sessions
+                SET last_used_at = $1, expires_at = $2
+                WHERE id = $3
+                "#,
+                now,
+                expires_at,
+                session.id
+            )
+            .execute(&self.pool)
+            .await?;
+            session.last_used_at = now;
+            session.expires_at = expires_at;
+        }
+        Ok(Some(session))
+    }
+
+    pub async fn remove_session(&self, token: &str) -> Result<()> {
+        let token_hash = hash_token(token);
+        sqlx::query!(
+            r#"
+            DELETE FROM sessions
+            WHERE token_hash = $1
+            "#,
+            token_hash
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn get_session_by_id(&self, id: &str) -> Result<Option<Session>> {
+        let session = sqlx::query_as!(
+            Session,
+            r#"
+            SELECT * FROM sessions
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(session)
+    }
+
+    // 获取用户所有有效的登录会话（不含临时会话和等待两步验证的会话），最近使用的在前
+    pub async fn get_user_sessions(&self, user_id: &str) -> Result<Vec<Session>> {
+        let now = Local::now().naive_local();
+        let sessions = sqlx::query_as!(
+            Session,
+            r#"
+            SELECT * FROM sessions
+            WHERE user_id = $1 AND temporary = FALSE AND pending = FALSE AND expires_at > $2
+            ORDER BY last_used_at DESC
+            "#,
+            user_id,
+            now
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(sessions)
+    }
+
+    pub async fn remove_session_by_id(&self, id: &str) -> Result<()> {
+        sqlx::query!(
+            r#"
+            DELETE FROM sessions
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // 删除用户的所有会话，可以保留一个（通常是发起请求的会话），返回被删除的数量
+    pub async fn remove_user_sessions(&self, user_id: &str, except: Option<&str>) -> Result<u64> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM sessions
+            WHERE user_id = $1 AND id IS NOT $2
+            "#,
+            user_id,
+            except
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn create_invite(
+        &self,
+        code: &str,
+        created_by: &str,
+        descriptor: i64,
+        expires_at: Option<NaiveDateTime>,
+        max_uses: Option<i64>,
+    ) -> Result<Invite> {
+        let id = Uuid::now_v7().to_string();
+        let code_hash = hash_token(code);
+        let now = Local::now().naive_local();
+        let invite = sqlx::query_as!(
+            Invite,
+            r#"
+            INSERT INTO invites (id, code_hash, created_by, descriptor, created_at, expires_at, max_uses)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING *
+            "#,
+            id,
+            code_hash,
+            created_by,
+            descriptor,
+            now,
+            expires_at,
+            max_uses
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(invite)
+    }
+
+    pub async fn get_invites(&self) -> Result<Vec<Invite>> {
+        let invites = sqlx::query_as!(
+            Invite,
+            r#"
+            SELECT * FROM invites
+            ORDER BY created_at DESC
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(invites)
+    }
+
+    pub async fn get_invite_by_code(&self, code: &str) -> Result<Option<Invite>> {
+        let code_hash = hash_token(code);
+        let invite = sqlx::query_as!(
+            Invite,
+            r#"
+            SELECT * FROM invites
+            WHERE code_hash = $1
+            "#,
+            code_hash
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(invite)
+    }
+
+    // 使用一次邀请码：仅在未过期且未用完时计数并返回，否则返回 None
+    pub async fn use_invite(&self, code: &str) -> Result<Option<Invite>> {
+        let code_hash = hash_token(code);
+        let now = Local::now().naive_local();
+        let invite = sqlx::query_as!(
+            Invite,
+            r#"
+            UPDATE invites
+            SET uses = uses + 1
+            WHERE code_hash = $1
+              AND (expires_at IS NULL OR expires_at > $2)
+              AND (max_uses IS NULL OR uses < max_uses)
+            RETURNING *
+            "#,
+            code_hash,
+            now
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(invite)
+    }
+
+    pub async fn remove_invite(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM invites
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    // 创建组，并在同一事务中把 owner 加入为组的所有者
+    pub async fn create_group(&self, name: &str, descriptor: i64, owner: &str) -> Result<Group> {
+        let id = Uuid::new_v4().to_string();
+        let now = Local::now().naive_local();
+        let mut transaction = self.pool.begin().await?;
+        let group = sqlx::query_as!(
+            Group,
+            r#"
+            INSERT INTO groups (id, name, descriptor, created_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING *
+            "#,
+            id,
+            name,
+            descriptor,
+            now
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"
+            INSERT INTO group_members (group_id, user_id, role, created_at)
+            VALUES ($1, $2, $3, $4)
+            "#,
+            id,
+            owner,
+            GroupMember::OWNER,
+            now
+        )
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(group)
+    }
+
+    pub async fn get_group(&self, id: &str) -> Result<Option<Group>> {
+        let group = sqlx::query_as!(
+            Group,
+            r#"
+            SELECT * FROM groups
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(group)
+    }
+
+    pub async fn get_groups(&self) -> Result<Vec<Group>> {
+        let groups = sqlx::query_as!(
+            Group,
+            r#"
+            SELECT * FROM groups
+            ORDER BY created_at
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(groups)
+    }
+
+    // 用户所在的组
+    pub async fn get_user_groups(&self, user_id: &str) -> Result<Vec<Group>> {
+        let groups = sqlx::query_as!(
+            Group,
+            r#"
+            SELECT g.* FROM groups g
+            JOIN group_members m ON m.group_id = g.id
+            WHERE m.user_id = $1
+            ORDER BY g.created_at
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(groups)
+    }
+
+    // 用户通过所在的组得到的权限之和
+    pub async fn get_user_group_descriptor(&self, user_id: &str) -> Result<i64> {
+        let descriptors = sqlx::query_scalar!(
+            r#"
+            SELECT g.descriptor FROM groups g
+            JOIN group_members m ON m.group_id = g.id
+            WHERE m.user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(descriptors.into_iter().fold(0, |a, b| a | b))
+    }
+
+    pub async fn update_group(&self, id: &str, name: &str, descriptor: i64) -> Result<Group> {
+        let group = sqlx::query_as!(
+            Group,
+            r#"
+            UPDATE groups
+            SET name = $1, descriptor = $2
+            WHERE id = $3
+            RETURNING *
+            "#,
+            name,
+            descriptor,
+            id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(group)
+    }
+
+    // 删除组，组的项目转为只属于各自的创建者
+    pub async fn remove_group(&self, id: &str) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query!(
+            r#"
+            UPDATE items
+            SET group_id = NULL
+            WHERE group_id = $1
+            "#,
+            id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM group_members
+            WHERE group_id = $1
+            "#,
+            id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM groups
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    pub async fn get_group_member(
+        &self,
+        group_id: &str,
+        user_id: &str,
+    ) -> Result<Option<GroupMember>> {
+        let member = sqlx::query_as!(
+            GroupMember,
+            r#"
+            SELECT * FROM group_members
+            WHERE group_id = $1 AND user_id = $2
+            "#,
+            group_id,
+            user_id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(member)
+    }
+
+    pub async fn get_group_members(&self, group_id: &str) -> Result<Vec<GroupMember>> {
+        let members = sqlx::query_as!(
+            GroupMember,
+            r#"
+            SELECT * FROM group_members
+            WHERE group_id = $1
+            ORDER BY created_at
+            "#,
+            group_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(members)
+    }
+
+    // 添加成员或修改成员的角色
+    pub async fn set_group_member(
+        &self,
+        group_id: &str,
+        user_id: &str,
+        role: &str,
+    ) -> Result<GroupMember> {
+        let now = Local::now().naive_local();
+        let member = sqlx::query_as!(
+            GroupMember,
+            r#"
+            INSERT INTO group_members (group_id, user_id, role, created_at)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT(group_id, user_id) DO UPDATE SET role = $3
+            RETURNING *
+            "#,
+            group_id,
+            user_id,
+            role,
+            now
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(member)
+    }
+
+    pub async fn remove_group_member(&self, group_id: &str, user_id: &str) -> Result<()> {
+        sqlx::query!(
+            r#"
+            DELETE FROM group_members
+            WHERE group_id = $1 AND user_id = $2
+            "#,
+            group_id,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn count_group_owners(&self, group_id: &str) -> Result<i64> {
+        let count = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) as "count!: i64" FROM group_members
+            WHERE group_id = $1 AND role = 'owner'
+            "#,
+            group_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(count)
+    }
+
+    // 创建找回密码的重置令牌，同一用户之前未使用的令牌全部作废
+    pub async fn create_password_reset(&self, token: &str, user_id: &str) -> Result<PasswordReset> {
+        let id = Uuid::now_v7().to_string();
+        let token_hash = hash_token(token);
+        let now = Local::now().naive_local();
+        let expires_at = now + PasswordReset::lifetime();
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM password_resets
+            WHERE user_id = $1 AND used_at IS NULL
+            "#,
+            user_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        let reset = sqlx::query_as!(
+            PasswordReset,
+            r#"
+            INSERT INTO password_resets (id, user_id, token_hash, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *
+            "#,
+            id,
+            user_id,
+            token_hash,
+            now,
+            expires_at
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(reset)
+    }
+
+    // 使用重置令牌：未过期且未使用过时标记为已使用并返回，否则返回 None
+    pub async fn use_password_reset(&self, token: &str) -> Result<Option<PasswordReset>> {
+        let token_hash = hash_token(token);
+        let now = Local::now().naive_local();
+        let reset = sqlx::query_as!(
+            PasswordReset,
+            r#"
+            UPDATE password_resets
+            SET used_at = $1
+            WHERE token_hash = $2 AND used_at IS NULL AND expires_at > $1
+            RETURNING *
+            "#,
+            now,
+            token_hash
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(reset)
+    }
+
+    // 删除所有已过期的会话，返回被删除的数量
+    pub async fn prune_sessions(&self) -> Result<u64> {
+        let now = Local::now().naive_local();
+        let result = sqlx::query!(
+            r#"
+            DELETE FROM sessions
+            WHERE expires_at <= $1
+            "#,
+            now
+        )
+        .execute(&self.pool)
+        .await?;
+        // 顺带清理过期的重置令牌
+        sqlx::query!(
+            r#"
+            DELETE FROM password_resets
+            WHERE expires_at <= $1
+            "#,
+            now
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn create_api_token(
+        &self,
+        token: &str,
+        user_id: &str,
+        name: &str,
+        descriptor: i64,
+        expires_at: Option<NaiveDateTime>,
+    ) -> Result<ApiToken> {
+        let id = Uuid::now_v7().to_string();
+        let token_hash = hash_token(token);
+        let now = Local::now().naive_local();
+        let api_token = sqlx::query_as!(
+            ApiToken,
+            r#"
+            INSERT INTO api_tokens (id, user_id, name, token_hash, descriptor, created_at, expires_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING *
+            "#,
+            id,
+            user_id,
+            name,
+            token_hash,
+            descriptor,
+            now,
+            expires_at
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(api_token)
+    }
+
+    // 按令牌查找 API 令牌，找到时记录最后使用时间；不检查是否过期
+    pub async fn use_api_token(&self, token: &str) -> Result<Option<ApiToken>> {
+        let token_hash = hash_token(token);
+        let now = Local::now().naive_local();
+        let api_token = sqlx::query_as!(
+            ApiToken,
+            r#"
+            UPDATE api_tokens
+            SET last_used_at = $1
+            WHERE token_hash = $2
+            RETURNING *
+            "#,
+            now,
+            token_hash
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(api_token)
+    }
+
+    pub async fn get_user_api_tokens(&self, user_id: &str) -> Result<Vec<ApiToken>> {
+        let tokens = sqlx::query_as!(
+            ApiToken,
+            r#"
+            SELECT * FROM api_tokens
+            WHERE user_id = $1
+            ORDER BY created_at DESC
+            "#,
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(tokens)
+    }
+
+    pub async fn get_api_token(&self, id: &str) -> Result<Option<ApiToken>> {
+        let api_token = sqlx::query_as!(
+            ApiToken,
+            r#"
+            SELECT * FROM api_tokens
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(api_token)
+    }
+
+    pub async fn remove_api_token(&self, id: &str) -> Result<()> {
+        sqlx::query!(
+            r#"
+            DELETE FROM api_tokens
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn set_user_totp(&self, id: &str, secret: Option<&str>, enabled: bool) -> Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE users
+            SET totp_secret = $1, totp_enabled = $2
+            WHERE id = $3
+            "#,
+            secret,
+            enabled,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // 用新的一组恢复码替换用户现有的全部恢复码
+    pub async fn replace_recovery_codes(&self, user_id: &str, codes: &[String]) -> Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM totp_recovery_codes
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        for code in codes {
+            let id = Uuid::now_v7().to_string();
+            let code_hash = hash_token(code);
+            sqlx::query!(
+                r#"
+                INSERT INTO totp_recovery_codes (id, user_id, code_hash)
+                VALUES ($1, $2, $3)
+                "#,
+                id,
+                user_id,
+                code_hash
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    // 消耗一个恢复码，返回它是否有效
+    pub async fn use_recovery_code(&self, user_id: &str, code: &str) -> Result<bool> {
+        let code_hash = hash_token(code);
+        let now = Local::now().naive_local();
+        let result = sqlx::query!(
+            r#"
+            UPDATE totp_recovery_codes
+            SET used_at = $1
+            WHERE user_id = $2 AND code_hash = $3 AND used_at IS NULL
+            "#,
+            now,
+            user_id,
+            code_hash
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn remove_recovery_codes(&self, user_id: &str) -> Result<()> {
+        sqlx::query!(
+            r#"
+            DELETE FROM totp_recovery_codes
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // 创建项目，新路径上原有的跳转会被移除
+    pub async fn create_item(
+        &self,
+        short_path: &str,
+        item_type: ItemType,
+        data: &str,
+        expires_at: Option<NaiveDateTime>,
+        max_visits: Option<i64>,
+        password_hash: Option<&str>,
+        extra_data: Option<&str>,
+        creator: Option<&str>,
+    ) -> anyhow::Result<Item> {
+        // 因为 Spectra 不是分布式的，使用 UUID v7 是一个十分经济、完全保证唯一并且有序的选择
+        let id = Uuid::now_v7().to_string();
+        let now = Local::now().naive_local();
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM item_redirects
+            WHERE short_path = $1
+            "#,
+            short_path
+        )
+        .execute(&mut *transaction)
+        .await?;
+        let item = sqlx::query_as!(
+            Item,
+            r#"
+            INSERT INTO items (id, short_path, item_type, data, expires_at, max_visits, visits, password_hash, created_at, extra_data, creator, available)
+            VALUES ($1, $2, $3, $4, $5, $6, 0, $7, $8, $9, $10, $11)
+            RETURNING *
+            "#,
+            id,
+            short_path,
+            item_type,
+            data,
+            expires_at,
+            max_visits,
+            password_hash,
+            now,
+            extra_data,
+            creator,
+            true,
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+
+        Ok(item)
+    }
+
+    pub async fn get_item(&self, short_path: &str) -> anyhow::Result<Option<Item>> {
+        let item = sqlx::query_as!(
+            Item,
+            r#"
+            SELECT * FROM items
+            WHERE short_path = $1
+            "#,
+            short_path
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(item)
+    }
+
+    pub async fn count_user_items(&self, user_id: &str) -> anyhow::Result<i64> {
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) as "count!: i64" FROM items
+            WHERE creator = $1
+            "#,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(total)
+    }
+
+    // 用户占用的存储空间（字节，包括历史版本）和有效项目数量
+    pub async fn get_user_usage(&self, user_id: &str) -> anyhow::Result<(i64, i64)> {
+        let usage = sqlx::query!(
+            r#"
+            SELECT
+              COALESCE(SUM(size), 0) + (
+                SELECT COALESCE(SUM(item_revisions.size), 0) FROM item_revisions
+                JOIN items ON items.id = item_revisions.item_id
+                WHERE items.creator = $1
+              ) as "storage!: i64",
+              COALESCE(SUM(available), 0) as "items!: i64"
+            FROM items
+            WHERE creator = $1
+            "#,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok((usage.storage, usage.items))
+    }
+
+    pub async fn update_item_group(&self, id: &str, group_id: Option<&str>) -> anyhow::Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE items
+            SET group_id = $1
+            WHERE id = $2
+            "#,
+            group_id,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // 按筛选条件列出项目：传入游标时从游标之后继续，否则使用 offset
+    pub async fn list_items(
+        &self,
+        filter: &ItemFilter,
+        cursor: Option<&ItemCursor>,
+        offset: i64,
+        limit: i64,
+    ) -> anyhow::Result<Vec<Item>> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT * FROM items");
+        push_item_filter(&mut query, filter);
+        let sort_key = match filter.sort {
+            ItemSort::Created => "created_at",
+            ItemSort::Visits => "visits",
+            ItemSort::Expires => "COALESCE(expires_at, '9999-12-31')",
+        };
+        let (cmp, order) = match filter.order {
+            SortOrder::Asc => (">", "ASC"),
+            SortOrder::Desc => ("<", "DESC"),
+        };
+        if let Some(cursor) = cursor {
+            query.push(format!(" AND ({sort_key}, id) {cmp} ("));
+            match filter.sort {
+                ItemSort::Created => query.push_bind(cursor.created_at),
+                ItemSort::Visits => query.push_bind(cursor.visits),
+                ItemSort::Expires => match cursor.expires_at {
+                    Some(expires_at) => query.push_bind(expires_at),
+                    None => query.push_bind("9999-12-31"),
+                },
+            };
+            query.push(", ").push_bind(cursor.id.clone()).push(")");
+        }
+        // ID 是按时间递增的 UUIDv7，排序字段相同时用它保证顺序稳定
+        query.push(format!(" ORDER BY {sort_key} {order}, id {order}"));
+        query.push(" LIMIT ").push_bind(limit);
+        if cursor.is_none() {
+            query.push(" OFFSET ").push_bind(offset);
+        }
+        let items = query.build_query_as::<Item>().fetch_all(&self.pool).await?;
+        Ok(items)
+    }
+
+    pub async fn count_items(&self, filter: &ItemFilter) -> anyhow::Result<i64> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) FROM items");
+        push_item_filter(&mut query, filter);
+        let total = query
+            .build_query_scalar::<i64>()
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(total)
+    }
+
+    pub async fn item_exists(&self, path: &str) -> Result<bool> {
+        let item = sqlx::query_as!(
+            Item,
+            r#"
+            SELECT * FROM items
+            WHERE short_path = $1
+            "#,
+            path
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(item.is_some())
+    }
+
+    // 删除项目及其跳转和历史版本，返回需要删除的历史版本文件
+    pub async fn remove_item(&self, id: &str) -> anyhow::Result<Vec<String>> {
+        let mut transaction = self.pool.begin().await?;
+        let files = sqlx::query_scalar!(
+            r#"
+            DELETE FROM item_revisions
+            WHERE item_id = $1
+            RETURNING data
+            "#,
+            id
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM items
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM item_redirects
+            WHERE item_id = $1
+            "#,
+            id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM item_search
+            WHERE item_id = $1
+            "#,
+            id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM item_tags
+            WHERE item_id = $1
+            "#,
+            id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(files)
+    }
+
+    // 保存修改后的项目（路径、内容、密码、过期设置和附加数据）
+    // redirect_from 不为空时在该路径保留一个指向项目的跳转；项目的新路径上原有的跳转会被移除
+    // previous 不为空时将其作为项目的历史版本保存
+    pub async fn update_item(
+        &self,
+        item: &Item,
+        redirect_from: Option<&str>,
+        previous: Option<&ItemRevision>,
+    ) -> anyhow::Result<Item> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM item_redirects
+            WHERE short_path = $1
+            "#,
+            item.short_path
+        )
+        .execute(&mut *transaction)
+        .await?;
+        let updated = sqlx::query_as!(
+            Item,
+            r#"
+            UPDATE items
+            SET short_path = $1, data = $2, expires_at = $3, max_visits = $4, password_hash = $5, extra_data = $6, size = $7,
+                revision = $8, max_revisions = $9, updated_at = $10, updated_by = $11
+            WHERE id = $12
+            RETURNING *
+            "#,
+            item.short_path,
+            item.data,
+            item.expires_at,
+            item.max_visits,
+            item.password_hash,
+            item.extra_data,
+            item.size,
+            item.revision,
+            item.max_revisions,
+            item.updated_at,
+            item.updated_by,
+            item.id
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        if let Some(previous) = previous {
+            sqlx::query!(
+                r#"
+                INSERT INTO item_revisions (id, item_id, revision, data, size, created_at, created_by)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                "#,
+                previous.id,
+                previous.item_id,
+                previous.revision,
+                previous.data,
+                previous.size,
+                previous.created_at,
+                previous.created_by
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+        if let Some(redirect_from) = redirect_from {
+            let now = Local::now().naive_local();
+            sqlx::query!(
+                r#"
+                INSERT INTO item_redirects (short_path, item_id, created_at)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (short_path) DO UPDATE SET item_id = $2, created_at = $3
+                "#,
+                redirect_from,
+                item.id,
+                now
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+        Ok(updated)
+    }
+
+    // 获取项目的标签名称，按名称排序
+    pub async fn get_item_tags(&self, item_id: &str) -> anyhow::Result<Vec<String>> {
+        let tags = sqlx::query_scalar!(
+            r#"
+            SELECT tags.name FROM item_tags
+            JOIN tags ON tags.id = item_tags.tag_id
+            WHERE item_tags.item_id = $1
+            ORDER BY tags.name
+            "#,
+            item_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(tags)
+    }
+
+    // 批量获取多个项目的标签，返回 (项目 ID, 标签名称)
+    pub async fn get_items_tags(
+        &self,
+        item_ids: &[String],
+    ) -> anyhow::Result<Vec<(String, String)>> {
+        let ids = serde_json::to_string(item_ids)?;
+        let tags = sqlx::query!(
+            r#"
+            SELECT item_tags.item_id, tags.name FROM item_tags
+            JOIN tags ON tags.id = item_tags.tag_id
+            WHERE item_tags.item_id IN (SELECT value FROM json_each($1))
+            ORDER BY tags.name
+            "#,
+            ids
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(tags.into_iter().map(|x| (x.item_id, x.name)).collect())
+    }
+
+    // 用给定的标签替换项目原有的标签，用户还没有的标签会被创建
+    pub async fn set_item_tags(
+        &self,
+        item_id: &str,
+        user_id: &str,
+        names: &[String],
+    ) -> anyhow::Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM item_tags
+            WHERE item_id = $1
+            "#,
+            item_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        let now = Local::now().naive_local();
+        for name in names {
+            let id = Uuid::now_v7().to_string();
+            sqlx::query!(
+                r#"
+                INSERT INTO tags (id, user_id, name, created_at)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT (user_id, name) DO NOTHING
+                "#,
+                id,
+                user_id,
+                name,
+                now
+            )
+            .execute(&mut *transaction)
+            .await?;
+            sqlx::query!(
+                r#"
+                INSERT OR IGNORE INTO item_tags (item_id, tag_id)
+                SELECT $1, id FROM tags
+                WHERE user_id = $2 AND name = $3
+                "#,
+                item_id,
+                user_id,
+                name
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    // 获取用户的所有标签以及各标签下的项目数量
+    pub async fn get_user_tags(&self, user_id: &str) -> anyhow::Result<Vec<(Tag, i64)>> {
+        let tags = sqlx::query!(
             r#"
-            SELECT * FROM items
-            WHERE creator = $1
-            ORDER BY created_at DESC
-            LIMIT $2 OFFSET $3
+            SELECT tags.id, tags.user_id, tags.name, tags.created_at,
+              (SELECT COUNT(*) FROM item_tags WHERE tag_id = tags.id) as "items!: i64"
+            FROM tags
+            WHERE user_id = $1
+            ORDER BY name
             "#,
-            user_id,
-            limit,
-            offset
+            user_id
         )
         .fetch_all(&self.pool)
         .await?;
-        Ok(items)
+        Ok(tags
+            .into_iter()
+            .map(|x| {
+                let tag = Tag {
+                    id: x.id,
+                    user_id: x.user_id,
+                    name: x.name,
+                    created_at: x.created_at,
+                };
+                (tag, x.items)
+            })
+            .collect())
     }
 
-    pub async fn count_user_items(&self, user_id: &str) -> anyhow::Result<i64> {
+    pub async fn get_tag(&self, id: &str) -> anyhow::Result<Option<Tag>> {
+        let tag = sqlx::query_as!(
+            Tag,
+            r#"
+            SELECT * FROM tags
+            WHERE id = $1
+            "#,
+            id
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(tag)
+    }
+
+    pub async fn get_tag_by_name(&self, user_id: &str, name: &str) -> anyhow::Result<Option<Tag>> {
+        let tag = sqlx::query_as!(
+            Tag,
+            r#"
+            SELECT * FROM tags
+            WHERE user_id = $1 AND name = $2
+            "#,
+            user_id,
+            name
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(tag)
+    }
+
+    pub async fn count_tag_items(&self, id: &str) -> anyhow::Result<i64> {
         let total = sqlx::query_scalar!(
             r#"
-            SELECT COUNT(*) as "count!: i64" FROM items
-            WHERE creator = $1
+            SELECT COUNT(*) as "count!: i64" FROM item_tags
+            WHERE tag_id = $1
             "#,
-            user_id
+            id
         )
         .fetch_one(&self.pool)
         .await?;
         Ok(total)
     }
 
-    pub async fn get_user_img_items(
+    pub async fn rename_tag(&self, id: &str, name: &str) -> anyhow::Result<Tag> {
+        let tag = sqlx::query_as!(
+            Tag,
+            r#"
+            UPDATE tags
+            SET name = $1
+            WHERE id = $2
+            RETURNING *
+            "#,
+            name,
+            id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(tag)
+    }
+
+    // 把 from 标签下的项目移到 into 标签下，然后删除 from 标签
+    pub async fn merge_tags(&self, from: &str, into: &str) -> anyhow::Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query!(
+            r#"
+            INSERT OR IGNORE INTO item_tags (item_id, tag_id)
+            SELECT item_id, $2 FROM item_tags
+            WHERE tag_id = $1
+            "#,
+            from,
+            into
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM item_tags
+            WHERE tag_id = $1
+            "#,
+            from
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM tags
+            WHERE id = $1
+            "#,
+            from
+        )
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    pub async fn remove_tag(&self, id: &str) -> anyhow::Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM item_tags
+            WHERE tag_id = $1
+            "#,
+            id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM tags
+            WHERE id = $1
+            "#,
+            id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    // 获取项目的历史版本，新的在前
+    pub async fn get_item_revisions(&self, item_id: &str) -> anyhow::Result<Vec<ItemRevision>> {
+        let revisions = sqlx::query_as!(
+            ItemRevision,
+            r#"
+            SELECT * FROM item_revisions
+            WHERE item_id = $1
+            ORDER BY revision DESC
+            "#,
+            item_id
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(revisions)
+    }
+
+    pub async fn get_item_revision(
         &self,
-        user_id: &str,
-        offset: i64,
-        limit: i64,
-    ) -> anyhow::Result<Vec<Item>> {
-        let items = sqlx::query_as!(
+        item_id: &str,
+        revision: i64,
+    ) -> anyhow::Result<Option<ItemRevision>> {
+        let revision = sqlx::query_as!(
+            ItemRevision,
+            r#"
+            SELECT * FROM item_revisions
+            WHERE item_id = $1 AND revision = $2
+            "#,
+            item_id,
+            revision
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(revision)
+    }
+
+    // 只保留最新的 keep 个历史版本，返回需要删除的文件
+    pub async fn prune_item_revisions(
+        &self,
+        item_id: &str,
+        keep: i64,
+    ) -> anyhow::Result<Vec<String>> {
+        let files = sqlx::query_scalar!(
+            r#"
+            DELETE FROM item_revisions
+            WHERE item_id = $1 AND id NOT IN (
+                SELECT id FROM item_revisions
+                WHERE item_id = $1
+                ORDER BY revision DESC
+                LIMIT $2
+            )
+            RETURNING data
+            "#,
+            item_id,
+            keep
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(files)
+    }
+
+    // 查找原路径上保留的跳转，返回项目现在的路径
+    pub async fn get_item_redirect(&self, short_path: &str) -> anyhow::Result<Option<String>> {
+        let path = sqlx::query_scalar!(
+            r#"
+            SELECT items.short_path FROM item_redirects
+            JOIN items ON items.id = item_redirects.item_id
+            WHERE item_redirects.short_path = $1
+            "#,
+            short_path
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(path)
+    }
+
+    pub async fn update_item_available(&self, id: &str, available: bool) -> anyhow::Result<()> {
+        if available {
+            sqlx::query!(
+                r#"
+            UPDATE items
+            SET available = $1, should_drop_at = NULL
+            WHERE id = $2
+            "#,
+                available,
+                id
+            )
+            .execute(&self.pool)
+            .await?;
+        } else {
+            let expiration_time = Local::now().naive_local() + chrono::Duration::days(7);
+            sqlx::query!(
+                r#"
+                UPDATE items
+                SET available = $1, should_drop_at = $2
+                WHERE id = $3
+                "#,
+                available,
+                expiration_time,
+                id
+            )
+            .execute(&self.pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    pub async fn update_item_data(&self, id: &str, data: &str) -> anyhow::Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE items
+            SET data = $1
+            WHERE id = $2
+            "#,
+            data,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn update_item_password(
+        &self,
+        id: &str,
+        password_hash: Option<&str>,
+    ) -> anyhow::Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE items
+            SET password_hash = $1
+            WHERE id = $2
+            "#,
+            password_hash,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // 校验项目的访问密码，成功时顺带将旧格式或参数过时的哈希升级为当前的 Argon2id 哈希
+    pub async fn check_item_password(&self, item: &Item, password: &str) -> anyhow::Result<bool> {
+        let Some(hash) = item.password_hash.as_deref() else {
+            return Ok(true);
+        };
+        let (matched, needs_rehash) = crate::util::verify_password(password, hash).await?;
+        if matched && needs_rehash {
+            let new_hash = crate::util::hash_password(password).await?;
+            self.update_item_password(&item.id, Some(&new_hash)).await?;
+        }
+        Ok(matched)
+    }
+
+    // 为游客项目设置管理密钥，数据库中只保存其哈希
+    pub async fn set_item_manage_key(&self, id: &str, key: &str) -> anyhow::Result<()> {
+        let hash = hash_token(key);
+        sqlx::query!(
+            r#"
+            UPDATE items
+            SET manage_key_hash = $1
+            WHERE id = $2
+            "#,
+            hash,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    pub fn check_item_manage_key(&self, item: &Item, key: &str) -> bool {
+        item.manage_key_hash.as_deref().is_some_and(|x| {
+            crate::util::constant_time_eq(x.as_bytes(), hash_token(key).as_bytes())
+        })
+    }
+
+    pub async fn get_item_by_manage_key(&self, key: &str) -> anyhow::Result<Option<Item>> {
+        let hash = hash_token(key);
+        let item = sqlx::query_as!(
             Item,
             r#"
             SELECT * FROM items
-            WHERE creator = $1 AND img = TRUE
-            ORDER BY created_at DESC
-            LIMIT $2 OFFSET $3
+            WHERE manage_key_hash = $1
+            "#,
+            hash
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(item)
+    }
+
+    // 将游客项目转为用户所有，认领后管理密钥失效
+    pub async fn claim_items(&self, ids: &[String], user_id: &str) -> anyhow::Result<()> {
+        let mut transaction = self.pool.begin().await?;
+        for id in ids {
+            sqlx::query!(
+                r#"
+                UPDATE items
+                SET creator = $1, manage_key_hash = NULL
+                WHERE id = $2
+                "#,
+                user_id,
+                id
+            )
+            .execute(&mut *transaction)
+            .await?;
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    pub async fn update_item_size(&self, id: &str, size: i64) -> anyhow::Result<()> {
+        sqlx::query!(
+            r#"
+            UPDATE items
+            SET size = $1
+            WHERE id = $2
+            "#,
+            size,
+            id
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // 补全旧版本创建的项目的大小，尚未上传的文件指向共用的占位文件，不计入占用
+    pub async fn fill_item_sizes(&self, fa: &FileAccessor) -> anyhow::Result<()> {
+        let items = sqlx::query!(
+            r#"
+            SELECT id, data FROM items
+            WHERE size IS NULL
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        for item in items {
+            let size = if item.data == "dummy_file.txt" {
+                0
+            } else {
+                fa.file_size(&item.data).await.unwrap_or(0)
+            };
+            self.update_item_size(&item.id, size).await?;
+        }
+        Ok(())
+    }
+
+    // 更新项目的搜索索引，代码项目的内容从文件中读取
+    pub async fn index_item(&self, item: &Item, fa: &FileAccessor) -> anyhow::Result<()> {
+        let data = match item.item_type {
+            ItemType::Code => fa.get_string(item.data.clone()).await,
+            ItemType::Link => Some(item.data.clone()),
+            ItemType::File => None,
+        };
+        let mut transaction = self.pool.begin().await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM item_search
+            WHERE item_id = $1
             "#,
-            user_id,
-            limit,
-            offset
+            item.id
         )
-        .fetch_all(&self.pool)
+        .execute(&mut *transaction)
         .await?;
-        Ok(items)
-    }
-
-    pub async fn count_user_img_items(&self, user_id: &str) -> anyhow::Result<i64> {
-        let total = sqlx::query_scalar!(
+        sqlx::query!(
             r#"
-            SELECT COUNT(*) as "count!: i64" FROM items
-            WHERE creator = $1 AND img = TRUE
+            INSERT INTO item_search (item_id, short_path, data, extra_data)
+            VALUES ($1, $2, $3, $4)
             "#,
-            user_id
+            item.id,
+            item.short_path,
+            data,
+            item.extra_data
         )
-        .fetch_one(&self.pool)
+        .execute(&mut *transaction)
         .await?;
-        Ok(total)
+        transaction.commit().await?;
+        Ok(())
     }
 
-    pub async fn get_all_items(&self, offset: i64, limit: i64) -> anyhow::Result<Vec<Item>> {
+    // 为还没有建立索引的项目（如旧版本创建的项目）建立搜索索引
+    pub async fn fill_search_index(&self, fa: &FileAccessor) -> anyhow::Result<()> {
         let items = sqlx::query_as!(
             Item,
             r#"
             SELECT * FROM items
-            ORDER BY created_at DESC
-            LIMIT $1 OFFSET $2
+            WHERE id NOT IN (SELECT item_id FROM item_search)
+            "#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        for item in items {
+            self.index_item(&item, fa).await?;
+        }
+        Ok(())
+    }
+
+    // 全文搜索项目，viewer 不为空时只搜索该用户创建的项目以及其所在组的项目
+    // 返回项目 ID 和匹配内容的片段，片段中匹配的部分用 \u{1} 和 \u{2} 包围
+    pub async fn search_items(
+        &self,
+        query: &str,
+        viewer: Option<&str>,
+        offset: i64,
+        limit: i64,
+    ) -> anyhow::Result<Vec<(String, String)>> {
+        // 作为短语搜索，避免用户输入被解析为 FTS5 的查询语法
+        let query = format!("\"{}\"", query.replace('"', "\"\""));
+        let hits = sqlx::query!(
+            r#"
+            SELECT
+              item_search.item_id as "item_id!: String",
+              snippet(item_search, -1, char(1), char(2), '…', 32) as "snippet!: String"
+            FROM item_search
+            JOIN items ON items.id = item_search.item_id
+            WHERE item_search MATCH $1
+              AND ($2 IS NULL OR items.creator = $2
+                OR items.group_id IN (SELECT group_id FROM group_members WHERE user_id = $2))
+            ORDER BY rank
+            LIMIT $3 OFFSET $4
             "#,
+            query,
+            viewer,
             limit,
             offset
         )
         .fetch_all(&self.pool)
         .await?;
-        Ok(items)
+        Ok(hits.into_iter().map(|x| (x.item_id, x.snippet)).collect())
     }
 
-    pub async fn count_all_items(&self) -> anyhow::Result<i64> {
+    pub async fn count_search_items(
+        &self,
+        query: &str,
+        viewer: Option<&str>,
+    ) -> anyhow::Result<i64> {
+        let query = format!("\"{}\"", query.replace('"', "\"\""));
         let total = sqlx::query_scalar!(
             r#"
-            SELECT COUNT(*) as "count!: i64" FROM items
-            "#
+            SELECT COUNT(*) as "count!: i64"
+            FROM item_search
+            JOIN items ON items.id = item_search.item_id
+            WHERE item_search MATCH $1
+              AND ($2 IS NULL OR items.creator = $2
+                OR items.group_id IN (SELECT group_id FROM group_members WHERE user_id = $2))
+            "#,
+            query,
+            viewer
         )
         .fetch_one(&self.pool)
         .await?;
         Ok(total)
     }
 
-    pub async fn item_exists(&self, path: &str) -> Result<bool> {
+    pub async fn get_item_by_id(&self, id: &str) -> anyhow::Result<Option<Item>> {
         let item = sqlx::query_as!(
             Item,
             r#"
             SELECT * FROM items
-            WHERE short_path = $1
-            "#,
-            path
-        )
-        .fetch_optional(&self.pool)
-        .await?;
-        Ok(item.is_some())
-    }
-
-    pub async fn remove_item(&self, id: &str) -> anyhow::Result<()> {
-        sqlx::query!(
-            r#"
-            DELETE FROM items
             WHERE id = $1
             "#,
             id
         )
-        .execute(&self.pool)
-        .await?;
-        Ok(())
-    }
-
-    pub async fn update_item_available(&self, id: &str, available: bool) -> anyhow::Result<()> {
-        if available {
-            sqlx::query!(
-                r#"
-            UPDATE items
-            SET available = $1, should_drop_at = NULL
-            WHERE id = $2
-            "#,
-                available,
-                id
-            )
-            .execute(&self.pool)
-            .await?;
-        } else {
-            let expiration_time = Local::now().naive_local() + chrono::Duration::days(7);
-            sqlx::query!(
-                r#"
-                UPDATE items
-                SET available = $1, should_drop_at = $2
-                WHERE id = $3
-                "#,
-                available,
-                expiration_time,
-                id
-            )
-            .execute(&self.pool)
-            .await?;
-        }
-        Ok(())
-    }
-
-    pub async fn update_item_data(&self, id: &str, data: &str) -> anyhow::Result<()> {
-        sqlx::query!(
-            r#"
-            UPDATE items
-            SET data = $1
-            WHERE id = $2
-            "#,
-            data,
-            id
-        )
-        .execute(&self.pool)
+        .fetch_optional(&self.pool)
         .await?;
-        Ok(())
+        Ok(item)
     }
 
     pub async fn update_item_img(&self, id: &str, img: bool) -> anyhow::Result<()> {
@@ -467,17 +2264,23 @@ impl DatabaseAccessor {
         Ok(log)
     }
 
-    pub async fn get_item_access_logs(&self, short_path: &str) -> anyhow::Result<Vec<AccessLog>> {
+    pub async fn get_item_access_logs(
+        &self,
+        item_id: &str,
+        offset: i64,
+        limit: i64,
+    ) -> anyhow::Result<Vec<AccessLog>> {
         let logs = sqlx::query_as!(
             AccessLog,
             r#"
-            SELECT al.*
-            FROM access_logs al
-            JOIN items i ON al.item_id = i.id
-            WHERE i.short_path = $1
-            ORDER BY al.accessed_at DESC
+            SELECT * FROM access_logs
+            WHERE item_id = $1
+            ORDER BY accessed_at DESC
+            LIMIT $2 OFFSET $3
             "#,
-            short_path
+            item_id,
+            limit,
+            offset
         )
         .fetch_all(&self.pool)
         .await?;
@@ -485,7 +2288,279 @@ impl DatabaseAccessor {
         Ok(logs)
     }
 
-    pub async fn remove_user(&self, user_id: &str) -> anyhow::Result<()> {
+    pub async fn count_item_access_logs(&self, item_id: &str) -> anyhow::Result<i64> {
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) as "count!: i64" FROM access_logs
+            WHERE item_id = $1
+            "#,
+            item_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(total)
+    }
+
+    pub async fn add_audit_log(
+        &self,
+        actor_id: &str,
+        user_id: &str,
+        action: &str,
+        detail: Option<&str>,
+        ip_address: Option<&str>,
+    ) -> Result<()> {
+        let id = Uuid::now_v7().to_string();
+        let now = Local::now().naive_local();
+        sqlx::query!(
+            r#"
+            INSERT INTO audit_logs (id, actor_id, user_id, action, detail, ip_address, created_at)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            "#,
+            id,
+            actor_id,
+            user_id,
+            action,
+            detail,
+            ip_address,
+            now
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    // 按时间倒序获取审计日志，可以只看某个管理员或被模拟用户的记录
+    pub async fn get_audit_logs(
+        &self,
+        user_id: Option<&str>,
+        offset: i64,
+        limit: i64,
+    ) -> Result<Vec<AuditLog>> {
+        let logs = sqlx::query_as!(
+            AuditLog,
+            r#"
+            SELECT * FROM audit_logs
+            WHERE $1 IS NULL OR actor_id = $1 OR user_id = $1
+            ORDER BY created_at DESC, id DESC
+            LIMIT $2 OFFSET $3
+            "#,
+            user_id,
+            limit,
+            offset
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        Ok(logs)
+    }
+
+    pub async fn count_audit_logs(&self, user_id: Option<&str>) -> Result<i64> {
+        let total = sqlx::query_scalar!(
+            r#"
+            SELECT COUNT(*) as "count!: i64" FROM audit_logs
+            WHERE $1 IS NULL OR actor_id = $1 OR user_id = $1
+            "#,
+            user_id
+        )
+        .fetch_one(&self.pool)
+        .await?;
+        Ok(total)
+    }
+
+    // 删除用户并按 disposal 处理其项目，同时吊销其所有会话与 API 令牌，返回需要删除的文件
+    pub async fn remove_user(
+        &self,
+        user_id: &str,
+        disposal: ItemDisposal,
+        reassign_to: Option<&str>,
+    ) -> anyhow::Result<Vec<String>> {
+        let mut transaction = self.pool.begin().await?;
+        let mut files = Vec::new();
+        match disposal {
+            ItemDisposal::Delete => {
+                sqlx::query!(
+                    r#"
+                    DELETE FROM item_search
+                    WHERE item_id IN (SELECT id FROM items WHERE creator = $1)
+                    "#,
+                    user_id
+                )
+                .execute(&mut *transaction)
+                .await?;
+                sqlx::query!(
+                    r#"
+                    DELETE FROM item_tags
+                    WHERE item_id IN (SELECT id FROM items WHERE creator = $1)
+                    "#,
+                    user_id
+                )
+                .execute(&mut *transaction)
+                .await?;
+                sqlx::query!(
+                    r#"
+                    DELETE FROM item_redirects
+                    WHERE item_id IN (SELECT id FROM items WHERE creator = $1)
+                    "#,
+                    user_id
+                )
+                .execute(&mut *transaction)
+                .await?;
+                sqlx::query!(
+                    r#"
+                    DELETE FROM tags
+                    WHERE user_id = $1
+                    "#,
+                    user_id
+                )
+                .execute(&mut *transaction)
+                .await?;
+                files = sqlx::query_scalar!(
+                    r#"
+                    DELETE FROM item_revisions
+                    WHERE item_id IN (SELECT id FROM items WHERE creator = $1)
+                    RETURNING data
+                    "#,
+                    user_id
+                )
+                .fetch_all(&mut *transaction)
+                .await?;
+                let items = sqlx::query!(
+                    r#"
+                    DELETE FROM items
+                    WHERE creator = $1
+                    RETURNING item_type, data
+                    "#,
+                    user_id
+                )
+                .fetch_all(&mut *transaction)
+                .await?;
+                // 尚未上传的文件指向共用的占位文件，不能删除
+                files.extend(
+                    items
+                        .into_iter()
+                        .filter(|x| x.item_type != "link" && x.data != "dummy_file.txt")
+                        .map(|x| x.data),
+                );
+            }
+            ItemDisposal::Reassign => {
+                let to = reassign_to.context("No user to reassign items to")?;
+                sqlx::query!(
+                    r#"
+                    UPDATE items
+                    SET creator = $1
+                    WHERE creator = $2
+                    "#,
+                    to,
+                    user_id
+                )
+                .execute(&mut *transaction)
+                .await?;
+                // 标签随项目一起转交，与对方已有的同名标签合并
+                sqlx::query!(
+                    r#"
+                    INSERT OR IGNORE INTO item_tags (item_id, tag_id)
+                    SELECT item_tags.item_id, target.id FROM item_tags
+                    JOIN tags AS source ON source.id = item_tags.tag_id
+                    JOIN tags AS target ON target.user_id = $1 AND target.name = source.name
+                    WHERE source.user_id = $2
+                    "#,
+                    to,
+                    user_id
+                )
+                .execute(&mut *transaction)
+                .await?;
+                sqlx::query!(
+                    r#"
+                    DELETE FROM item_tags
+                    WHERE tag_id IN (
+                      SELECT id FROM tags
+                      WHERE user_id = $2 AND name IN (SELECT name FROM tags WHERE user_id = $1)
+                    )
+                    "#,
+                    to,
+                    user_id
+                )
+                .execute(&mut *transaction)
+                .await?;
+                sqlx::query!(
+                    r#"
+                    DELETE FROM tags
+                    WHERE user_id = $2 AND name IN (SELECT name FROM tags WHERE user_id = $1)
+                    "#,
+                    to,
+                    user_id
+                )
+                .execute(&mut *transaction)
+                .await?;
+                sqlx::query!(
+                    r#"
+                    UPDATE tags
+                    SET user_id = $1
+                    WHERE user_id = $2
+                    "#,
+                    to,
+                    user_id
+                )
+                .execute(&mut *transaction)
+                .await?;
+            }
+            ItemDisposal::Archive => {
+                sqlx::query!(
+                    r#"
+                    UPDATE items
+                    SET available = FALSE, should_drop_at = NULL
+                    WHERE creator = $1
+                    "#,
+                    user_id
+                )
+                .execute(&mut *transaction)
+                .await?;
+            }
+        }
+        sqlx::query!(
+            r#"
+            DELETE FROM sessions
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM api_tokens
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM totp_recovery_codes
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM password_resets
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM group_members
+            WHERE user_id = $1
+            "#,
+            user_id
+        )
+        .execute(&mut *transaction)
+        .await?;
         sqlx::query!(
             r#"
             DELETE FROM users
@@ -493,9 +2568,10 @@ impl DatabaseAccessor {
             "#,
             user_id
         )
-        .execute(&self.pool)
+        .execute(&mut *transaction)
         .await?;
-        Ok(())
+        transaction.commit().await?;
+        Ok(files)
     }
 
     #[instrument(skip(self, fa))]
@@ -522,6 +2598,19 @@ impl DatabaseAccessor {
         .execute(&mut *transaction)
         .await?;
 
+        let revisions = sqlx::query_scalar!(
+            r#"
+            DELETE FROM item_revisions
+            WHERE item_id IN (
+              SELECT id FROM items
+              WHERE available = 0 AND should_drop_at <= datetime(?)
+            )
+            RETURNING data;
+            "#,
+            now
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
         let result = sqlx::query!(
             r#"
             DELETE FROM items
@@ -533,11 +2622,39 @@ impl DatabaseAccessor {
         )
         .fetch_all(&mut *transaction)
         .await?;
+        // 清除已删除项目的跳转和搜索索引
+        sqlx::query!(
+            r#"
+            DELETE FROM item_redirects
+            WHERE item_id NOT IN (SELECT id FROM items)
+            "#
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM item_search
+            WHERE item_id NOT IN (SELECT id FROM items)
+            "#
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            r#"
+            DELETE FROM item_tags
+            WHERE item_id NOT IN (SELECT id FROM items)
+            "#
+        )
+        .execute(&mut *transaction)
+        .await?;
 
         transaction.commit().await?;
         for data in result {
             fa.remove_file(&data.data).await?;
         }
+        for data in revisions {
+            fa.remove_file(&data).await?;
+        }
         Ok(())
     }
 }
@@ -587,6 +2704,12 @@ impl FileAccessor {
         Ok(())
     }
 
+    pub async fn file_size(&self, path: &str) -> Option<i64> {
+        let path = self.data_dir.join(path);
+        let metadata = tokio::fs::metadata(path).await.ok()?;
+        Some(metadata.len() as i64)
+    }
+
     pub async fn remove_file(&self, path: &str) -> anyhow::Result<()> {
         let path = self.data_dir.join(path);
         if path.exists() {
@@ -595,3 +2718,71 @@ impl FileAccessor {
         Ok(())
     }
 }
+
+#[cfg(test)]
+impl DatabaseAccessor {
+    // 测试用的数据库，每次调用都使用一个新的临时文件
+    pub async fn temporary() -> Self {
+        let path = std::env::temp_dir().join(format!("spectra-test-{}.db", Uuid::now_v7()));
+        Self::new(&format!("sqlite://{}?mode=rwc", path.display()))
+            .await
+            .unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn user(da: &DatabaseAccessor) -> User {
+        da.create_user("user-1", "Alice", "alice@example.com", "password", 0, None)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn password_reset_token_is_single_use() {
+        let da = DatabaseAccessor::temporary().await;
+        let user = user(&da).await;
+        da.create_password_reset("token-1", &user.id).await.unwrap();
+
+        let reset = da.use_password_reset("token-1").await.unwrap().unwrap();
+        assert_eq!(reset.user_id, user.id);
+        assert!(reset.used_at.is_some());
+        assert!(da.use_password_reset("token-1").await.unwrap().is_none());
+        assert!(da.use_password_reset("unknown").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn new_password_reset_replaces_unused_token() {
+        let da = DatabaseAccessor::temporary().await;
+        let user = user(&da).await;
+        da.create_password_reset("token-1", &user.id).await.unwrap();
+        da.create_password_reset("token-2", &user.id).await.unwrap();
+
+        assert!(da.use_password_reset("token-1").await.unwrap().is_none());
+        assert!(da.use_password_reset("token-2").await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn expired_password_reset_is_rejected_and_pruned() {
+        let da = DatabaseAccessor::temporary().await;
+        let user = user(&da).await;
+        let reset = da.create_password_reset("token-1", &user.id).await.unwrap();
+        let past = Local::now().naive_local() - chrono::Duration::minutes(1);
+        sqlx::query("UPDATE password_resets SET expires_at = $1 WHERE id = $2")
+            .bind(past)
+            .bind(&reset.id)
+            .execute(&da.pool)
+            .await
+            .unwrap();
+
+        assert!(da.use_password_reset("token-1").await.unwrap().is_none());
+        da.prune_sessions().await.unwrap();
+        let remaining: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM password_resets")
+            .fetch_one(&da.pool)
+            .await
+            .unwrap();
+        assert_eq!(remaining, 0);
+    }
+}
@@ -0,0 +1,195 @@
+use dashmap::DashMap;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+
+// 登录与项目密码的防暴力破解配置，对应配置文件中的 [login_limit] 段
+// 失败次数超过 *_free_attempts 后，每次失败的等待时间从 base_delay 开始翻倍，最长 max_delay；
+// 失败次数达到 *_lockout_attempts 后锁定 lockout_duration
+// 所有时间的单位都是秒
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LoginLimitConfig {
+    pub enabled: bool,
+    pub base_delay: u64,
+    pub max_delay: u64,
+    pub lockout_duration: u64,
+    // 超过这个时间没有新的失败记录后，失败次数清零
+    pub reset_after: u64,
+    // 针对单个账号（或单个带密码的项目）
+    pub account_free_attempts: u32,
+    pub account_lockout_attempts: u32,
+    // 针对单个 IP 地址
+    pub ip_free_attempts: u32,
+    pub ip_lockout_attempts: u32,
+}
+
+impl Default for LoginLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            base_delay: 1,
+            max_delay: 300,
+            lockout_duration: 900,
+            reset_after: 3600,
+            account_free_attempts: 5,
+            account_lockout_attempts: 20,
+            ip_free_attempts: 20,
+            ip_lockout_attempts: 100,
+        }
+    }
+}
+
+// 被限制的对象
+#[derive(Debug, Clone, Copy)]
+pub enum LimitKey<'a> {
+    Account(&'a str),
+    Item(&'a str),
+    // 找回密码的请求，按邮箱计数
+    PasswordReset(&'a str),
+    Ip(&'a str),
+}
+
+impl<'a> LimitKey<'a> {
+    // 同时限制某个对象和发起请求的 IP 地址
+    pub fn with_ip(self, ip: Option<&'a str>) -> Vec<Self> {
+        let mut keys = vec![self];
+        keys.extend(ip.map(Self::Ip));
+        keys
+    }
+
+    fn key(&self) -> String {
+        match self {
+            Self::Account(email) => format!("account:{}", email.to_lowercase()),
+            Self::Item(id) => format!("item:{}", id),
+            Self::PasswordReset(email) => format!("reset:{}", email.to_lowercase()),
+            Self::Ip(ip) => format!("ip:{}", ip),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Attempts {
+    failures: u32,
+    last_failure: Instant,
+    locked_until: Option<Instant>,
+}
+
+// 记录失败的尝试，只保存在内存中，重启后清空
+#[derive(Debug)]
+pub struct LoginLimiter {
+    config: LoginLimitConfig,
+    attempts: DashMap<String, Attempts>,
+}
+
+impl LoginLimiter {
+    pub fn new(config: LoginLimitConfig) -> Self {
+        Self {
+            config,
+            attempts: DashMap::new(),
+        }
+    }
+
+    fn thresholds(&self, key: &LimitKey) -> (u32, u32) {
+        match key {
+            LimitKey::Account(_) | LimitKey::Item(_) | LimitKey::PasswordReset(_) => (
+                self.config.account_free_attempts,
+                self.config.account_lockout_attempts,
+            ),
+            LimitKey::Ip(_) => (
+                self.config.ip_free_attempts,
+                self.config.ip_lockout_attempts,
+            ),
+        }
+    }
+
+    fn is_stale(&self, attempts: &Attempts, now: Instant) -> bool {
+        attempts.locked_until.is_none_or(|x| x <= now)
+            && now.duration_since(attempts.last_failure)
+                >= Duration::from_secs(self.config.reset_after)
+    }
+
+    // 检查是否允许再次尝试，不允许时返回需要等待的秒数
+    pub fn check(&self, keys: &[LimitKey]) -> Result<(), u64> {
+        if !self.config.enabled {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let mut wait = Duration::ZERO;
+        for key in keys {
+            let Some(attempts) = self.attempts.get(&key.key()) else {
+                continue;
+            };
+            if self.is_stale(&attempts, now) {
+                continue;
+            }
+            if let Some(locked_until) = attempts.locked_until
+                && locked_until > now
+            {
+                wait = wait.max(locked_until - now);
+                continue;
+            }
+            let (free_attempts, _) = self.thresholds(key);
+            if attempts.failures >= free_attempts {
+                let exponent = (attempts.failures - free_attempts).min(31);
+                let delay = self
+                    .config
+                    .base_delay
+                    .saturating_mul(1 << exponent)
+                    .min(self.config.max_delay);
+                let allowed_at = attempts.last_failure + Duration::from_secs(delay);
+                if allowed_at > now {
+                    wait = wait.max(allowed_at - now);
+                }
+            }
+        }
+        if wait.is_zero() {
+            Ok(())
+        } else {
+            // 向上取整，避免返回 0 秒
+            Err(wait.as_secs() + u64::from(wait.subsec_nanos() > 0))
+        }
+    }
+
+    pub fn record_failure(&self, keys: &[LimitKey]) {
+        if !self.config.enabled {
+            return;
+        }
+        let now = Instant::now();
+        for key in keys {
+            let (_, lockout_attempts) = self.thresholds(key);
+            let mut attempts = self.attempts.entry(key.key()).or_insert(Attempts {
+                failures: 0,
+                last_failure: now,
+                locked_until: None,
+            });
+            if self.is_stale(&attempts, now) {
+                attempts.failures = 0;
+                attempts.locked_until = None;
+            }
+            attempts.failures += 1;
+            attempts.last_failure = now;
+            if attempts.failures >= lockout_attempts {
+                attempts.locked_until =
+                    Some(now + Duration::from_secs(self.config.lockout_duration));
+            }
+        }
+    }
+
+    // 验证成功后清除记录。IP 的记录不会因此清除，避免攻击者用自己的账号重置限制
+    pub fn record_success(&self, keys: &[LimitKey]) {
+        for key in keys {
+            if !matches!(key, LimitKey::Ip(_)) {
+                self.attempts.remove(&key.key());
+            }
+        }
+    }
+
+    // 清除已经失效的记录
+    pub fn prune(&self) -> usize {
+        let now = Instant::now();
+        let before = self.attempts.len();
+        self.attempts
+            .retain(|_, attempts| !self.is_stale(attempts, now));
+        before - self.attempts.len()
+    }
+}
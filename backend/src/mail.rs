@@ -0,0 +1,122 @@
+use crate::types::SmtpConfig;
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+// 通过配置的 SMTP 服务器发送一封纯文本邮件
+pub async fn send_mail(
+    config: &SmtpConfig,
+    to: &str,
+    subject: &str,
+    body: String,
+) -> anyhow::Result<()> {
+    if !config.enabled {
+        anyhow::bail!("SMTP is not enabled");
+    }
+    let builder = match config.security.as_str() {
+        "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?,
+        "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?,
+        // 不加密，只应该用于本机或内网中的邮件服务器
+        _ => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+    };
+    let mut builder = builder.port(config.port);
+    if !config.username.is_empty() {
+        builder = builder.credentials(Credentials::new(
+            config.username.clone(),
+            config.password.clone(),
+        ));
+    }
+    let mailer = builder.build();
+
+    let message = Message::builder()
+        .from(config.from.parse()?)
+        .to(to.parse()?)
+        .subject(subject)
+        .header(ContentType::TEXT_PLAIN)
+        .body(body)?;
+    mailer.send(message).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    // 最简单的 SMTP 服务器，接受一封邮件并返回 DATA 部分的内容
+    async fn smtp_sink(listener: TcpListener) -> String {
+        let (socket, _) = listener.accept().await.unwrap();
+        let (read, mut write) = socket.into_split();
+        let mut lines = BufReader::new(read).lines();
+        write.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+        let mut data = String::new();
+        let mut in_data = false;
+        while let Some(line) = lines.next_line().await.unwrap() {
+            if in_data {
+                if line == "." {
+                    in_data = false;
+                    write.write_all(b"250 OK\r\n").await.unwrap();
+                } else {
+                    data.push_str(&line);
+                    data.push('\n');
+                }
+                continue;
+            }
+            let command = line.to_ascii_uppercase();
+            let reply: &[u8] = if command.starts_with("DATA") {
+                in_data = true;
+                b"354 Go ahead\r\n"
+            } else if command.starts_with("QUIT") {
+                write.write_all(b"221 Bye\r\n").await.unwrap();
+                break;
+            } else {
+                b"250 OK\r\n"
+            };
+            write.write_all(reply).await.unwrap();
+        }
+        data
+    }
+
+    fn config(port: u16) -> SmtpConfig {
+        SmtpConfig {
+            enabled: true,
+            host: "127.0.0.1".to_string(),
+            port,
+            from: "Spectra <noreply@example.com>".to_string(),
+            security: "none".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn delivers_mail_to_smtp_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let sink = tokio::spawn(smtp_sink(listener));
+
+        send_mail(
+            &config(port),
+            "alice@example.com",
+            "Reset your password",
+            "Open this link to continue".to_string(),
+        )
+        .await
+        .unwrap();
+        let data = sink.await.unwrap();
+        assert!(data.contains("From: Spectra <noreply@example.com>"));
+        assert!(data.contains("To: alice@example.com"));
+        assert!(data.contains("Subject: Reset your password"));
+        assert!(data.contains("Open this link to continue"));
+    }
+
+    #[tokio::test]
+    async fn refuses_to_send_when_disabled() {
+        let config = SmtpConfig {
+            enabled: false,
+            ..config(25)
+        };
+        let result = send_mail(&config, "alice@example.com", "Subject", String::new()).await;
+        assert!(result.is_err());
+    }
+}
@@ -2,9 +2,7 @@ use axum::extract::DefaultBodyLimit;
 use axum::http::header;
 use axum_extra::extract::cookie::Key;
 use clap::{Command, arg, crate_version, value_parser};
-use dashmap::DashMap;
 use serde::Deserialize;
-use sha2::{Digest, Sha256};
 use shadow_rs::shadow;
 use std::fs::{self, read_to_string};
 use std::net::{IpAddr, SocketAddr};
@@ -19,14 +17,17 @@ use tracing_subscriber::EnvFilter;
 use tracing_subscriber::util::SubscriberInitExt;
 
 mod data;
+mod limiter;
+mod mail;
 mod service;
 mod types;
 mod util;
 
 use crate::data::{DatabaseAccessor, FileAccessor};
+use crate::limiter::{LoginLimitConfig, LoginLimiter};
 use crate::service::frontend::make_frontend_router;
 use crate::service::main::main_service;
-use crate::types::AppState;
+use crate::types::{AppState, LdapConfig, OidcConfig, ToPermission, UserPermission};
 
 shadow!(shadow);
 const DEFAULT_CONFIG_FILE: &str = include_str!("../assets/config_example.toml");
@@ -41,9 +42,39 @@ struct ServiceConfig {
     port: u16,
 }
 
+// 密码哈希（Argon2id）参数，默认值取自 OWASP 的推荐配置
+#[derive(Deserialize)]
+#[serde(default)]
+struct PasswordConfig {
+    // 内存开销，单位 KiB
+    memory_cost: u32,
+    // 迭代次数
+    time_cost: u32,
+    // 并行度
+    parallelism: u32,
+}
+
+impl Default for PasswordConfig {
+    fn default() -> Self {
+        Self {
+            memory_cost: 19456,
+            time_cost: 2,
+            parallelism: 1,
+        }
+    }
+}
+
 #[derive(Deserialize)]
 struct AppConfig {
     service: ServiceConfig,
+    #[serde(default)]
+    password: PasswordConfig,
+    #[serde(default)]
+    oidc: Option<OidcConfig>,
+    #[serde(default)]
+    ldap: Option<LdapConfig>,
+    #[serde(default)]
+    login_limit: LoginLimitConfig,
 }
 
 #[tokio::main]
@@ -75,7 +106,11 @@ async fn main() {
                         .default_value("config.toml"),
                 ),
         )
-        .subcommand(Command::new("reset-admin-password").about("Reset the admin password"))
+        .subcommand(
+            Command::new("reset-admin-password")
+                .about("Reset the password of an administrator (the first owner by default)")
+                .arg(arg!([EMAIL] "Email of the administrator")),
+        )
         .subcommand(
             Command::new("generate-cookie-key")
                 .about("Generate a random string appropriate to use as cookie key"),
@@ -250,6 +285,16 @@ async fn main() {
         }
     };
 
+    let password_config = &app_config.password;
+    if let Err(e) = util::init_password_hasher(
+        password_config.memory_cost,
+        password_config.time_cost,
+        password_config.parallelism,
+    ) {
+        error!("{}", e);
+        std::process::exit(1);
+    }
+
     // 确保数据目录存在
     let data_dir = &service_config.data_dir;
     if let Err(e) = fs::create_dir_all(data_dir) {
@@ -331,24 +376,120 @@ async fn main() {
             },
         };
 
+        // SMTP 配置是后来加入的，不存在时使用默认值（不启用）
+        let mut smtp_config = crate::types::SmtpConfig {
+            port: 587,
+            security: "starttls".to_string(),
+            ..Default::default()
+        };
+        if let Ok(Some(val)) = da.get_sys_config("smtp_enabled").await {
+            smtp_config.enabled = val == "true";
+        }
+        if let Ok(Some(val)) = da.get_sys_config("smtp_host").await {
+            smtp_config.host = val;
+        }
+        if let Ok(Some(val)) = da.get_sys_config("smtp_port").await
+            && let Ok(port) = val.parse()
+        {
+            smtp_config.port = port;
+        }
+        if let Ok(Some(val)) = da.get_sys_config("smtp_username").await {
+            smtp_config.username = val;
+        }
+        if let Ok(Some(val)) = da.get_sys_config("smtp_password").await {
+            smtp_config.password = val;
+        }
+        if let Ok(Some(val)) = da.get_sys_config("smtp_from").await {
+            smtp_config.from = val;
+        }
+        if let Ok(Some(val)) = da.get_sys_config("smtp_security").await {
+            smtp_config.security = val;
+        }
+
+        // 开放注册默认关闭，开启后注册的用户默认拥有创建项目相关的所有权限
+        let mut registration_config = crate::types::RegistrationConfig {
+            descriptor: UserPermission::to_descriptor(&[
+                UserPermission::Link,
+                UserPermission::Code,
+                UserPermission::File,
+                UserPermission::CustomPath,
+                UserPermission::ItemPassword,
+                UserPermission::NoExpiry,
+            ]),
+            ..Default::default()
+        };
+        if let Ok(Some(val)) = da.get_sys_config("registration_enabled").await {
+            registration_config.enabled = val == "true";
+        }
+        if let Ok(Some(val)) = da.get_sys_config("registration_approval").await {
+            registration_config.approval = val == "true";
+        }
+        if let Ok(Some(val)) = da.get_sys_config("registration_domains").await {
+            registration_config.domains = val
+                .split(',')
+                .map(|x| x.trim().to_string())
+                .filter(|x| !x.is_empty())
+                .collect();
+        }
+        if let Ok(Some(val)) = da.get_sys_config("registration_descriptor").await
+            && let Ok(descriptor) = val.parse()
+        {
+            registration_config.descriptor = descriptor;
+        }
+
+        // 默认配额，未设置时不限制
+        let mut quota_config = crate::types::QuotaConfig::default();
+        for (key, value) in [
+            ("quota_storage", &mut quota_config.storage),
+            ("quota_items", &mut quota_config.items),
+            ("quota_file_size", &mut quota_config.file_size),
+            ("quota_expiry", &mut quota_config.expiry),
+        ] {
+            if let Ok(Some(val)) = da.get_sys_config(key).await {
+                *value = val.parse().ok();
+            }
+        }
+
+        // 代码项目默认保留最近 20 个历史版本
+        let max_revisions = match da.get_sys_config("max_revisions").await {
+            Ok(Some(val)) => val.parse().unwrap_or(20),
+            _ => 20,
+        };
+
         let runtime_config = crate::types::AppRuntimeConfig {
             setup,
             cookie_key: cookie_key.clone(),
             refresh_time,
             domain: domain.clone(),
             turnstile: turnstile_config,
+            smtp: smtp_config,
+            registration: registration_config,
+            quota: quota_config,
+            max_revisions,
         };
 
         AppState {
             database_accessor: da,
             file_accessor: FileAccessor::new(data_dir.to_string()),
-            user_tokens: Arc::new(DashMap::new()),
             runtime_config: Arc::new(arc_swap::ArcSwap::from_pointee(runtime_config)),
             cookie_key: Arc::new(arc_swap::ArcSwap::from_pointee(Key::from(
                 cookie_key.as_bytes(),
             ))),
             cron_scheduler: JobScheduler::new().await.unwrap(),
             cron_job_id: Arc::new(arc_swap::ArcSwap::from_pointee(None)),
+            oidc: app_config.oidc.clone().map(Arc::new),
+            ldap: app_config.ldap.clone().map(Arc::new),
+            login_limiter: Arc::new(LoginLimiter::new(app_config.login_limit.clone())),
+            // 每次启动时随机生成，自动化测试需要固定的代码时可以用 SPECTRA_SETUP_CODE 环境变量指定
+            setup_code: Arc::new(
+                std::env::var("SPECTRA_SETUP_CODE")
+                    .ok()
+                    .map(|x| x.trim().to_string())
+                    .filter(|x| !x.is_empty())
+                    .unwrap_or_else(|| {
+                        util::random_string(16, Some("ABCDEFGHJKLMNPQRSTUVWXYZ23456789"))
+                    }),
+            ),
         }
     };
 
@@ -356,7 +497,7 @@ async fn main() {
 
     if state
         .database_accessor
-        .admin_user_exists()
+        .owner_exists()
         .await
         .is_ok_and(|x| !x)
     {
@@ -367,10 +508,11 @@ async fn main() {
             util::random_password()
         };
         println!("[!] Generating new admin password: {}", new_password);
+        let id = uuid::Uuid::new_v4().to_string();
         let _ = state
             .database_accessor
             .create_user(
-                "00000000-0000-0000-0000-000000000000",
+                &id,
                 "admin",
                 "admin@example.com",
                 &new_password,
@@ -382,19 +524,46 @@ async fn main() {
                 error!("Failed to create admin user: {}", e);
                 std::process::exit(1);
             });
-    } else if let Some(("reset-admin-password", _)) = matches.subcommand() {
+        let _ = state
+            .database_accessor
+            .grant_owner(&id)
+            .await
+            .is_err_and(|e| {
+                error!("Failed to create admin user: {}", e);
+                std::process::exit(1);
+            });
+    } else if let Some(("reset-admin-password", sub_matches)) = matches.subcommand() {
+        let da = &state.database_accessor;
+        // 指定邮箱时重置该管理员的密码，否则重置最早的所有者
+        let user = match sub_matches.get_one::<String>("EMAIL") {
+            Some(email) => da.get_user_by_email(email).await,
+            None => da.get_owners().await.map(|x| x.into_iter().next()),
+        }
+        .unwrap_or_else(|e| {
+            error!("Failed to query admin user: {}", e);
+            std::process::exit(1);
+        });
+        let Some(user) = user.filter(|x| x.descriptor.contains(UserPermission::Manage)) else {
+            error!("No such administrator");
+            std::process::exit(1);
+        };
         let new_password = if cfg!(debug_assertions) {
             "1234567890".to_string()
         } else {
             util::random_password()
         };
-        println!("[!] Generating new admin password: {}", new_password);
-        let _ = state
-            .database_accessor
-            .change_user_password(
-                "00000000-0000-0000-0000-000000000000",
-                format!("{:x}", Sha256::digest(new_password.as_bytes())).as_str(),
-            )
+        println!(
+            "[!] Generating new password for {}: {}",
+            user.email, new_password
+        );
+        let password_hash = util::hash_password(&new_password)
+            .await
+            .unwrap_or_else(|e| {
+                error!("Failed to hash admin password: {}", e);
+                std::process::exit(1);
+            });
+        let _ = da
+            .change_user_password(&user.id, &password_hash)
             .await
             .is_err_and(|e| {
                 error!("Failed to reset admin password: {}", e);
@@ -403,23 +572,48 @@ async fn main() {
         std::process::exit(0);
     }
 
+    if !state.runtime_config.load().setup {
+        println!(
+            "[!] Setup code (required by the setup wizard): {}",
+            state.setup_code
+        );
+    }
+
+    if let Err(e) = state
+        .database_accessor
+        .fill_item_sizes(&state.file_accessor)
+        .await
+    {
+        error!("Failed to fill item sizes: {}", e);
+    }
+    if let Err(e) = state
+        .database_accessor
+        .fill_search_index(&state.file_accessor)
+        .await
+    {
+        error!("Failed to fill search index: {}", e);
+    }
+
     {
         let scheduler = state.cron_scheduler.clone();
-        let outer_tokens = Arc::clone(&state.user_tokens);
+        let session_da = state.database_accessor.clone();
+        let limiter = state.login_limiter.clone();
         if let Err(e) = scheduler
             .add(
                 Job::new_async("0 0/30 * * * ?", move |_, _| {
-                    info!("Triggered scheduled task: clearing expired tokens...");
-                    let inner_tokens = Arc::clone(&outer_tokens);
+                    info!("Triggered scheduled task: pruning expired sessions...");
+                    let da = session_da.clone();
+                    let limiter = limiter.clone();
                     Box::pin(async move {
-                        service::scheduled::clear_expired_token(inner_tokens).await;
+                        service::scheduled::prune_expired_sessions(da).await;
+                        service::scheduled::prune_login_attempts(limiter);
                     })
                 })
                 .unwrap(),
             )
             .await
         {
-            error!("Failed to add token clearing job to scheduler: {}", e);
+            error!("Failed to add session pruning job to scheduler: {}", e);
         }
         let da_clone = state.database_accessor.clone();
         let fa_clone = state.file_accessor.clone();
@@ -458,11 +652,18 @@ async fn main() {
             axum::http::Method::GET,
             axum::http::Method::POST,
             axum::http::Method::PUT,
+            axum::http::Method::PATCH,
             axum::http::Method::DELETE,
             axum::http::Method::HEAD,
             axum::http::Method::OPTIONS,
         ])
-        .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION, header::ACCEPT]);
+        .allow_headers([
+            header::CONTENT_TYPE,
+            header::AUTHORIZATION,
+            header::ACCEPT,
+            header::HeaderName::from_static("x-csrf-token"),
+            header::HeaderName::from_static("x-manage-key"),
+        ]);
 
     let app = make_frontend_router()
         .layer(axum::middleware::from_fn_with_state(
@@ -473,7 +674,8 @@ async fn main() {
         .layer(CompressionLayer::new())
         .layer(cors)
         .layer(TraceLayer::new_for_http())
-        .layer(DefaultBodyLimit::max(1024 * 1024 * 1024 * 1024))
+        // 除文件上传外的请求都是 JSON，最大 16MB
+        .layer(DefaultBodyLimit::max(16 * 1024 * 1024))
         .with_state(state);
 
     // 绑定服务器地址
@@ -483,5 +685,10 @@ async fn main() {
 
     // 启动服务器
     let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await
+    .unwrap();
 }
@@ -0,0 +1,367 @@
+use crate::limiter::LimitKey;
+use crate::service::api::result::ApiError;
+use crate::types::{
+    ApiToken, AppState, ClientInfo, Item, Session, ToPermission, User, UserPermission,
+};
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::request::Parts;
+use axum::http::{HeaderMap, header};
+use axum_extra::extract::PrivateCookieJar;
+use axum_extra::extract::cookie::Key;
+use std::convert::Infallible;
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+use std::ops::Deref;
+
+impl<S: Send + Sync> FromRequestParts<S> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let ip_address = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string());
+        let user_agent = parts
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string());
+        Ok(Self {
+            ip_address,
+            user_agent,
+        })
+    }
+}
+
+// 游客项目的管理密钥通过这个请求头提供
+pub const MANAGE_KEY_HEADER: &str = "X-Manage-Key";
+
+pub fn manage_key(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(MANAGE_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(|key| key.trim())
+        .filter(|key| !key.is_empty())
+}
+
+// 从 Authorization 头中取出 Bearer 令牌（如果有）
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(|token| token.trim())
+        .filter(|token| !token.is_empty())
+}
+
+// 请求者用来证明身份的凭证
+#[derive(Debug, Clone)]
+pub enum Credential {
+    // 浏览器登录后得到的会话
+    Session(Session),
+    // 通过 Authorization: Bearer 提供的 API 令牌
+    ApiToken(ApiToken),
+}
+
+// 已登录的用户。作为提取器使用时，未登录的请求会直接返回 401
+//
+// 认证规则：
+// - 提供了 Bearer 令牌时只使用 API 令牌认证，令牌无效或已过期即视为未登录，
+//   返回的用户权限会被限制在令牌的权限范围内；
+// - 否则使用 cookie 中的会话，过期的会话、等待两步验证的会话以及游客的临时会话都不算登录；
+// - 用户必须存在且处于启用状态（不是等待审核等状态）
+#[derive(Debug)]
+pub struct CurrentUser {
+    pub user: User,
+    pub credential: Credential,
+}
+
+impl CurrentUser {
+    // 当前使用的会话，通过 API 令牌认证时为 None
+    pub fn session(&self) -> Option<&Session> {
+        match &self.credential {
+            Credential::Session(session) => Some(session),
+            Credential::ApiToken(_) => None,
+        }
+    }
+
+    // 管理员模拟登录时，发起模拟的管理员 ID
+    pub fn impersonator_id(&self) -> Option<&str> {
+        self.session().and_then(|x| x.impersonator_id.as_deref())
+    }
+
+    // 修改密码、两步验证等敏感操作不允许在模拟登录期间进行
+    pub fn deny_impersonation(&self) -> Result<(), ApiError> {
+        if self.impersonator_id().is_some() {
+            return Err(ApiError::new(
+                403,
+                "Not allowed while impersonating a user".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    // 同样的敏感操作也不允许使用 API 令牌进行，令牌的权限范围只限制 UserPermission，
+    // 泄露的令牌不应该能够接管账户
+    pub fn deny_api_token(&self) -> Result<(), ApiError> {
+        if matches!(self.credential, Credential::ApiToken(_)) {
+            return Err(ApiError::new(
+                403,
+                "Not allowed when using an API token".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn has_permission(&self, permission: UserPermission) -> bool {
+        self.user.descriptor.contains(permission)
+    }
+
+    // 是否可以操作属于 user_id 的账户：本人或者拥有 ManageUsers 权限
+    pub fn can_access(&self, user_id: &str) -> bool {
+        self.user.id == user_id || self.has_permission(UserPermission::ManageUsers)
+    }
+
+    // 只能把自己拥有的权限授予他人，避免管理用户的权限被用来提升权限
+    pub fn check_grantable(&self, permissions: &[UserPermission]) -> Result<(), ApiError> {
+        if let Some(permission) = permissions
+            .iter()
+            .find(|x| !self.has_permission((*x).clone()))
+        {
+            return Err(ApiError::new(
+                403,
+                format!("Cannot grant permission {:?}", permission),
+            ));
+        }
+        Ok(())
+    }
+
+    // 管理其他用户时，对方的权限必须都是自己拥有的，避免只有 ManageUsers 权限的用户接管管理员账户。
+    // 对方通过组获得的权限同样计算在内
+    pub async fn check_manageable(&self, state: &AppState, user: &User) -> Result<(), ApiError> {
+        if self.user.id == user.id {
+            return Ok(());
+        }
+        let descriptor = user.descriptor
+            | state
+                .database_accessor
+                .get_user_group_descriptor(&user.id)
+                .await?;
+        if UserPermission::from_i64(descriptor)
+            .into_iter()
+            .any(|x| !self.has_permission(x))
+        {
+            return Err(ApiError::new(
+                403,
+                "Cannot manage a user with more permissions".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl CurrentUser {
+    // 测试用的已登录用户，使用新建的会话，和 authenticate 一样带上组授予的权限
+    pub async fn temporary(state: &AppState, user_id: &str) -> Self {
+        let da = &state.database_accessor;
+        let session = da
+            .create_session(
+                &uuid::Uuid::now_v7().to_string(),
+                user_id,
+                false,
+                false,
+                &ClientInfo::default(),
+            )
+            .await
+            .unwrap();
+        let mut user = da.get_user_by_id(user_id).await.unwrap().unwrap();
+        user.descriptor |= da.get_user_group_descriptor(user_id).await.unwrap();
+        Self {
+            user,
+            credential: Credential::Session(session),
+        }
+    }
+}
+
+impl FromRequestParts<AppState> for CurrentUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let jar = PrivateCookieJar::<Key>::from_request_parts(parts, state)
+            .await
+            .unwrap_or_else(|e| match e {});
+        match authenticate(state, &jar, &parts.headers).await? {
+            Some(user) => Ok(user),
+            None => Err(ApiError::new(401, "Unauthorized".to_string())),
+        }
+    }
+}
+
+// 可选的登录用户，未登录时为 None
+pub struct OptionalUser(pub Option<CurrentUser>);
+
+impl FromRequestParts<AppState> for OptionalUser {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let jar = PrivateCookieJar::<Key>::from_request_parts(parts, state)
+            .await
+            .unwrap_or_else(|e| match e {});
+        Ok(Self(authenticate(state, &jar, &parts.headers).await?))
+    }
+}
+
+// 在类型中标记接口需要的权限，配合 RequirePermission 使用
+pub trait Permission {
+    const PERMISSION: UserPermission;
+}
+
+pub struct Manage;
+impl Permission for Manage {
+    const PERMISSION: UserPermission = UserPermission::Manage;
+}
+
+pub struct ManageUsers;
+impl Permission for ManageUsers {
+    const PERMISSION: UserPermission = UserPermission::ManageUsers;
+}
+
+pub struct ViewAllItems;
+impl Permission for ViewAllItems {
+    const PERMISSION: UserPermission = UserPermission::ViewAllItems;
+}
+
+// 拥有权限 P 的登录用户，未登录时返回 401，没有权限时返回 403
+pub struct RequirePermission<P: Permission>(pub CurrentUser, PhantomData<P>);
+
+impl<P: Permission> Deref for RequirePermission<P> {
+    type Target = CurrentUser;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<P: Permission> FromRequestParts<AppState> for RequirePermission<P> {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let OptionalUser(user) = OptionalUser::from_request_parts(parts, state).await?;
+        Ok(Self(require_permission(user, P::PERMISSION)?, PhantomData))
+    }
+}
+
+// 要求请求者已登录并拥有指定权限，用于需要在处理函数中按条件检查权限的情况
+pub fn require_permission(
+    user: Option<CurrentUser>,
+    permission: UserPermission,
+) -> Result<CurrentUser, ApiError> {
+    let Some(user) = user else {
+        return Err(ApiError::new(401, "Unauthorized".to_string()));
+    };
+    if !user.has_permission(permission) {
+        return Err(ApiError::new(403, "No sufficient permissions".to_string()));
+    }
+    Ok(user)
+}
+
+async fn authenticate(
+    state: &AppState,
+    jar: &PrivateCookieJar,
+    headers: &HeaderMap,
+) -> anyhow::Result<Option<CurrentUser>> {
+    let da = &state.database_accessor;
+    let (user_id, credential) = if let Some(token) = bearer_token(headers) {
+        let Some(api_token) = da.use_api_token(token).await? else {
+            return Ok(None);
+        };
+        if api_token.is_expired() {
+            return Ok(None);
+        }
+        (api_token.user_id.clone(), Credential::ApiToken(api_token))
+    } else {
+        let Some(session) = current_session(state, jar).await else {
+            return Ok(None);
+        };
+        if session.temporary {
+            return Ok(None);
+        }
+        (session.user_id.clone(), Credential::Session(session))
+    };
+
+    let Some(mut user) = da.get_user_by_id(&user_id).await? else {
+        return Ok(None);
+    };
+    // 等待审核的用户不能使用任何需要登录的功能
+    if !user.is_active() {
+        return Ok(None);
+    }
+    // 用户同时拥有所在的组授予的权限
+    user.descriptor |= da.get_user_group_descriptor(&user.id).await?;
+    if let Credential::ApiToken(api_token) = &credential {
+        // Manage 包含其他所有权限，这时令牌本身的权限就是实际生效的权限
+        if user.descriptor & UserPermission::Manage.as_i64() != 0 {
+            user.descriptor = api_token.descriptor;
+        } else {
+            user.descriptor &= api_token.descriptor;
+        }
+    }
+    Ok(Some(CurrentUser { user, credential }))
+}
+
+// 获取 cookie 中的令牌对应的会话（如果有效）
+pub async fn current_session(state: &AppState, jar: &PrivateCookieJar) -> Option<Session> {
+    let token = jar.get("token")?;
+    state
+        .database_accessor
+        .get_active_session(token.value())
+        .await
+        .ok()?
+}
+
+// 获取游客通过 Turnstile 验证后得到的临时会话（如果有效）
+pub async fn guest_session(state: &AppState, jar: &PrivateCookieJar) -> Option<Session> {
+    current_session(state, jar).await.filter(|x| x.temporary)
+}
+
+// 项目密码的校验结果
+pub enum ItemPasswordCheck {
+    Matched,
+    Mismatched,
+    // 失败次数过多，需要等待的秒数
+    Limited(u64),
+}
+
+// 带防暴力破解限制地校验项目密码，同时按项目和 IP 地址计数
+pub async fn check_item_password(
+    state: &AppState,
+    item: &Item,
+    password: &str,
+    ip: Option<&str>,
+) -> anyhow::Result<ItemPasswordCheck> {
+    let limit_keys = LimitKey::Item(&item.id).with_ip(ip);
+    if let Err(wait) = state.login_limiter.check(&limit_keys) {
+        return Ok(ItemPasswordCheck::Limited(wait));
+    }
+    if state
+        .database_accessor
+        .check_item_password(item, password)
+        .await?
+    {
+        state.login_limiter.record_success(&limit_keys);
+        Ok(ItemPasswordCheck::Matched)
+    } else {
+        state.login_limiter.record_failure(&limit_keys);
+        Ok(ItemPasswordCheck::Mismatched)
+    }
+}
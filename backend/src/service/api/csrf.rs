@@ -0,0 +1,115 @@
+use crate::service::api::auth::bearer_token;
+use crate::service::api::result::ApiError;
+use crate::types::AppState;
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::{HeaderMap, Method, header};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum_extra::extract::CookieJar;
+use axum_extra::extract::cookie::{Cookie, SameSite};
+
+pub const CSRF_COOKIE: &str = "csrf_token";
+pub const CSRF_HEADER: &str = "X-CSRF-Token";
+
+// 使用 cookie 认证的请求会由浏览器自动带上凭据，需要防止其他网站伪造修改数据的请求：
+// 1. Origin（没有时使用 Referer）必须与配置的 domain 一致；
+// 2. 带有登录 cookie 时，X-CSRF-Token 头必须与 csrf_token cookie 一致（双重提交）。
+// 使用 Bearer 令牌认证的请求不依赖 cookie，不做检查
+pub async fn csrf_protection(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let safe = matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    );
+    if !safe
+        && bearer_token(request.headers()).is_none()
+        && let Err(e) = check_request(&state, &jar, request.headers())
+    {
+        return e.into_response();
+    }
+
+    let response = next.run(request).await;
+    if jar.get(CSRF_COOKIE).is_some() {
+        return response;
+    }
+    // 前端需要读取这个 cookie 放到请求头中，所以不能设置 http_only
+    let cookie = Cookie::build((
+        CSRF_COOKIE,
+        crate::util::random_string(
+            32,
+            Some("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ1234567890"),
+        ),
+    ))
+    .same_site(SameSite::Strict)
+    .path("/")
+    .build();
+    (jar.add(cookie), response).into_response()
+}
+
+fn check_request(state: &AppState, jar: &CookieJar, headers: &HeaderMap) -> Result<(), ApiError> {
+    let rt = state.runtime_config.load();
+    // 完成初始设置之前 domain 还没有配置，设置接口由设置代码保护
+    if rt.setup
+        && let Some(origin) = request_origin(headers)
+        && !allowed_origin(&rt.domain, origin)
+    {
+        return Err(ApiError::new(
+            403,
+            "Cross-origin request rejected".to_string(),
+        ));
+    }
+
+    let authenticated = ["token", "pending_token"]
+        .iter()
+        .any(|name| jar.get(name).is_some());
+    if !authenticated {
+        return Ok(());
+    }
+    let expected = jar.get(CSRF_COOKIE).map(|x| x.value().to_string());
+    let provided = headers
+        .get(CSRF_HEADER)
+        .and_then(|value| value.to_str().ok());
+    match (expected, provided) {
+        (Some(expected), Some(provided))
+            if !expected.is_empty()
+                && crate::util::constant_time_eq(expected.as_bytes(), provided.as_bytes()) =>
+        {
+            Ok(())
+        }
+        _ => Err(ApiError::new(403, "Invalid CSRF token".to_string())),
+    }
+}
+
+// 请求来源，优先使用 Origin 头，没有时从 Referer 中截取。两者都没有说明不是浏览器发起的请求
+fn request_origin(headers: &HeaderMap) -> Option<&str> {
+    if let Some(origin) = headers.get(header::ORIGIN) {
+        return Some(origin.to_str().unwrap_or("null"));
+    }
+    let referer = headers.get(header::REFERER)?.to_str().ok()?;
+    Some(origin_of(referer))
+}
+
+// 截取 URL 中 scheme://host[:port] 的部分
+fn origin_of(url: &str) -> &str {
+    let Some(scheme_end) = url.find("://") else {
+        return url;
+    };
+    let rest = &url[scheme_end + 3..];
+    match rest.find(['/', '?', '#']) {
+        Some(end) => &url[..scheme_end + 3 + end],
+        None => url,
+    }
+}
+
+fn allowed_origin(domain: &str, origin: &str) -> bool {
+    if origin.eq_ignore_ascii_case(origin_of(domain.trim_end_matches('/'))) {
+        return true;
+    }
+    // 调试构建中允许前端开发服务器的请求
+    cfg!(debug_assertions) && matches!(origin, "http://localhost:3000" | "http://127.0.0.1:3000")
+}
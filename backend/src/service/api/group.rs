@@ -0,0 +1,326 @@
+use crate::service::api::auth::CurrentUser;
+use crate::service::api::result::{ApiError, ApiJson, ApiPath, ApiResult};
+use crate::service::api::types::{
+    ApiGroup, ApiGroupCreate, ApiGroupFull, ApiGroupMember, ApiGroupMemberUpdate, ApiGroupUpdate,
+    ApiList,
+};
+use crate::types::{AppState, Group, GroupMember, UserPermission};
+use crate::{fail, success};
+use axum::extract::State;
+use tracing::{info, instrument};
+
+// 获取组以及当前用户在组中的成员身份，不是成员时需要 ManageUsers 权限
+async fn find_group(
+    state: &AppState,
+    current: &CurrentUser,
+    id: &str,
+) -> Result<(Group, Option<GroupMember>), ApiError> {
+    let Some(group) = state.database_accessor.get_group(id).await? else {
+        return Err(ApiError::new(404, "Group not found".to_string()));
+    };
+    let member = state
+        .database_accessor
+        .get_group_member(id, &current.user.id)
+        .await?;
+    if member.is_none() && !current.has_permission(UserPermission::ManageUsers) {
+        return Err(ApiError::new(404, "Group not found".to_string()));
+    }
+    Ok((group, member))
+}
+
+// 组的所有者和拥有 ManageUsers 权限的用户可以管理组
+fn can_manage(current: &CurrentUser, member: &Option<GroupMember>) -> bool {
+    member.as_ref().is_some_and(|x| x.is_owner())
+        || current.has_permission(UserPermission::ManageUsers)
+}
+
+// 组的权限会授予所有成员，所以只有 ManageUsers 可以修改，并且不能超出自己拥有的权限
+fn check_descriptor(current: &CurrentUser, descriptor: &[UserPermission]) -> Result<(), ApiError> {
+    if descriptor.is_empty() {
+        return Ok(());
+    }
+    if !current.has_permission(UserPermission::ManageUsers) {
+        return Err(ApiError::new(403, "No sufficient permissions".to_string()));
+    }
+    current.check_grantable(descriptor)
+}
+
+#[instrument(skip(state, current))]
+pub async fn get_groups(State(state): State<AppState>, current: CurrentUser) -> ApiResult {
+    let da = &state.database_accessor;
+    let groups = if current.has_permission(UserPermission::ManageUsers) {
+        da.get_groups().await?
+    } else {
+        da.get_user_groups(&current.user.id).await?
+    };
+    let items = groups.into_iter().map(ApiGroup::from).collect::<Vec<_>>();
+    success!(ApiList {
+        total: items.len() as i64,
+        items,
+    })
+}
+
+#[instrument(skip(state, current))]
+pub async fn create_group(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    ApiJson(body): ApiJson<ApiGroupCreate>,
+) -> ApiResult {
+    let name = body.name.trim();
+    if name.is_empty() {
+        fail!(400, "Group name cannot be empty");
+    }
+    check_descriptor(&current, &body.descriptor)?;
+    // 创建者自动成为组的所有者
+    let group = state
+        .database_accessor
+        .create_group(
+            name,
+            UserPermission::to_descriptor(&body.descriptor),
+            &current.user.id,
+        )
+        .await?;
+    info!("User {} created group {}", current.user.id, group.id);
+    success!(ApiGroup::from(group))
+}
+
+#[instrument(skip(state, current))]
+pub async fn get_group(
+    ApiPath(id): ApiPath<String>,
+    State(state): State<AppState>,
+    current: CurrentUser,
+) -> ApiResult {
+    let (group, _) = find_group(&state, &current, &id).await?;
+    let da = &state.database_accessor;
+    let mut members = Vec::new();
+    for member in da.get_group_members(&id).await? {
+        if let Some(user) = da.get_user_by_id(&member.user_id).await? {
+            members.push(ApiGroupMember::new(member, &user));
+        }
+    }
+    success!(ApiGroupFull {
+        group: ApiGroup::from(group),
+        members,
+    })
+}
+
+#[instrument(skip(state, current))]
+pub async fn update_group(
+    ApiPath(id): ApiPath<String>,
+    State(state): State<AppState>,
+    current: CurrentUser,
+    ApiJson(body): ApiJson<ApiGroupUpdate>,
+) -> ApiResult {
+    let (group, member) = find_group(&state, &current, &id).await?;
+    if !can_manage(&current, &member) {
+        fail!(403, "No sufficient permissions");
+    }
+    let name = match body.name.as_deref().map(str::trim) {
+        Some("") => fail!(400, "Group name cannot be empty"),
+        Some(name) => name.to_string(),
+        None => group.name,
+    };
+    let descriptor = match body.descriptor {
+        Some(descriptor) => {
+            // 清空权限同样需要 ManageUsers
+            if !current.has_permission(UserPermission::ManageUsers) {
+                fail!(403, "No sufficient permissions");
+            }
+            check_descriptor(&current, &descriptor)?;
+            UserPermission::to_descriptor(&descriptor)
+        }
+        None => group.descriptor,
+    };
+    let group = state
+        .database_accessor
+        .update_group(&id, &name, descriptor)
+        .await?;
+    info!("User {} updated group {}", current.user.id, id);
+    success!(ApiGroup::from(group))
+}
+
+#[instrument(skip(state, current))]
+pub async fn remove_group(
+    ApiPath(id): ApiPath<String>,
+    State(state): State<AppState>,
+    current: CurrentUser,
+) -> ApiResult {
+    let (group, member) = find_group(&state, &current, &id).await?;
+    if !can_manage(&current, &member) {
+        fail!(403, "No sufficient permissions");
+    }
+    state.database_accessor.remove_group(&id).await?;
+    info!("User {} removed group {}", current.user.id, id);
+    success!(ApiGroup::from(group))
+}
+
+// 添加成员或修改成员的角色
+#[instrument(skip(state, current))]
+pub async fn set_group_member(
+    ApiPath((id, user_id)): ApiPath<(String, String)>,
+    State(state): State<AppState>,
+    current: CurrentUser,
+    ApiJson(body): ApiJson<ApiGroupMemberUpdate>,
+) -> ApiResult {
+    let (group, member) = find_group(&state, &current, &id).await?;
+    if !can_manage(&current, &member) {
+        fail!(403, "No sufficient permissions");
+    }
+    if body.role != GroupMember::OWNER && body.role != GroupMember::MEMBER {
+        fail!(400, "Role must be either owner or member");
+    }
+    let da = &state.database_accessor;
+    let Some(user) = da.get_user_by_id(&user_id).await? else {
+        fail!(404, "User not found");
+    };
+    let existing = da.get_group_member(&id, &user_id).await?;
+    // 组的权限会授予所有成员，添加成员或提升角色时同样不能超出自己拥有的权限
+    let promoted =
+        body.role == GroupMember::OWNER && !existing.as_ref().is_some_and(|x| x.is_owner());
+    if existing.is_none() || promoted {
+        current.check_grantable(&UserPermission::from_i64(group.descriptor))?;
+    }
+    if existing.as_ref().is_some_and(|x| x.is_owner())
+        && body.role != GroupMember::OWNER
+        && da.count_group_owners(&id).await? <= 1
+    {
+        fail!(409, "Cannot remove the last owner of a group");
+    }
+    let member = da.set_group_member(&id, &user_id, &body.role).await?;
+    info!(
+        "User {} set role of user {} in group {} to {}",
+        current.user.id, user_id, id, body.role
+    );
+    success!(ApiGroupMember::new(member, &user))
+}
+
+// 移除成员，成员也可以自己退出
+#[instrument(skip(state, current))]
+pub async fn remove_group_member(
+    ApiPath((id, user_id)): ApiPath<(String, String)>,
+    State(state): State<AppState>,
+    current: CurrentUser,
+) -> ApiResult {
+    let (_, member) = find_group(&state, &current, &id).await?;
+    if user_id != current.user.id && !can_manage(&current, &member) {
+        fail!(403, "No sufficient permissions");
+    }
+    let da = &state.database_accessor;
+    let Some(target) = da.get_group_member(&id, &user_id).await? else {
+        fail!(404, "Member not found");
+    };
+    if target.is_owner() && da.count_group_owners(&id).await? <= 1 {
+        fail!(409, "Cannot remove the last owner of a group");
+    }
+    da.remove_group_member(&id, &user_id).await?;
+    info!(
+        "User {} removed user {} from group {}",
+        current.user.id, user_id, id
+    );
+    success!(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+
+    async fn set_member(
+        state: &AppState,
+        current: CurrentUser,
+        group_id: &str,
+        user_id: &str,
+        role: &str,
+    ) -> ApiResult {
+        set_group_member(
+            ApiPath((group_id.to_string(), user_id.to_string())),
+            State(state.clone()),
+            current,
+            ApiJson(ApiGroupMemberUpdate {
+                role: role.to_string(),
+            }),
+        )
+        .await
+    }
+
+    fn status(result: ApiResult) -> StatusCode {
+        match result {
+            Ok(res) => res.status(),
+            Err(ApiError::Anyhow(e)) => panic!("unexpected error: {e}"),
+            Err(ApiError::Other((status, _))) => status,
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_to_grant_group_permissions_beyond_own() {
+        let state = AppState::temporary().await;
+        let da = &state.database_accessor;
+        let manage = UserPermission::Manage.as_i64();
+        let manage_users = UserPermission::ManageUsers.as_i64();
+        da.create_user(
+            "admin",
+            "Admin",
+            "admin@example.com",
+            "password",
+            manage,
+            None,
+        )
+        .await
+        .unwrap();
+        da.create_user(
+            "mu",
+            "Mallory",
+            "mu@example.com",
+            "password",
+            manage_users,
+            None,
+        )
+        .await
+        .unwrap();
+        da.create_user("carol", "Carol", "carol@example.com", "password", 0, None)
+            .await
+            .unwrap();
+        let admins = da.create_group("Admins", manage, "admin").await.unwrap();
+        let helpers = da
+            .create_group("Helpers", manage_users, "admin")
+            .await
+            .unwrap();
+
+        // 只有 ManageUsers 的用户不能把自己加入拥有 Manage 权限的组
+        let current = CurrentUser::temporary(&state, "mu").await;
+        assert_eq!(
+            status(set_member(&state, current, &admins.id, "mu", GroupMember::MEMBER).await),
+            StatusCode::FORBIDDEN
+        );
+        assert!(
+            da.get_group_member(&admins.id, "mu")
+                .await
+                .unwrap()
+                .is_none()
+        );
+        assert_eq!(da.get_user_group_descriptor("mu").await.unwrap(), 0);
+
+        // 也不能提升这种组中已有成员的角色
+        da.set_group_member(&admins.id, "carol", GroupMember::MEMBER)
+            .await
+            .unwrap();
+        let current = CurrentUser::temporary(&state, "mu").await;
+        assert_eq!(
+            status(set_member(&state, current, &admins.id, "carol", GroupMember::OWNER).await),
+            StatusCode::FORBIDDEN
+        );
+        let carol = da
+            .get_group_member(&admins.id, "carol")
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!carol.is_owner());
+
+        // 组的权限都是自己拥有的则可以
+        let current = CurrentUser::temporary(&state, "mu").await;
+        assert_eq!(
+            status(set_member(&state, current, &helpers.id, "carol", GroupMember::OWNER).await),
+            StatusCode::OK
+        );
+    }
+}
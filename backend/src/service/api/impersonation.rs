@@ -0,0 +1,230 @@
+use crate::service::api::auth::{Manage, RequirePermission, bearer_token};
+use crate::service::api::result::{ApiPath, ApiQuery, ApiResult};
+use crate::service::api::types::{ApiAuditLog, ApiImpersonation, ApiList, ApiUser, ApiUserInfo};
+use crate::service::api::user::session_cookie;
+use crate::types::{AppState, ClientInfo};
+use crate::{fail, success};
+use axum::body::Body;
+use axum::extract::{OriginalUri, Request, State};
+use axum::http::Method;
+use axum::middleware::Next;
+use axum::response::Response;
+use axum_extra::extract::PrivateCookieJar;
+use axum_extra::extract::cookie::{Cookie, SameSite};
+use std::collections::HashMap;
+use tracing::{error, info, instrument};
+
+// 模拟登录期间保存管理员自己的会话令牌，结束模拟时恢复
+const IMPERSONATOR_COOKIE: &str = "impersonator_token";
+
+// 管理员以其他用户的身份登录，用于排查用户看到的内容
+// 模拟会话有效期较短且不会续期，期间的修改操作都会记录到审计日志
+#[instrument(skip(state, manager, jar))]
+pub async fn start(
+    ApiPath(id): ApiPath<String>,
+    State(state): State<AppState>,
+    manager: RequirePermission<Manage>,
+    jar: PrivateCookieJar,
+    client: ClientInfo,
+) -> ApiResult {
+    let Some(session) = manager.session() else {
+        fail!(403, "Impersonation requires a browser session");
+    };
+    if session.impersonator_id.is_some() {
+        fail!(409, "Already impersonating a user");
+    }
+    let Some(admin_token) = jar.get("token").map(|x| x.value().to_string()) else {
+        fail!(401, "Unauthorized");
+    };
+    if id == manager.user.id {
+        fail!(400, "Cannot impersonate yourself");
+    }
+    let Some(target) = state.database_accessor.get_user_by_id(&id).await? else {
+        fail!(404, "User not found");
+    };
+    if !target.is_active() {
+        fail!(400, "User is not active");
+    }
+    if target.owner && !manager.user.owner {
+        fail!(403, "Only owners can impersonate an owner");
+    }
+
+    let token = crate::util::random_string(64, None);
+    let session = state
+        .database_accessor
+        .create_impersonation_session(&token, &target.id, &manager.user.id, &client)
+        .await?;
+    state
+        .database_accessor
+        .add_audit_log(
+            &manager.user.id,
+            &target.id,
+            "impersonation_start",
+            Some(&session.id),
+            client.ip_address.as_deref(),
+        )
+        .await?;
+    info!(
+        "User {} started impersonating user {}",
+        manager.user.id, target.id
+    );
+
+    let impersonator_cookie = Cookie::build((IMPERSONATOR_COOKIE, admin_token))
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .path("/")
+        .build();
+    let jar = jar
+        .add(impersonator_cookie)
+        .remove("token")
+        .add(session_cookie(token, false));
+    let impersonation = ApiImpersonation::from_session(&state, &session).await?;
+    let user = ApiUser::from_user(target, &state).await?;
+    success!(
+        ApiUserInfo {
+            user,
+            impersonation: Some(impersonation),
+        },
+        jar
+    )
+}
+
+// 结束模拟登录：删除模拟会话并恢复管理员自己的会话（如果仍然有效）
+// 不在模拟登录时返回 None
+pub async fn end_impersonation(
+    state: &AppState,
+    jar: PrivateCookieJar,
+    client: &ClientInfo,
+) -> anyhow::Result<Option<PrivateCookieJar>> {
+    let da = &state.database_accessor;
+    let Some(admin_token) = jar.get(IMPERSONATOR_COOKIE).map(|x| x.value().to_string()) else {
+        return Ok(None);
+    };
+    // 模拟会话过期后仍然可以结束模拟
+    if let Some(token) = jar.get("token") {
+        match da.get_session(token.value()).await? {
+            Some(session) if session.impersonator_id.is_some() => {
+                let impersonator_id = session.impersonator_id.unwrap_or_default();
+                da.remove_session(token.value()).await?;
+                da.add_audit_log(
+                    &impersonator_id,
+                    &session.user_id,
+                    "impersonation_end",
+                    Some(&session.id),
+                    client.ip_address.as_deref(),
+                )
+                .await?;
+                info!(
+                    "User {} stopped impersonating user {}",
+                    impersonator_id, session.user_id
+                );
+            }
+            // 之后又以其他方式登录了，遗留的 cookie 不再有意义
+            Some(_) => return Ok(None),
+            None => {}
+        }
+    }
+
+    let jar = jar
+        .remove(Cookie::build(IMPERSONATOR_COOKIE).path("/"))
+        .remove("token");
+    let jar = match da.get_active_session(&admin_token).await? {
+        Some(session) if session.impersonator_id.is_none() => {
+            jar.add(session_cookie(admin_token, session.remember))
+        }
+        _ => jar,
+    };
+    Ok(Some(jar))
+}
+
+#[instrument(skip(state, jar))]
+pub async fn end(
+    State(state): State<AppState>,
+    jar: PrivateCookieJar,
+    client: ClientInfo,
+) -> ApiResult {
+    let Some(jar) = end_impersonation(&state, jar, &client).await? else {
+        fail!(400, "Not impersonating a user");
+    };
+    success!((), jar)
+}
+
+// 记录模拟登录期间的所有修改操作（请求方法、路径和响应状态）
+pub async fn audit_impersonation(
+    State(state): State<AppState>,
+    jar: PrivateCookieJar,
+    client: ClientInfo,
+    request: Request<Body>,
+    next: Next,
+) -> Response {
+    let safe = matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    );
+    if safe || bearer_token(request.headers()).is_some() {
+        return next.run(request).await;
+    }
+    let session = match jar.get("token") {
+        Some(token) => state
+            .database_accessor
+            .get_session(token.value())
+            .await
+            .ok()
+            .flatten(),
+        None => None,
+    };
+    let Some(session) = session.filter(|x| x.impersonator_id.is_some() && !x.is_expired()) else {
+        return next.run(request).await;
+    };
+
+    let path = match request.extensions().get::<OriginalUri>() {
+        Some(OriginalUri(uri)) => uri.path().to_string(),
+        None => request.uri().path().to_string(),
+    };
+    let method = request.method().clone();
+    let response = next.run(request).await;
+    let detail = format!("{} {} -> {}", method, path, response.status().as_u16());
+    if let Err(e) = state
+        .database_accessor
+        .add_audit_log(
+            session.impersonator_id.as_deref().unwrap_or_default(),
+            &session.user_id,
+            "request",
+            Some(&detail),
+            client.ip_address.as_deref(),
+        )
+        .await
+    {
+        error!("Failed to write audit log: {}", e);
+    }
+    response
+}
+
+// 查看审计日志，可以按管理员或被模拟的用户筛选
+#[instrument(skip(state, _manager))]
+pub async fn get_audit_logs(
+    State(state): State<AppState>,
+    _manager: RequirePermission<Manage>,
+    ApiQuery(params): ApiQuery<HashMap<String, String>>,
+) -> ApiResult {
+    let user_id = params.get("user").map(|x| x.as_str());
+    let offset = params
+        .get("offset")
+        .and_then(|x| x.parse::<i64>().ok())
+        .unwrap_or(0);
+    let limit = params
+        .get("limit")
+        .and_then(|x| x.parse::<i64>().ok())
+        .unwrap_or(50)
+        .min(100);
+
+    let items = state
+        .database_accessor
+        .get_audit_logs(user_id, offset, limit)
+        .await?
+        .into_iter()
+        .map(ApiAuditLog::from)
+        .collect::<Vec<_>>();
+    let total = state.database_accessor.count_audit_logs(user_id).await?;
+    success!(ApiList { total, items })
+}
@@ -1,34 +1,59 @@
+use crate::service::api::auth::{
+    CurrentUser, ItemPasswordCheck, OptionalUser, RequirePermission, ViewAllItems,
+    check_item_password, guest_session, manage_key,
+};
+use crate::service::api::quota::{
+    check_expiry, check_file_size, check_items, check_storage, get_quota,
+};
 use crate::service::api::result::{ApiError, ApiJson, ApiPath, ApiQuery, ApiResult};
-use crate::service::api::types::{ApiCode, ApiItemFull, ApiItemUpload, ApiList, ItemSimplified};
-use crate::types::{AppState, ItemType, ToPermission, Token, User, UserPermission};
+use crate::service::api::tag::normalize_tags;
+use crate::service::api::types::{
+    ApiAccessLog, ApiCode, ApiGuestItem, ApiItemClaim, ApiItemFull, ApiItemPage, ApiItemQuery,
+    ApiItemUpdate, ApiItemUpload, ApiList, ApiRevision, ApiRevisionContent, ApiRevisionDiff,
+    ApiRevisionDiffQuery, ApiSearchResult, ItemSimplified,
+};
+use crate::types::{
+    AppState, ClientInfo, Item, ItemCursor, ItemFilter, ItemRevision, ItemType, QuotaConfig,
+    Session, ToPermission, User, UserPermission,
+};
 use crate::{fail, success};
-use axum::extract::{Multipart, State};
+use axum::body::Body;
+use axum::extract::{Multipart, Request, State};
+use axum::http::{HeaderMap, header};
+use axum::middleware::Next;
 use axum_extra::extract::PrivateCookieJar;
-use cookie::Cookie;
 use cookie::time::Duration;
-use sha2::{Digest, Sha256};
+use cookie::{Cookie, SameSite};
+use http_body_util::Limited;
 use std::collections::HashMap;
 use tracing::{debug, info, instrument};
 use uuid::Uuid;
 
-async fn try_get_user(state: &AppState, jar: &PrivateCookieJar) -> Option<User> {
-    let token = jar
-        .get("token")
-        .and_then(|token| state.user_tokens.get(token.value()))?;
-    if token.is_expired() {
-        return None;
+// 项目的创建者、项目所在组的成员以及拥有指定权限的用户可以访问项目
+async fn can_access_item(
+    state: &AppState,
+    user: &User,
+    item: &Item,
+    permission: UserPermission,
+) -> anyhow::Result<bool> {
+    if item.creator.as_deref() == Some(user.id.as_str()) || user.descriptor.contains(permission) {
+        return Ok(true);
     }
-    state
+    let Some(group_id) = item.group_id.as_deref() else {
+        return Ok(false);
+    };
+    Ok(state
         .database_accessor
-        .get_user_by_id(&token.user_id)
-        .await
-        .ok()?
+        .get_group_member(group_id, &user.id)
+        .await?
+        .is_some())
 }
 
-#[instrument(skip(state, jar))]
+#[instrument(skip(state, user))]
 pub async fn get_item(
     ApiPath(item_path): ApiPath<String>,
-    jar: PrivateCookieJar,
+    OptionalUser(user): OptionalUser,
+    client: ClientInfo,
     State(state): State<AppState>,
     ApiQuery(params): ApiQuery<HashMap<String, String>>,
 ) -> ApiResult {
@@ -43,42 +68,67 @@ pub async fn get_item(
     let item = item.unwrap();
 
     info!("Item found with path: {}", item_path);
+    let user_auth = match user {
+        Some(current) => {
+            can_access_item(&state, &current.user, &item, UserPermission::ViewAllItems).await?
+        }
+        None => false,
+    };
+    // 标签只对能管理或查看全部项目的用户可见
+    let tags = if user_auth {
+        state.database_accessor.get_item_tags(&item.id).await?
+    } else {
+        Vec::new()
+    };
     if item.password_hash.is_none() {
         if detailed {
-            success!(ApiItemFull::from(item));
+            success!(ApiItemFull {
+                tags,
+                ..ApiItemFull::from(item)
+            });
         } else {
-            success!(ItemSimplified::from(item));
+            success!(ItemSimplified {
+                tags,
+                ..ItemSimplified::from(item)
+            });
         };
     }
 
     debug!("Item has a password hash, checking authentication");
     let item_clone = item.clone();
-    // 使用 try_get_user 函数进行用户认证
-    let user = try_get_user(&state, &jar).await;
-    let user_auth = user.is_some_and(|user| {
-        item.creator.is_some_and(|creator| creator == user.id)
-            || &user.id == "00000000-0000-0000-0000-000000000000"
-    });
-    let password_auth = if !user_auth {
-        params.get("password").is_some_and(|password| {
-            let password_hash = format!("{:x}", Sha256::digest(password.as_bytes()));
-            let match_result = &password_hash == item.password_hash.as_ref().unwrap();
-            // 记录密码认证结果
-            debug!(
-                "Password authentication result for item {}: {}",
-                item_path, match_result
+    let password_auth = if !user_auth && let Some(password) = params.get("password") {
+        let check =
+            check_item_password(&state, &item_clone, password, client.ip_address.as_deref())
+                .await?;
+        if let ItemPasswordCheck::Limited(wait) = check {
+            fail!(
+                429,
+                "Too many failed attempts, try again in {} seconds",
+                wait
             );
-            match_result
-        })
+        }
+        let match_result = matches!(check, ItemPasswordCheck::Matched);
+        // 记录密码认证结果
+        debug!(
+            "Password authentication result for item {}: {}",
+            item_path, match_result
+        );
+        match_result
     } else {
         false
     };
     if user_auth || password_auth {
         info!("Authentication successful for item {}", item_path);
         if detailed {
-            success!(ApiItemFull::from(item_clone));
+            success!(ApiItemFull {
+                tags,
+                ..ApiItemFull::from(item_clone)
+            });
         } else {
-            success!(ItemSimplified::from(item_clone));
+            success!(ItemSimplified {
+                tags,
+                ..ItemSimplified::from(item_clone)
+            });
         }
     } else {
         info!("Authentication failed for item {}", item_path);
@@ -86,10 +136,11 @@ pub async fn get_item(
     }
 }
 
-#[instrument(skip(state, jar))]
+#[instrument(skip(state, user))]
 pub async fn get_code(
     ApiPath(item_path): ApiPath<String>,
-    jar: PrivateCookieJar,
+    OptionalUser(user): OptionalUser,
+    client: ClientInfo,
     State(state): State<AppState>,
     ApiQuery(params): ApiQuery<HashMap<String, String>>,
 ) -> ApiResult {
@@ -105,17 +156,24 @@ pub async fn get_code(
         success!(ApiCode::read_from(item, state.file_accessor.clone()).await);
     }
     let item_clone = item.clone();
-    // 使用 try_get_user 函数进行用户认证
-    let user = try_get_user(&state, &jar).await;
-    let user_auth = user.is_some_and(|user| {
-        item.creator.is_some_and(|creator| creator == user.id)
-            || &user.id == "00000000-0000-0000-0000-000000000000"
-    });
-    let password_auth = if !user_auth {
-        params.get("password").is_some_and(|password| {
-            let password_hash = format!("{:x}", Sha256::digest(password.as_bytes()));
-            &password_hash == item.password_hash.as_ref().unwrap()
-        })
+    let user_auth = match user {
+        Some(current) => {
+            can_access_item(&state, &current.user, &item, UserPermission::ViewAllItems).await?
+        }
+        None => false,
+    };
+    let password_auth = if !user_auth && let Some(password) = params.get("password") {
+        let check =
+            check_item_password(&state, &item_clone, password, client.ip_address.as_deref())
+                .await?;
+        if let ItemPasswordCheck::Limited(wait) = check {
+            fail!(
+                429,
+                "Too many failed attempts, try again in {} seconds",
+                wait
+            );
+        }
+        matches!(check, ItemPasswordCheck::Matched)
     } else {
         false
     };
@@ -126,11 +184,13 @@ pub async fn get_code(
     }
 }
 
-#[instrument(skip(state, jar))]
+#[instrument(skip(state, jar, user))]
 pub async fn create_item(
     ApiPath(path): ApiPath<String>,
     State(state): State<AppState>,
     jar: PrivateCookieJar,
+    OptionalUser(user): OptionalUser,
+    client: ClientInfo,
     ApiQuery(query): ApiQuery<HashMap<String, String>>,
     ApiJson(body): ApiJson<ApiItemUpload>,
 ) -> ApiResult {
@@ -176,7 +236,7 @@ pub async fn create_item(
         );
     }
 
-    let user = try_get_user(&state, &jar).await;
+    let user = user.map(|x| x.user);
     if user.is_none() && !turnstile {
         fail!(401, "Unauthorized");
     }
@@ -186,24 +246,64 @@ pub async fn create_item(
             "User {} is attempting to create item at path: {}",
             user.id, path
         );
-        // 权限鉴定，如果不符合条件会直接返回 Err
-        if !user.descriptor.contains(UserPermission::Manage) {
-            fn check_permission(user: &User, item_type: ItemType) -> Result<(), ApiError> {
-                let required_permission = match item_type {
-                    ItemType::Code => UserPermission::Code,
-                    ItemType::File => UserPermission::File,
-                    ItemType::Link => UserPermission::Link,
-                };
-
-                if !user.descriptor.contains(required_permission) {
-                    return Err(ApiError::new(403, "Forbidden".to_string()));
-                }
-                Ok(())
-            }
-            check_permission(&user, body.item_type)?;
+        // 权限鉴定：项目类型本身的权限，以及自定义路径、密码和永不过期各自需要的权限
+        let mut required = vec![match body.item_type {
+            ItemType::Code => UserPermission::Code,
+            ItemType::File => UserPermission::File,
+            ItemType::Link => UserPermission::Link,
+        }];
+        if path.as_str() != "__RANDOM__" {
+            required.push(UserPermission::CustomPath);
+        }
+        if body.password.is_some() {
+            required.push(UserPermission::ItemPassword);
+        }
+        if expires_at.is_none() {
+            required.push(UserPermission::NoExpiry);
+        }
+        if let Some(permission) = required
+            .into_iter()
+            .find(|x| !user.descriptor.contains(x.clone()))
+        {
+            info!(
+                "User {} lacks permission {:?} to create item at path: {}",
+                user.id, permission, path
+            );
+            fail!(403, "No sufficient permissions");
         }
+        // 只能在自己所在的组中创建项目
+        if let Some(group_id) = body.group_id.as_deref()
+            && state
+                .database_accessor
+                .get_group_member(group_id, &user.id)
+                .await?
+                .is_none()
+        {
+            fail!(403, "Not a member of the group");
+        }
+    } else if body.group_id.is_some() {
+        fail!(403, "Guest users cannot create items in a group");
     }
 
+    let id = if turnstile {
+        format!("guest-{}", Uuid::now_v7().as_hyphenated().to_string())
+    } else {
+        user_id_clone.unwrap()
+    };
+
+    // 配额检查，游客每次创建都是新的身份，不限制项目数量
+    let quota = get_quota(&state, &id).await?;
+    check_expiry(&quota, expires_at)?;
+    if !turnstile {
+        check_items(&state, &id, &quota, 1).await?;
+    }
+    let size = match body.item_type {
+        ItemType::Code => body.data.len() as i64,
+        _ => 0,
+    };
+    check_file_size(&quota, size)?;
+    check_storage(&state, &id, &quota, size, 0).await?;
+
     // 权限鉴定通过，继续处理创建逻辑
     let data = match body.item_type {
         ItemType::Code => {
@@ -231,12 +331,6 @@ pub async fn create_item(
             body.data
         }
     };
-    let id = if turnstile {
-        format!("guest-{}", Uuid::now_v7().as_hyphenated().to_string())
-    } else {
-        user_id_clone.unwrap()
-    };
-
     let path = if path.as_str() != "__RANDOM__" {
         path
     } else {
@@ -252,6 +346,10 @@ pub async fn create_item(
         }
     };
 
+    let password_hash = match body.password {
+        Some(password) => Some(crate::util::hash_password(&password).await?),
+        None => None,
+    };
     let item = state
         .database_accessor
         .create_item(
@@ -260,98 +358,187 @@ pub async fn create_item(
             &data,
             expires_at,
             body.max_visits,
-            body.password
-                .map(|x| format!("{:x}", Sha256::digest(x.as_bytes())))
-                .as_deref(),
+            password_hash.as_deref(),
             body.extra_data.as_deref(),
             Some(&id),
         )
         .await?;
+    state
+        .database_accessor
+        .update_item_size(&item.id, size)
+        .await?;
+    let item = if let Some(group_id) = body.group_id {
+        state
+            .database_accessor
+            .update_item_group(&item.id, Some(&group_id))
+            .await?;
+        Item {
+            group_id: Some(group_id),
+            ..item
+        }
+    } else {
+        item
+    };
+    state
+        .database_accessor
+        .index_item(&item, &state.file_accessor)
+        .await?;
 
-    if turnstile && body.item_type == ItemType::File {
-        info!("Guest user {} created item at path {}", id, path);
-        let token = crate::util::random_password();
-        let _ = state
-            .user_tokens
-            .insert(token.clone(), Token::new(id, true));
+    if !turnstile {
+        success!(ItemSimplified::from(item));
+    }
+    // 游客之后可以凭管理密钥删除、修改项目，或在登录后认领项目
+    info!("Guest user {} created item at path {}", id, path);
+    let key = crate::util::random_string(
+        32,
+        Some("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ1234567890"),
+    );
+    state
+        .database_accessor
+        .set_item_manage_key(&item.id, &key)
+        .await?;
+    let item = ApiGuestItem {
+        item: ItemSimplified::from(item),
+        manage_key: key,
+    };
+    if body.item_type == ItemType::File {
+        let token = crate::util::random_string(64, None);
+        state
+            .database_accessor
+            .create_session(&token, &id, true, false, &client)
+            .await?;
+        let lifetime = Session::lifetime(true, false);
         let jar = jar.remove("token").add(
-            Cookie::build(("token", token.clone()))
-                .max_age(Duration::new(600, 0))
+            Cookie::build(("token", token))
+                .max_age(Duration::seconds(lifetime.num_seconds()))
                 .http_only(true)
+                .same_site(SameSite::Strict)
                 .path("/")
                 .build(),
         );
-        success!(ItemSimplified::from(item), jar)
+        success!(item, jar)
     } else {
-        success!(ItemSimplified::from(item))
+        success!(item)
+    }
+}
+
+// multipart 的分隔符和字段头等额外内容所允许的大小
+const MULTIPART_OVERHEAD: usize = 64 * 1024;
+
+// 上传的文件计入项目创建者的配额，项目不存在时使用默认配额
+async fn upload_quota(state: &AppState, item: Option<&Item>) -> Result<QuotaConfig, ApiError> {
+    match item.and_then(|x| x.creator.as_deref()) {
+        Some(creator) => get_quota(state, creator).await,
+        None => Ok(state.runtime_config.load().quota.clone()),
     }
 }
 
-#[instrument(skip(state, jar, multipart))]
+// 按单个文件大小配额限制上传请求的请求体尺寸，没有设置配额时不限制
+pub async fn upload_body_limit(
+    ApiPath(path): ApiPath<String>,
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> ApiResult {
+    let item = state.database_accessor.get_item(&path).await?;
+    let quota = upload_quota(&state, item.as_ref()).await?;
+    let Some(file_size) = quota.file_size else {
+        return Ok(next.run(request).await);
+    };
+    let limit = usize::try_from(file_size)
+        .unwrap_or(0)
+        .saturating_add(MULTIPART_OVERHEAD);
+    let length = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .and_then(|x| x.to_str().ok())
+        .and_then(|x| x.parse::<usize>().ok());
+    if length.is_some_and(|x| x > limit) {
+        fail!(413, "File exceeds the size limit");
+    }
+    let request = request.map(|body| Body::new(Limited::new(body, limit)));
+    Ok(next.run(request).await)
+}
+
+#[instrument(skip(state, jar, user, headers, multipart))]
 pub async fn upload_file(
     ApiPath(path): ApiPath<String>,
     State(state): State<AppState>,
     jar: PrivateCookieJar,
+    OptionalUser(user): OptionalUser,
+    headers: HeaderMap,
     mut multipart: Multipart,
 ) -> ApiResult {
     info!("Attempting to upload file to item at path: {}", path);
 
+    let item = state.database_accessor.get_item(&path).await?;
+    let quota = upload_quota(&state, item.as_ref()).await?;
+
     // 先消费 multipart，避免返回错误时客户端未传输完毕，导致出现 connection reset
+    // 超出单个文件大小限制后继续读取但不再保存，避免占用内存
     let mut field = None;
-    while let Some(inner_field) = multipart.next_field().await? {
+    let mut too_large = false;
+    while let Some(mut inner_field) = multipart.next_field().await? {
         if inner_field.name().unwrap_or("").to_string() != "file" {
             continue;
         }
-        field = Some(inner_field.bytes().await?);
+        let mut bytes = Vec::new();
+        while let Some(chunk) = inner_field.chunk().await? {
+            if too_large {
+                continue;
+            }
+            if quota
+                .file_size
+                .is_some_and(|x| (bytes.len() + chunk.len()) as i64 > x)
+            {
+                too_large = true;
+                bytes = Vec::new();
+                continue;
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+        field = Some(bytes);
     }
 
-    let item = state.database_accessor.get_item(&path).await?;
     if item.is_none() {
         fail!(404, "Item not found");
     }
     let item = item.unwrap();
 
-    let token = if let Some(x) = jar.get("token") {
-        x.value().to_string()
-    } else {
-        info!("No token found in cookie");
-        fail!(401, "Unauthorized");
-    };
-
-    // 显式限制 user_token（which 是从哈希表里取出的）的作用域以避免产生死锁
+    // 已登录用户（会话或 API 令牌）直接按用户权限处理，否则尝试使用游客的临时令牌
     let token_temporary = {
-        let user_token = state.user_tokens.get(&token);
-        if user_token.is_none() {
-            fail!(401, "Unauthorized");
-        }
-        let user_token = user_token.unwrap();
-        let info = if !user_token.temporary {
-            let user = state
-                .database_accessor
-                .get_user_by_id(&user_token.user_id)
-                .await?;
-            if user.is_none() {
-                fail!(401, "Unauthorized");
-            }
-            let user = user.unwrap();
-            (user.descriptor, user.id, false)
+        // (用户 ID, 是否可以修改该项目, 是否为游客的临时令牌)
+        let info = if let Some(key) = manage_key(&headers) {
+            let allowed = state.database_accessor.check_item_manage_key(&item, key);
+            (item.creator.clone().unwrap_or_default(), allowed, false)
+        } else if let Some(current) = user {
+            let allowed =
+                can_access_item(&state, &current.user, &item, UserPermission::Manage).await?;
+            (current.user.id, allowed, false)
         } else {
-            (0_i64, user_token.user_id.clone(), true)
+            match guest_session(&state, &jar).await {
+                Some(session) => {
+                    let allowed = item.creator.as_deref() == Some(session.user_id.as_str());
+                    (session.user_id, allowed, true)
+                }
+                None => {
+                    info!("No valid token found in request");
+                    fail!(401, "Unauthorized");
+                }
+            }
         };
 
         info!(
             "User {} is attempting to upload file to path: {}",
-            info.1, path
+            info.0, path
         );
 
-        if !info.0.contains(UserPermission::Manage)
-            && !item.creator.clone().is_some_and(|x| info.1 == x)
-        {
+        if !info.1 {
             info!(
                 "User {} has no sufficient permission to upload file to path: {}",
-                info.1, path
+                info.0, path
             );
-            fail!(403, "No sufficient permission");
+            fail!(403, "No sufficient permissions");
         }
 
         info.2
@@ -363,11 +550,22 @@ pub async fn upload_file(
         fail!(409, "Item is not a File");
     }
 
+    if too_large {
+        info!(
+            "File uploaded to item at path {} exceeds the size limit",
+            path
+        );
+        fail!(413, "File exceeds the size limit");
+    }
     if field.is_none() {
         info!("No part named 'file' uploaded to item at path: {}", path);
         fail!(400, "No part named 'file' uploaded");
     }
     let data = Box::new(field.unwrap());
+    let size = data.len() as i64;
+    if let Some(creator) = item.creator.as_deref() {
+        check_storage(&state, creator, &quota, size, item.size.unwrap_or(0)).await?;
+    }
     let filename_id = Uuid::now_v7().as_hyphenated().to_string();
     let fa_clone = state.file_accessor.clone();
     let (ext, img) = infer::get(&data)
@@ -381,26 +579,41 @@ pub async fn upload_file(
         .database_accessor
         .update_item_data(&item.id, &filename)
         .await?;
+    // 重新上传时删除旧文件，占位文件是共用的，不能删除
+    if item.data != "dummy_file.txt" {
+        state.file_accessor.remove_file(&item.data).await?;
+    }
     state
         .database_accessor
         .update_item_img(&item.id, img)
         .await?;
+    state
+        .database_accessor
+        .update_item_size(&item.id, size)
+        .await?;
 
     if !token_temporary {
         success!(ItemSimplified::from(item))
     } else {
-        // 这里 token 是 cookie 的值，并且和 user_token 的键名一样
-        state.user_tokens.remove(&token);
+        // 临时令牌只能用于一次上传，用完即删除
+        if let Some(token) = jar.get("token") {
+            state
+                .database_accessor
+                .remove_session(token.value())
+                .await?;
+        }
         let jar = jar.remove("token");
         success!(ItemSimplified::from(item), jar)
     }
 }
 
-#[instrument(skip(state, jar))]
+#[instrument(skip(state, jar, user, headers))]
 pub async fn remove_item(
     ApiPath(path): ApiPath<String>,
     State(state): State<AppState>,
     jar: PrivateCookieJar,
+    OptionalUser(user): OptionalUser,
+    headers: HeaderMap,
 ) -> ApiResult {
     let item = state.database_accessor.get_item(&path).await?;
     if item.is_none() {
@@ -408,64 +621,471 @@ pub async fn remove_item(
     }
     let item = item.unwrap();
 
-    let user = try_get_user(&state, &jar).await;
-    if user.is_none() {
-        if let Some(token) = state
-            .user_tokens
-            .get(&jar.get("token").unwrap().value().to_string())
+    // 持有管理密钥即可删除游客项目；游客也可以用上传时得到的临时会话删除自己创建的项目
+    let allowed = if let Some(key) = manage_key(&headers) {
+        state.database_accessor.check_item_manage_key(&item, key)
+    } else if let Some(CurrentUser { user, .. }) = user {
+        info!(
+            "User {} is attempting to delete item at path: {}",
+            user.id, path
+        );
+        can_access_item(&state, &user, &item, UserPermission::Manage).await?
+    } else {
+        let Some(session) = guest_session(&state, &jar).await else {
+            fail!(401, "Unauthorized");
+        };
+        item.creator.as_deref() == Some(session.user_id.as_str())
+    };
+    if !allowed {
+        info!("No sufficient permission to delete item at path: {}", path);
+        fail!(403, "No sufficient permissions");
+    }
+    let revisions = state.database_accessor.remove_item(&item.id).await?;
+    // 尚未上传的文件指向共用的占位文件，不能删除
+    if item.item_type != ItemType::Link && item.data != "dummy_file.txt" {
+        state.file_accessor.remove_file(&item.data).await?;
+    }
+    for file in revisions {
+        state.file_accessor.remove_file(&file).await?;
+    }
+    success!(ItemSimplified::from(item))
+}
+
+// 修改已有的项目。项目的管理者或者持有管理密钥的游客可以修改，未提供的字段保持不变
+#[instrument(skip(state, user, headers, body))]
+pub async fn update_item(
+    ApiPath(path): ApiPath<String>,
+    State(state): State<AppState>,
+    OptionalUser(user): OptionalUser,
+    headers: HeaderMap,
+    ApiJson(body): ApiJson<ApiItemUpdate>,
+) -> ApiResult {
+    let Some(item) = state.database_accessor.get_item(&path).await? else {
+        fail!(404, "Item not found");
+    };
+    let item = apply_item_update(&state, item, user, &headers, body).await?;
+    info!("Item at path {} updated", path);
+    let tags = state.database_accessor.get_item_tags(&item.id).await?;
+    success!(ItemSimplified {
+        tags,
+        ..ItemSimplified::from(item)
+    })
+}
+
+// 代码项目实际生效的历史版本数上限：项目自己的设置不能超过全站设置
+fn revision_limit(state: &AppState, item: &Item) -> i64 {
+    let limit = state.runtime_config.load().max_revisions;
+    item.max_revisions.map_or(limit, |x| x.min(limit))
+}
+
+async fn apply_item_update(
+    state: &AppState,
+    item: Item,
+    user: Option<CurrentUser>,
+    headers: &HeaderMap,
+    body: ApiItemUpdate,
+) -> Result<Item, ApiError> {
+    let expires_at = match body.expires_at {
+        Some(Some(x)) => Some(Some(
+            x.parse::<chrono::DateTime<chrono::Utc>>()
+                .map_err(|e| ApiError::new(400, e.to_string()))?
+                .with_timezone(&chrono::Local)
+                .naive_local(),
+        )),
+        Some(None) => Some(None),
+        None => None,
+    };
+
+    // 本次修改的执行者，记录在新的版本中
+    let editor = if let Some(key) = manage_key(headers) {
+        if !state.database_accessor.check_item_manage_key(&item, key) {
+            fail!(403, "No sufficient permissions");
+        }
+        // 和创建时一样，游客不能使用自定义路径
+        if body.short_path.is_some() {
+            fail!(
+                403,
+                "Guest users are not allowed to create items at customized paths"
+            );
+        }
+        item.creator.clone()
+    } else {
+        let Some(CurrentUser { user, .. }) = user else {
+            fail!(401, "Unauthorized");
+        };
+        info!(
+            "User {} is attempting to update item at path: {}",
+            user.id, item.short_path
+        );
+        if !can_access_item(state, &user, &item, UserPermission::Manage).await? {
+            fail!(403, "No sufficient permissions");
+        }
+        // 与创建项目时相同的权限要求，只检查本次修改涉及的部分
+        let mut required = Vec::new();
+        if body.data.is_some() {
+            required.push(match item.item_type {
+                ItemType::Code => UserPermission::Code,
+                ItemType::File => UserPermission::File,
+                ItemType::Link => UserPermission::Link,
+            });
+        }
+        if body.short_path.is_some() {
+            required.push(UserPermission::CustomPath);
+        }
+        if matches!(body.password, Some(Some(_))) {
+            required.push(UserPermission::ItemPassword);
+        }
+        if matches!(expires_at, Some(None)) {
+            required.push(UserPermission::NoExpiry);
+        }
+        if let Some(permission) = required
+            .into_iter()
+            .find(|x| !user.descriptor.contains(x.clone()))
         {
-            if token.is_expired() {
-                fail!(403, "Token expired");
+            info!(
+                "User {} lacks permission {:?} to update item at path: {}",
+                user.id, permission, item.short_path
+            );
+            fail!(403, "No sufficient permissions");
+        }
+        Some(user.id)
+    };
+
+    // 标签属于项目的创建者，游客的项目不能添加标签
+    let tags = match body.tags {
+        Some(tags) => {
+            if item
+                .creator
+                .as_deref()
+                .is_none_or(|x| x.starts_with("guest-"))
+            {
+                fail!(400, "Guest items cannot be tagged");
             }
-            if item.creator.clone().is_some_and(|x| token.user_id == x) {
-                state.database_accessor.remove_item(&item.id).await?;
-                if item.item_type == ItemType::File || item.item_type == ItemType::Code {
-                    state.file_accessor.remove_file(&item.data).await?;
+            Some(normalize_tags(&tags)?)
+        }
+        None => None,
+    };
+
+    // 修改计入项目创建者的配额
+    let quota = match item.creator.as_deref() {
+        Some(creator) => get_quota(state, creator).await?,
+        None => state.runtime_config.load().quota.clone(),
+    };
+    let mut updated = item.clone();
+    if let Some(expires_at) = expires_at {
+        check_expiry(&quota, expires_at)?;
+        updated.expires_at = expires_at;
+    }
+    if let Some(max_visits) = body.max_visits {
+        updated.max_visits = max_visits;
+    }
+    if let Some(extra_data) = body.extra_data {
+        updated.extra_data = extra_data;
+    }
+    if let Some(max_revisions) = body.max_revisions {
+        if max_revisions.is_some_and(|x| x < 0) {
+            fail!(400, "Invalid maximum number of revisions");
+        }
+        updated.max_revisions = max_revisions;
+    }
+    if let Some(password) = body.password {
+        updated.password_hash = match password {
+            Some(password) => Some(crate::util::hash_password(&password).await?),
+            None => None,
+        };
+    }
+    if let Some(short_path) = body.short_path {
+        if short_path.is_empty() || short_path == "__RANDOM__" || short_path.contains('/') {
+            fail!(400, "Invalid path");
+        }
+        if short_path != item.short_path && state.database_accessor.item_exists(&short_path).await?
+        {
+            info!("Item already exists at path: {}", short_path);
+            fail!(409, "Item already exists");
+        }
+        updated.short_path = short_path;
+    }
+
+    // 因过期而停用、尚未被清理的项目，修改后不再过期时恢复可用；存档的项目保持停用
+    let now = chrono::Local::now().naive_local();
+    let revive = !item.available
+        && item.should_drop_at.is_some()
+        && updated.expires_at.is_none_or(|x| x > now)
+        && updated.max_visits.is_none_or(|x| updated.visits < x);
+    if revive
+        && let Some(creator) = item.creator.as_deref()
+        && !creator.starts_with("guest-")
+    {
+        check_items(state, creator, &quota, 1).await?;
+    }
+
+    // 最后写入新的内容，避免前面的检查失败后留下无用的文件
+    let keep = revision_limit(state, &updated);
+    let mut old_file = None;
+    let mut previous = None;
+    if let Some(data) = body.data {
+        match item.item_type {
+            ItemType::Link => updated.data = data,
+            ItemType::Code => {
+                let size = data.len() as i64;
+                check_file_size(&quota, size)?;
+                // 保留历史版本时旧的内容仍然占用空间
+                let replaced = if keep > 0 { 0 } else { item.size.unwrap_or(0) };
+                if let Some(creator) = item.creator.as_deref() {
+                    check_storage(state, creator, &quota, size, replaced).await?;
+                }
+                let filename = format!("{}.txt", Uuid::now_v7());
+                state
+                    .file_accessor
+                    .write_file(filename.clone(), data.as_bytes())
+                    .await?;
+                let old_data = std::mem::replace(&mut updated.data, filename);
+                updated.size = Some(size);
+                updated.revision = item.revision + 1;
+                updated.updated_at = Some(now);
+                updated.updated_by = editor;
+                if keep > 0 {
+                    previous = Some(ItemRevision {
+                        id: Uuid::now_v7().to_string(),
+                        item_id: item.id.clone(),
+                        revision: item.revision,
+                        data: old_data,
+                        size: item.size.unwrap_or(0),
+                        created_at: item.updated_at.unwrap_or(item.created_at),
+                        created_by: item.updated_by.clone().or(item.creator.clone()),
+                    });
+                } else {
+                    old_file = Some(old_data);
                 }
-                success!(ItemSimplified::from(item))
             }
-            fail!(403, "No sufficient permission");
-        } else {
-            fail!(401, "Invalid token");
+            ItemType::File => fail!(409, "Upload a new file to replace the content of a File"),
         }
     }
-    let user = user.unwrap();
-    info!(
-        "User {} is attempting to delete item at path: {}",
-        user.id, path
-    );
-    if !user.descriptor.contains(UserPermission::Manage)
-        && !item.creator.clone().is_some_and(|x| user.id == x)
+
+    let redirect_from = (body.keep_redirect && updated.short_path != item.short_path)
+        .then_some(item.short_path.as_str());
+    let updated = state
+        .database_accessor
+        .update_item(&updated, redirect_from, previous.as_ref())
+        .await?;
+    if revive {
+        state
+            .database_accessor
+            .update_item_available(&updated.id, true)
+            .await?;
+    }
+    state
+        .database_accessor
+        .index_item(&updated, &state.file_accessor)
+        .await?;
+    if let Some(tags) = tags
+        && let Some(creator) = updated.creator.as_deref()
     {
-        info!(
-            "User {} has no sufficient permission to delete item at path: {}",
-            user.id, path
-        );
-        fail!(403, "No sufficient permission");
+        state
+            .database_accessor
+            .set_item_tags(&updated.id, creator, &tags)
+            .await?;
     }
-    state.database_accessor.remove_item(&item.id).await?;
-    if item.item_type == ItemType::File || item.item_type == ItemType::Code {
-        state.file_accessor.remove_file(&item.data).await?;
+    if let Some(old_file) = old_file {
+        state.file_accessor.remove_file(&old_file).await?;
     }
-    success!(ItemSimplified::from(item))
+    // 超出上限的旧版本（包括上限调低后多出的版本）
+    if updated.item_type == ItemType::Code {
+        for file in state
+            .database_accessor
+            .prune_item_revisions(&updated.id, keep)
+            .await?
+        {
+            state.file_accessor.remove_file(&file).await?;
+        }
+    }
+    Ok(Item {
+        available: updated.available || revive,
+        ..updated
+    })
 }
 
-#[instrument(skip(state, jar))]
-pub async fn get_user_items(
+// 历史版本可能包含被有意删除的内容，只有项目的管理者、持有管理密钥的游客
+// 以及拥有 permission 权限的用户可以查看，不能通过项目密码访问
+// 先检查权限再检查项目类型，避免无权访问的用户得知项目的信息
+async fn get_code_item_for_history(
+    state: &AppState,
+    path: &str,
+    user: Option<&CurrentUser>,
+    headers: &HeaderMap,
+    permission: UserPermission,
+) -> Result<Item, ApiError> {
+    let Some(item) = state.database_accessor.get_item(path).await? else {
+        fail!(404, "Item not found");
+    };
+    let allowed = if let Some(key) = manage_key(headers) {
+        state.database_accessor.check_item_manage_key(&item, key)
+    } else if let Some(CurrentUser { user, .. }) = user {
+        can_access_item(state, user, &item, permission).await?
+    } else {
+        fail!(401, "Unauthorized");
+    };
+    if !allowed {
+        fail!(403, "No sufficient permissions");
+    }
+    if item.item_type != ItemType::Code {
+        fail!(400, "Item is not a Code");
+    }
+    Ok(item)
+}
+
+// 读取代码项目某个版本的内容
+async fn read_revision(
+    state: &AppState,
+    item: &Item,
+    revision: i64,
+) -> Result<ApiRevisionContent, ApiError> {
+    let (info, data) = if revision == item.revision {
+        (ApiRevision::from_item(item), item.data.clone())
+    } else {
+        let Some(revision) = state
+            .database_accessor
+            .get_item_revision(&item.id, revision)
+            .await?
+        else {
+            fail!(404, "Revision not found");
+        };
+        let data = revision.data.clone();
+        (ApiRevision::from(revision), data)
+    };
+    let Some(content) = state.file_accessor.get_string(data).await else {
+        fail!(404, "Revision content not found");
+    };
+    Ok(ApiRevisionContent { info, content })
+}
+
+// 列出代码项目的所有版本，当前版本在最前
+#[instrument(skip(state, user, headers))]
+pub async fn get_revisions(
+    ApiPath(path): ApiPath<String>,
     State(state): State<AppState>,
-    jar: PrivateCookieJar,
+    OptionalUser(user): OptionalUser,
+    headers: HeaderMap,
+) -> ApiResult {
+    let item = get_code_item_for_history(
+        &state,
+        &path,
+        user.as_ref(),
+        &headers,
+        UserPermission::ViewAllItems,
+    )
+    .await?;
+    let mut items = vec![ApiRevision::from_item(&item)];
+    items.extend(
+        state
+            .database_accessor
+            .get_item_revisions(&item.id)
+            .await?
+            .into_iter()
+            .map(ApiRevision::from),
+    );
+    success!(ApiList {
+        total: items.len() as i64,
+        items,
+    })
+}
+
+#[instrument(skip(state, user, headers))]
+pub async fn get_revision(
+    ApiPath((path, revision)): ApiPath<(String, i64)>,
+    State(state): State<AppState>,
+    OptionalUser(user): OptionalUser,
+    headers: HeaderMap,
+) -> ApiResult {
+    let item = get_code_item_for_history(
+        &state,
+        &path,
+        user.as_ref(),
+        &headers,
+        UserPermission::ViewAllItems,
+    )
+    .await?;
+    success!(read_revision(&state, &item, revision).await?)
+}
+
+// 两个版本之间的差异，to 缺省为当前版本
+#[instrument(skip(state, user, headers))]
+pub async fn diff_revisions(
+    ApiPath(path): ApiPath<String>,
+    State(state): State<AppState>,
+    OptionalUser(user): OptionalUser,
+    headers: HeaderMap,
+    ApiQuery(query): ApiQuery<ApiRevisionDiffQuery>,
+) -> ApiResult {
+    let item = get_code_item_for_history(
+        &state,
+        &path,
+        user.as_ref(),
+        &headers,
+        UserPermission::ViewAllItems,
+    )
+    .await?;
+    let to = query.to.unwrap_or(item.revision);
+    let old = read_revision(&state, &item, query.from).await?;
+    let new = read_revision(&state, &item, to).await?;
+    let diff = similar::TextDiff::from_lines(&old.content, &new.content)
+        .unified_diff()
+        .context_radius(3)
+        .header(
+            &format!("{}@{}", item.short_path, query.from),
+            &format!("{}@{}", item.short_path, to),
+        )
+        .to_string();
+    success!(ApiRevisionDiff {
+        from: query.from,
+        to,
+        diff,
+    })
+}
+
+// 恢复到某个历史版本：以该版本的内容创建一个新版本，不会丢弃之后的版本
+#[instrument(skip(state, user, headers))]
+pub async fn restore_revision(
+    ApiPath((path, revision)): ApiPath<(String, i64)>,
+    State(state): State<AppState>,
+    OptionalUser(user): OptionalUser,
+    headers: HeaderMap,
+) -> ApiResult {
+    let item = get_code_item_for_history(
+        &state,
+        &path,
+        user.as_ref(),
+        &headers,
+        UserPermission::Manage,
+    )
+    .await?;
+    if revision == item.revision {
+        fail!(409, "Revision is already the current content");
+    }
+    let content = read_revision(&state, &item, revision).await?.content;
+    let body = ApiItemUpdate {
+        data: Some(content),
+        ..Default::default()
+    };
+    let item = apply_item_update(&state, item, user, &headers, body).await?;
+    info!(
+        "Item at path {} restored to revision {} as revision {}",
+        path, revision, item.revision
+    );
+    success!(ApiCode::read_from(item, state.file_accessor.clone()).await)
+}
+
+// 全文搜索项目：普通用户搜索自己的项目和所在组的项目，拥有 ViewAllItems 权限的用户搜索所有项目
+#[instrument(skip(state, current))]
+pub async fn search_items(
+    State(state): State<AppState>,
+    current: CurrentUser,
     ApiQuery(params): ApiQuery<HashMap<String, String>>,
 ) -> ApiResult {
-    let user = try_get_user(&state, &jar).await;
-    if user.is_none() {
-        fail!(401, "Invalid or missing token");
-    }
-    let user = user.unwrap();
-    if params
-        .get("user")
-        .is_some_and(|x| &user.id != x && !user.descriptor.contains(UserPermission::Manage))
-    {
-        fail!(403, "Insufficient permission");
+    let query = params.get("q").map(|x| x.trim()).unwrap_or("");
+    // 索引使用 trigram 分词，少于 3 个字符的查询无法匹配
+    if query.chars().count() < 3 {
+        fail!(400, "Search query must be at least 3 characters");
     }
     let offset = params
         .get("offset")
@@ -474,39 +1094,158 @@ pub async fn get_user_items(
     let limit = params
         .get("limit")
         .and_then(|x| x.parse::<i64>().ok())
-        .unwrap_or(50)
-        .min(100); // 设置最大限制为 100 个项目
+        .unwrap_or(20)
+        .min(100);
+    let viewer =
+        (!current.has_permission(UserPermission::ViewAllItems)).then_some(current.user.id.as_str());
 
-    let items = state
+    let hits = state
         .database_accessor
-        .get_user_items(params.get("user").unwrap_or(&user.id), offset, limit)
-        .await?
-        .into_iter()
-        .map(|x| ItemSimplified::from(x))
-        .collect::<Vec<_>>();
+        .search_items(query, viewer, offset, limit)
+        .await?;
     let total = state
         .database_accessor
-        .count_user_items(params.get("user").unwrap_or(&user.id))
+        .count_search_items(query, viewer)
         .await?;
+    let mut items = Vec::with_capacity(hits.len());
+    for (id, snippet) in hits {
+        if let Some(item) = state.database_accessor.get_item_by_id(&id).await? {
+            let tags = state.database_accessor.get_item_tags(&item.id).await?;
+            let result = ApiSearchResult::new(item, &snippet);
+            items.push(ApiSearchResult {
+                item: ItemSimplified {
+                    tags,
+                    ..result.item
+                },
+                ..result
+            });
+        }
+    }
     success!(ApiList { total, items })
 }
 
-#[instrument(skip(state, jar))]
+// 按查询参数列出项目，多取一个项目用于判断是否还有下一页
+async fn list_items(
+    state: &AppState,
+    filter: ItemFilter,
+    query: &ApiItemQuery,
+) -> Result<ApiItemPage, ApiError> {
+    let cursor = match query.cursor.as_deref() {
+        Some(cursor) => match ItemCursor::decode(cursor) {
+            // 游标只能用于生成它时的排序方式
+            Some(x) if x.sort == filter.sort && x.order == filter.order => Some(x),
+            _ => return Err(ApiError::new(400, "Invalid cursor".to_string())),
+        },
+        None => None,
+    };
+    let offset = query.offset.unwrap_or(0).max(0);
+    let limit = query.limit.unwrap_or(50).clamp(1, 100); // 设置最大限制为 100 个项目
+
+    let mut items = state
+        .database_accessor
+        .list_items(&filter, cursor.as_ref(), offset, limit + 1)
+        .await?;
+    let next_cursor = if items.len() as i64 > limit {
+        items.truncate(limit as usize);
+        items.last().map(|x| ItemCursor::after(x, &filter).encode())
+    } else {
+        None
+    };
+    let total = state.database_accessor.count_items(&filter).await?;
+
+    let ids = items.iter().map(|x| x.id.clone()).collect::<Vec<_>>();
+    let mut tags: HashMap<String, Vec<String>> = HashMap::new();
+    for (item_id, name) in state.database_accessor.get_items_tags(&ids).await? {
+        tags.entry(item_id).or_default().push(name);
+    }
+    let items = items
+        .into_iter()
+        .map(|x| ItemSimplified {
+            tags: tags.remove(&x.id).unwrap_or_default(),
+            ..ItemSimplified::from(x)
+        })
+        .collect();
+    Ok(ApiItemPage {
+        total,
+        items,
+        next_cursor,
+    })
+}
+
+#[instrument(skip(state, user))]
+pub async fn get_user_items(
+    State(state): State<AppState>,
+    CurrentUser { user, .. }: CurrentUser,
+    ApiQuery(query): ApiQuery<ApiItemQuery>,
+) -> ApiResult {
+    let view_all = user.descriptor.contains(UserPermission::ViewAllItems);
+    if query
+        .user
+        .as_ref()
+        .is_some_and(|x| x != &user.id && !view_all)
+    {
+        fail!(403, "No sufficient permissions");
+    }
+
+    let mut filter = query.filter();
+    // 按组筛选时列出组内的所有项目，需要是组的成员
+    if let Some(group_id) = &query.group {
+        if !view_all
+            && state
+                .database_accessor
+                .get_group_member(group_id, &user.id)
+                .await?
+                .is_none()
+        {
+            fail!(403, "No sufficient permissions");
+        }
+        filter.group_id = Some(group_id.clone());
+        filter.creator = query.user.clone();
+    } else {
+        filter.creator = Some(query.user.clone().unwrap_or(user.id));
+    }
+    success!(list_items(&state, filter, &query).await?)
+}
+
+// 旧的图片列表接口，等同于 /items?img=true
+#[instrument(skip(state, user))]
 pub async fn get_user_img_items(
+    state: State<AppState>,
+    user: CurrentUser,
+    ApiQuery(mut query): ApiQuery<ApiItemQuery>,
+) -> ApiResult {
+    query.img = Some(true);
+    get_user_items(state, user, ApiQuery(query)).await
+}
+
+#[instrument(skip(state, _viewer))]
+pub async fn get_all_items(
     State(state): State<AppState>,
-    jar: PrivateCookieJar,
+    _viewer: RequirePermission<ViewAllItems>,
+    ApiQuery(query): ApiQuery<ApiItemQuery>,
+) -> ApiResult {
+    let mut filter = query.filter();
+    filter.creator = query.user.clone();
+    filter.group_id = query.group.clone();
+    success!(list_items(&state, filter, &query).await?)
+}
+
+// 查看项目的访问日志：需要 ViewLogs 权限，查看不属于自己或所在组的项目还需要 ViewAllItems 权限
+#[instrument(skip(state, current))]
+pub async fn get_item_logs(
+    ApiPath(path): ApiPath<String>,
+    State(state): State<AppState>,
+    current: CurrentUser,
     ApiQuery(params): ApiQuery<HashMap<String, String>>,
 ) -> ApiResult {
-    let user = try_get_user(&state, &jar).await;
-    if user.is_none() {
-        fail!(401, "Invalid or missing token");
-    }
-    let user = user.unwrap();
-    if params
-        .get("user")
-        .is_some_and(|x| &user.id != x && !user.descriptor.contains(UserPermission::Manage))
-    {
-        fail!(403, "Insufficient permission");
+    if !current.has_permission(UserPermission::ViewLogs) {
+        fail!(403, "No sufficient permissions");
+    }
+    let Some(item) = state.database_accessor.get_item(&path).await? else {
+        fail!(404, "Item not found");
+    };
+    if !can_access_item(&state, &current.user, &item, UserPermission::ViewAllItems).await? {
+        fail!(403, "No sufficient permissions");
     }
     let offset = params
         .get("offset")
@@ -516,52 +1255,63 @@ pub async fn get_user_img_items(
         .get("limit")
         .and_then(|x| x.parse::<i64>().ok())
         .unwrap_or(50)
-        .min(100); // 设置最大限制为 100 个项目
+        .min(100); // 设置最大限制为 100 条日志
 
     let items = state
         .database_accessor
-        .get_user_img_items(params.get("user").unwrap_or(&user.id), offset, limit)
+        .get_item_access_logs(&item.id, offset, limit)
         .await?
         .into_iter()
-        .map(|x| ItemSimplified::from(x))
+        .map(ApiAccessLog::from)
         .collect::<Vec<_>>();
     let total = state
         .database_accessor
-        .count_user_img_items(params.get("user").unwrap_or(&user.id))
+        .count_item_access_logs(&item.id)
         .await?;
     success!(ApiList { total, items })
 }
 
-#[instrument(skip(state, jar))]
-pub async fn get_all_items(
+// 登录后凭管理密钥认领之前以游客身份创建的项目，认领的项目计入用户的配额
+#[instrument(skip(state, user, body))]
+pub async fn claim_items(
     State(state): State<AppState>,
-    jar: PrivateCookieJar,
-    ApiQuery(params): ApiQuery<HashMap<String, String>>,
+    CurrentUser { user, .. }: CurrentUser,
+    ApiJson(body): ApiJson<ApiItemClaim>,
 ) -> ApiResult {
-    let user = try_get_user(&state, &jar).await;
-    if user.is_none() {
-        fail!(401, "Unauthorized");
+    let mut items = Vec::new();
+    for key in &body.keys {
+        if let Some(item) = state.database_accessor.get_item_by_manage_key(key).await?
+            && item
+                .creator
+                .as_deref()
+                .is_some_and(|x| x.starts_with("guest-"))
+            && !items.iter().any(|x: &Item| x.id == item.id)
+        {
+            items.push(item);
+        }
     }
-    if !user.unwrap().descriptor.contains(UserPermission::Manage) {
-        fail!(403, "Insufficient permission");
+
+    let quota = get_quota(&state, &user.id).await?;
+    let available = items.iter().filter(|x| x.available).count() as i64;
+    let size = items.iter().map(|x| x.size.unwrap_or(0)).sum();
+    for item in &items {
+        check_file_size(&quota, item.size.unwrap_or(0))?;
     }
-    let offset = params
-        .get("offset")
-        .and_then(|x| x.parse::<i64>().ok())
-        .unwrap_or(0);
-    let limit = params
-        .get("limit")
-        .and_then(|x| x.parse::<i64>().ok())
-        .unwrap_or(50)
-        .min(100); // 设置最大限制为 100 个项目
+    check_items(&state, &user.id, &quota, available).await?;
+    check_storage(&state, &user.id, &quota, size, 0).await?;
 
-    let items = state
-        .database_accessor
-        .get_all_items(offset, limit)
-        .await?
+    let ids = items.iter().map(|x| x.id.clone()).collect::<Vec<_>>();
+    state.database_accessor.claim_items(&ids, &user.id).await?;
+    info!("User {} claimed {} guest items", user.id, ids.len());
+    let items = items
         .into_iter()
-        .map(|x| ItemSimplified::from(x))
+        .map(|x| ItemSimplified {
+            creator: Some(user.id.clone()),
+            ..ItemSimplified::from(x)
+        })
         .collect::<Vec<_>>();
-    let total = state.database_accessor.count_all_items().await?;
-    success!(ApiList { total, items })
+    success!(ApiList {
+        total: items.len() as i64,
+        items,
+    })
 }
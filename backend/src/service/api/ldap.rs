@@ -0,0 +1,350 @@
+use crate::service::api::result::ApiError;
+use crate::service::api::user::resolve_external_user;
+use crate::types::{AppState, LdapConfig, User, UserPermission};
+use anyhow::anyhow;
+use ldap3::{LdapConnAsync, LdapConnSettings, Scope, SearchEntry, ldap_escape};
+use tracing::debug;
+
+const AUTH_SOURCE: &str = "ldap";
+
+// 读取条目某个属性的第一个值
+fn first_attr<'a>(entry: &'a SearchEntry, attr: &str) -> Option<&'a str> {
+    entry
+        .attrs
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(attr))
+        .and_then(|(_, v)| v.first())
+        .map(|v| v.as_str())
+}
+
+// 先搜索登录名对应的条目，再用条目的 DN 和密码绑定
+// 目录中找不到用户或密码错误时返回 None，连接或配置出错时返回 Err
+pub async fn authenticate(
+    config: &LdapConfig,
+    login: &str,
+    password: &str,
+) -> anyhow::Result<Option<SearchEntry>> {
+    let settings = LdapConnSettings::new().set_starttls(config.starttls);
+    let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &config.url).await?;
+    ldap3::drive!(conn);
+
+    if let Some(bind_dn) = config.bind_dn.as_deref() {
+        ldap.simple_bind(bind_dn, config.bind_password.as_deref().unwrap_or(""))
+            .await?
+            .success()?;
+    }
+    let filter = config.user_filter.replace("{login}", &ldap_escape(login));
+    let attrs = vec![
+        config.email_attribute.as_str(),
+        config.name_attribute.as_str(),
+        config.group_attribute.as_str(),
+        "entryUUID",
+    ];
+    let (entries, _) = ldap
+        .search(&config.base_dn, Scope::Subtree, &filter, attrs)
+        .await?
+        .success()?;
+    // 登录名必须唯一对应一个条目
+    if entries.len() != 1 {
+        debug!(
+            "LDAP search for {} returned {} entries",
+            login,
+            entries.len()
+        );
+        let _ = ldap.unbind().await;
+        return Ok(None);
+    }
+    let entry = SearchEntry::construct(entries.into_iter().next().unwrap());
+
+    let bound = ldap
+        .simple_bind(&entry.dn, password)
+        .await?
+        .success()
+        .is_ok();
+    let _ = ldap.unbind().await;
+    Ok(bound.then_some(entry))
+}
+
+// 根据配置把条目所属的组映射为权限
+fn descriptor_from_entry(config: &LdapConfig, entry: &SearchEntry) -> i64 {
+    let mut descriptor = UserPermission::to_descriptor(&config.default_permissions);
+    let groups = entry
+        .attrs
+        .iter()
+        .filter(|(k, _)| k.eq_ignore_ascii_case(&config.group_attribute))
+        .flat_map(|(_, v)| v);
+    for group in groups {
+        for (dn, permissions) in &config.group_permissions {
+            if dn.eq_ignore_ascii_case(group) {
+                descriptor |= UserPermission::to_descriptor(permissions);
+            }
+        }
+    }
+    descriptor
+}
+
+// 找到或创建目录条目对应的用户
+pub async fn resolve_user(
+    state: &AppState,
+    config: &LdapConfig,
+    entry: SearchEntry,
+) -> Result<User, ApiError> {
+    let Some(email) = first_attr(&entry, &config.email_attribute) else {
+        return Err(anyhow!("LDAP entry {} has no {}", entry.dn, config.email_attribute).into());
+    };
+    let name = first_attr(&entry, &config.name_attribute).unwrap_or(email);
+    // 优先使用不会随改名变化的 entryUUID，目录不支持时退回到 DN
+    let external_id = first_attr(&entry, "entryUUID").unwrap_or(&entry.dn);
+    let descriptor = descriptor_from_entry(config, &entry);
+    resolve_external_user(
+        &state.database_accessor,
+        AUTH_SOURCE,
+        external_id,
+        name,
+        email,
+        descriptor,
+        config.link_existing_users,
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::api::result::ApiResult;
+    use crate::service::api::user::login;
+    use crate::types::ClientInfo;
+    use axum::Json;
+    use axum::extract::State;
+    use axum::http::StatusCode;
+    use axum::http::header::{COOKIE, SET_COOKIE};
+    use axum_extra::extract::PrivateCookieJar;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    const ADMINS: &str = "cn=spectra-admins,ou=groups,dc=example,dc=com";
+    const WRITERS: &str = "cn=writers,ou=groups,dc=example,dc=com";
+
+    fn config() -> LdapConfig {
+        serde_json::from_value(json!({
+            "url": "ldap://localhost:389",
+            "base_dn": "ou=people,dc=example,dc=com",
+            "default_permissions": ["Link"],
+            "group_permissions": {
+                ADMINS: ["Manage"],
+                WRITERS: ["Code", "File"],
+            },
+        }))
+        .unwrap()
+    }
+
+    fn entry(attrs: &[(&str, &[&str])]) -> SearchEntry {
+        SearchEntry {
+            dn: "uid=alice,ou=people,dc=example,dc=com".to_string(),
+            attrs: attrs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.iter().map(|x| x.to_string()).collect()))
+                .collect(),
+            bin_attrs: HashMap::new(),
+        }
+    }
+
+    #[test]
+    fn reads_first_attribute_value() {
+        let entry = entry(&[("mail", &["a@example.com", "b@example.com"])]);
+        assert_eq!(first_attr(&entry, "MAIL"), Some("a@example.com"));
+        assert_eq!(first_attr(&entry, "cn"), None);
+    }
+
+    #[test]
+    fn maps_groups_to_permissions() {
+        let config = config();
+        let link = UserPermission::to_descriptor(&[UserPermission::Link]);
+        assert_eq!(descriptor_from_entry(&config, &entry(&[])), link);
+        assert_eq!(
+            descriptor_from_entry(
+                &config,
+                &entry(&[("memberOf", &[WRITERS, "cn=other,dc=example,dc=com"])])
+            ),
+            UserPermission::to_descriptor(&[
+                UserPermission::Link,
+                UserPermission::Code,
+                UserPermission::File,
+            ])
+        );
+    }
+
+    #[test]
+    fn matches_groups_case_insensitively() {
+        let config = config();
+        // 属性名和 DN 都不区分大小写
+        let entry = entry(&[(
+            "MEMBEROF",
+            &["CN=Spectra-Admins,OU=Groups,DC=example,DC=com"],
+        )]);
+        assert_eq!(
+            descriptor_from_entry(&config, &entry),
+            UserPermission::to_descriptor(&[UserPermission::Link, UserPermission::Manage])
+        );
+    }
+
+    // 以下测试通过 user::login 登录，可能使用 LDAP 也可能使用本地密码
+    async fn try_login(state: &AppState, email: &str, password: &str) -> ApiResult {
+        login(
+            State(state.clone()),
+            PrivateCookieJar::new((**state.cookie_key.load()).clone()),
+            ClientInfo::default(),
+            Json(json!({"email": email, "password": password})),
+        )
+        .await
+    }
+
+    fn status(result: ApiResult) -> StatusCode {
+        match result {
+            Ok(res) => res.status(),
+            Err(ApiError::Anyhow(e)) => panic!("unexpected error: {e}"),
+            Err(ApiError::Other((status, _))) => status,
+        }
+    }
+
+    // 登录成功后浏览器拿到的会话令牌
+    fn session_token(state: &AppState, result: ApiResult) -> String {
+        let res = match result {
+            Ok(res) => res,
+            Err(ApiError::Anyhow(e)) => panic!("unexpected error: {e}"),
+            Err(ApiError::Other((status, message))) => panic!("unexpected {status}: {message}"),
+        };
+        let mut headers = axum::http::HeaderMap::new();
+        for cookie in res.headers().get_all(SET_COOKIE) {
+            let cookie = cookie.to_str().unwrap().split(';').next().unwrap();
+            headers.append(COOKIE, cookie.parse().unwrap());
+        }
+        let jar = PrivateCookieJar::from_headers(&headers, (**state.cookie_key.load()).clone());
+        jar.get("token").unwrap().value().to_string()
+    }
+
+    async fn local_user(state: &AppState) -> User {
+        state
+            .database_accessor
+            .create_user("local-1", "Bob", "bob@example.com", "bob-password", 0, None)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_local_password_when_directory_is_unavailable() {
+        let mut state = AppState::temporary().await;
+        let mut config = config();
+        config.url = "ldap://127.0.0.1:1".to_string();
+        state.ldap = Some(Arc::new(config));
+        let user = local_user(&state).await;
+
+        let token = session_token(
+            &state,
+            try_login(&state, "bob@example.com", "bob-password").await,
+        );
+        let session = state
+            .database_accessor
+            .get_active_session(&token)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(session.user_id, user.id);
+        assert_eq!(
+            status(try_login(&state, "bob@example.com", "wrong-password").await),
+            StatusCode::UNAUTHORIZED
+        );
+    }
+
+    // 集成测试使用 scripts/ldap 中的目录，设置 SPECTRA_TEST_LDAP_URL 后用 --ignored 运行
+    fn directory_config() -> LdapConfig {
+        let url = std::env::var("SPECTRA_TEST_LDAP_URL")
+            .expect("SPECTRA_TEST_LDAP_URL must point to the directory in scripts/ldap");
+        let var = |key: &str, default: &str| std::env::var(key).unwrap_or(default.to_string());
+        serde_json::from_value(json!({
+            "url": url,
+            "base_dn": var("SPECTRA_TEST_LDAP_BASE_DN", "dc=example,dc=com"),
+            "bind_dn": var(
+                "SPECTRA_TEST_LDAP_BIND_DN",
+                "cn=search,ou=svcaccts,ou=users,dc=example,dc=com"
+            ),
+            "bind_password": var("SPECTRA_TEST_LDAP_BIND_PASSWORD", "search-password"),
+            "default_permissions": ["Link"],
+            "group_permissions": {
+                var("SPECTRA_TEST_LDAP_WRITERS_DN", "ou=writers,ou=groups,dc=example,dc=com"):
+                    ["Code"],
+            },
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the directory in scripts/ldap"]
+    async fn searches_then_binds_against_directory() {
+        let config = directory_config();
+        // 邮箱和 uid 都可以作为登录名
+        for login in ["alice@example.com", "alice"] {
+            let entry = authenticate(&config, login, "alice-password")
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(first_attr(&entry, "mail"), Some("alice@example.com"));
+        }
+        let wrong = authenticate(&config, "alice@example.com", "wrong-password").await;
+        assert!(wrong.unwrap().is_none());
+        let unknown = authenticate(&config, "nobody@example.com", "alice-password").await;
+        assert!(unknown.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the directory in scripts/ldap"]
+    async fn escapes_login_in_search_filter() {
+        let config = directory_config();
+        // 不转义时过滤器变为 (|(mail=alice@example.com)(mail=nobody)...)，仍然只找到 alice
+        let login = "alice@example.com)(mail=nobody";
+        let result = authenticate(&config, login, "alice-password").await;
+        assert!(result.unwrap().is_none());
+        let result = authenticate(&config, "*", "alice-password").await;
+        assert!(result.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    #[ignore = "needs the directory in scripts/ldap"]
+    async fn logs_in_directory_users() {
+        let mut state = AppState::temporary().await;
+        state.ldap = Some(Arc::new(directory_config()));
+        let da = &state.database_accessor;
+
+        let token = session_token(
+            &state,
+            try_login(&state, "alice@example.com", "alice-password").await,
+        );
+        let user = da
+            .get_user_by_email("alice@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.auth_source, AUTH_SOURCE);
+        assert_eq!(
+            user.descriptor,
+            UserPermission::to_descriptor(&[UserPermission::Link, UserPermission::Code])
+        );
+        let session = da.get_active_session(&token).await.unwrap().unwrap();
+        assert_eq!(session.user_id, user.id);
+
+        // 空密码在 LDAP 中是匿名绑定，不能当作登录成功
+        assert_eq!(
+            status(try_login(&state, "alice@example.com", "").await),
+            StatusCode::UNAUTHORIZED
+        );
+        // 目录中没有的用户使用本地密码登录
+        let bob = local_user(&state).await;
+        let token = session_token(
+            &state,
+            try_login(&state, "bob@example.com", "bob-password").await,
+        );
+        let session = da.get_active_session(&token).await.unwrap().unwrap();
+        assert_eq!(session.user_id, bob.id);
+    }
+}
@@ -1,9 +1,11 @@
-use crate::service::api::result::{ApiResponse, ApiResult};
+use crate::service::api::auth::{OptionalUser, require_permission};
+use crate::service::api::result::{ApiJson, ApiResponse, ApiResult};
+use crate::service::api::setup::check_setup_code;
 use crate::shadow;
-use crate::types::{AppState, ToPermission, UserPermission};
+use crate::types::{AppState, ClientInfo, QuotaConfig, UserPermission};
 use crate::{fail, success};
 use axum::extract::State;
-use axum_extra::extract::PrivateCookieJar;
+use axum::http::HeaderMap;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::str::FromStr;
@@ -40,12 +42,16 @@ pub async fn get_information() -> axum::Json<ApiResponse<About>> {
 pub struct Config {
     turnstile_enabled: bool,
     turnstile_site_key: String,
+    oidc_enabled: bool,
+    registration_enabled: bool,
 }
 pub async fn get_config(State(state): State<AppState>) -> axum::Json<ApiResponse<Config>> {
     let t_conf = state.runtime_config.load().turnstile.clone();
     let config = Config {
         turnstile_enabled: t_conf.enabled,
         turnstile_site_key: t_conf.site_key,
+        oidc_enabled: state.oidc.is_some(),
+        registration_enabled: state.runtime_config.load().registration.enabled,
     };
     ApiResponse::from(config).into()
 }
@@ -59,6 +65,19 @@ pub struct AdminConfig {
     turnstile_enabled: bool,
     turnstile_site_key: String,
     turnstile_secret_key: String,
+    smtp_enabled: bool,
+    smtp_host: String,
+    smtp_port: u16,
+    smtp_username: String,
+    smtp_password: String,
+    smtp_from: String,
+    smtp_security: String,
+    registration_enabled: bool,
+    registration_approval: bool,
+    registration_domains: Vec<String>,
+    registration_permissions: Vec<UserPermission>,
+    quota: QuotaConfig,
+    max_revisions: i64,
 }
 
 #[derive(Deserialize)]
@@ -70,32 +89,33 @@ pub struct AdminConfigUpdate {
     turnstile_enabled: Option<bool>,
     turnstile_site_key: Option<String>,
     turnstile_secret_key: Option<String>,
+    smtp_enabled: Option<bool>,
+    smtp_host: Option<String>,
+    smtp_port: Option<u16>,
+    smtp_username: Option<String>,
+    smtp_password: Option<String>,
+    smtp_from: Option<String>,
+    smtp_security: Option<String>,
+    registration_enabled: Option<bool>,
+    registration_approval: Option<bool>,
+    registration_domains: Option<Vec<String>>,
+    registration_permissions: Option<Vec<UserPermission>>,
+    quota: Option<QuotaConfig>,
+    max_revisions: Option<i64>,
 }
 
-pub async fn admin_get_config(State(state): State<AppState>, jar: PrivateCookieJar) -> ApiResult {
+pub async fn admin_get_config(
+    State(state): State<AppState>,
+    OptionalUser(current): OptionalUser,
+    client: ClientInfo,
+    headers: HeaderMap,
+) -> ApiResult {
     let setup = state.runtime_config.load().setup;
+    // 完成初始设置之前还没有管理员账号，改为要求提供设置代码
     if setup {
-        let token = jar.get("token").map(|c| c.value().to_string());
-        if token.is_none() {
-            fail!(401, "Unauthorized");
-        }
-        let user_token = state.user_tokens.get(&token.unwrap());
-        if user_token.is_none() {
-            fail!(401, "Unauthorized");
-        }
-        let current_user = state
-            .database_accessor
-            .get_user_by_id(&user_token.unwrap().user_id)
-            .await
-            .unwrap_or(None);
-        if current_user.is_none()
-            || !current_user
-                .unwrap()
-                .descriptor
-                .contains(UserPermission::Manage)
-        {
-            fail!(403, "Forbidden");
-        }
+        require_permission(current, UserPermission::ManageConfig)?;
+    } else {
+        check_setup_code(&state, &client, &headers)?;
     }
 
     let rt = state.runtime_config.load();
@@ -107,38 +127,54 @@ pub async fn admin_get_config(State(state): State<AppState>, jar: PrivateCookieJ
         turnstile_enabled: rt.turnstile.enabled,
         turnstile_site_key: rt.turnstile.site_key.clone(),
         turnstile_secret_key: rt.turnstile.secret_key.clone(),
+        smtp_enabled: rt.smtp.enabled,
+        smtp_host: rt.smtp.host.clone(),
+        smtp_port: rt.smtp.port,
+        smtp_username: rt.smtp.username.clone(),
+        smtp_password: rt.smtp.password.clone(),
+        smtp_from: rt.smtp.from.clone(),
+        smtp_security: rt.smtp.security.clone(),
+        registration_enabled: rt.registration.enabled,
+        registration_approval: rt.registration.approval,
+        registration_domains: rt.registration.domains.clone(),
+        registration_permissions: UserPermission::from_i64(rt.registration.descriptor),
+        quota: rt.quota.clone(),
+        max_revisions: rt.max_revisions,
     };
     crate::success!(config)
 }
 
 pub async fn admin_set_config(
     State(state): State<AppState>,
-    jar: PrivateCookieJar,
-    axum::Json(update): axum::Json<AdminConfigUpdate>,
+    OptionalUser(current): OptionalUser,
+    client: ClientInfo,
+    headers: HeaderMap,
+    ApiJson(update): ApiJson<AdminConfigUpdate>,
 ) -> ApiResult {
     let setup = state.runtime_config.load().setup;
+    // 完成初始设置之前还没有管理员账号，改为要求提供设置代码
     if setup {
-        let token = jar.get("token").map(|c| c.value().to_string());
-        if token.is_none() {
-            fail!(401, "Unauthorized");
-        }
-        let user_token = state.user_tokens.get(&token.unwrap());
-        if user_token.is_none() {
-            fail!(401, "Unauthorized");
-        }
-        let current_user = state
-            .database_accessor
-            .get_user_by_id(&user_token.unwrap().user_id)
-            .await
-            .unwrap_or(None);
-        if current_user.is_none()
-            || !current_user
-                .unwrap()
-                .descriptor
-                .contains(UserPermission::Manage)
-        {
-            fail!(403, "Forbidden");
+        let current = require_permission(current, UserPermission::ManageConfig)?;
+        if let Some(ref v) = update.registration_permissions {
+            current.check_grantable(v)?;
         }
+    } else {
+        check_setup_code(&state, &client, &headers)?;
+    }
+
+    // 先检查所有字段，避免部分设置已经写入后才因为某个字段无效而失败
+    if let Some(ref v) = update.smtp_security
+        && !["none", "starttls", "tls"].contains(&v.as_str())
+    {
+        fail!(400, "Invalid SMTP security mode");
+    }
+    if let Some(ref v) = update.refresh_time
+        && croner::Cron::from_str(v).is_err()
+    {
+        fail!(400, "Invalid cron expression");
+    }
+    if update.max_revisions.is_some_and(|x| x < 0) {
+        fail!(400, "Invalid maximum number of revisions");
     }
 
     let mut new_config = (**state.runtime_config.load()).clone();
@@ -177,34 +213,113 @@ pub async fn admin_set_config(
         new_config.turnstile.secret_key = v.clone();
         let _ = da.set_sys_config("turnstile_secret_key", v).await;
     }
+    if let Some(ref v) = update.smtp_security {
+        new_config.smtp.security = v.clone();
+        let _ = da.set_sys_config("smtp_security", v).await;
+    }
+    if let Some(v) = update.smtp_enabled {
+        new_config.smtp.enabled = v;
+        let _ = da
+            .set_sys_config("smtp_enabled", if v { "true" } else { "false" })
+            .await;
+    }
+    if let Some(ref v) = update.smtp_host {
+        new_config.smtp.host = v.clone();
+        let _ = da.set_sys_config("smtp_host", v).await;
+    }
+    if let Some(v) = update.smtp_port {
+        new_config.smtp.port = v;
+        let _ = da.set_sys_config("smtp_port", &v.to_string()).await;
+    }
+    if let Some(ref v) = update.smtp_username {
+        new_config.smtp.username = v.clone();
+        let _ = da.set_sys_config("smtp_username", v).await;
+    }
+    if let Some(ref v) = update.smtp_password {
+        new_config.smtp.password = v.clone();
+        let _ = da.set_sys_config("smtp_password", v).await;
+    }
+    if let Some(ref v) = update.smtp_from {
+        new_config.smtp.from = v.clone();
+        let _ = da.set_sys_config("smtp_from", v).await;
+    }
+    if let Some(v) = update.registration_enabled {
+        new_config.registration.enabled = v;
+        let _ = da
+            .set_sys_config("registration_enabled", if v { "true" } else { "false" })
+            .await;
+    }
+    if let Some(v) = update.registration_approval {
+        new_config.registration.approval = v;
+        let _ = da
+            .set_sys_config("registration_approval", if v { "true" } else { "false" })
+            .await;
+    }
+    if let Some(ref v) = update.registration_domains {
+        let domains: Vec<String> = v
+            .iter()
+            .map(|x| x.trim().trim_start_matches('@').to_string())
+            .filter(|x| !x.is_empty())
+            .collect();
+        let _ = da
+            .set_sys_config("registration_domains", &domains.join(","))
+            .await;
+        new_config.registration.domains = domains;
+    }
+    if let Some(ref v) = update.registration_permissions {
+        let descriptor = UserPermission::to_descriptor(v);
+        new_config.registration.descriptor = descriptor;
+        let _ = da
+            .set_sys_config("registration_descriptor", &descriptor.to_string())
+            .await;
+    }
+    if let Some(ref v) = update.quota {
+        // 默认配额中的空值和负数都表示不限制
+        let quota = QuotaConfig {
+            storage: v.storage.filter(|x| *x >= 0),
+            items: v.items.filter(|x| *x >= 0),
+            file_size: v.file_size.filter(|x| *x >= 0),
+            expiry: v.expiry.filter(|x| *x >= 0),
+        };
+        for (key, value) in [
+            ("quota_storage", quota.storage),
+            ("quota_items", quota.items),
+            ("quota_file_size", quota.file_size),
+            ("quota_expiry", quota.expiry),
+        ] {
+            let value = value.map(|x| x.to_string()).unwrap_or_default();
+            let _ = da.set_sys_config(key, &value).await;
+        }
+        new_config.quota = quota;
+    }
+    if let Some(v) = update.max_revisions {
+        new_config.max_revisions = v;
+        let _ = da.set_sys_config("max_revisions", &v.to_string()).await;
+    }
     if let Some(ref v) = update.refresh_time {
         let old_refresh = new_config.refresh_time.clone();
         new_config.refresh_time = v.clone();
         let _ = da.set_sys_config("refresh_time", v).await;
 
         if old_refresh != *v {
-            if croner::Cron::from_str(v).is_ok() {
-                let scheduler = state.cron_scheduler.clone();
-                if let Some(old_job) = **state.cron_job_id.load() {
-                    let _ = scheduler.remove(&old_job).await;
-                }
+            let scheduler = state.cron_scheduler.clone();
+            if let Some(old_job) = **state.cron_job_id.load() {
+                let _ = scheduler.remove(&old_job).await;
+            }
 
-                let da_clone = state.database_accessor.clone();
-                let fa_clone = state.file_accessor.clone();
-                if let Ok(job) = tokio_cron_scheduler::Job::new_async(v.as_str(), move |_, _| {
-                    tracing::info!("Triggered scheduled task: refreshing database...");
-                    let da = da_clone.clone();
-                    let fa = fa_clone.clone();
-                    Box::pin(async move {
-                        let _ = da.refresh_db(fa).await;
-                    })
-                }) {
-                    if let Ok(job_id) = scheduler.add(job).await {
-                        state.cron_job_id.store(std::sync::Arc::new(Some(job_id)));
-                    }
+            let da_clone = state.database_accessor.clone();
+            let fa_clone = state.file_accessor.clone();
+            if let Ok(job) = tokio_cron_scheduler::Job::new_async(v.as_str(), move |_, _| {
+                tracing::info!("Triggered scheduled task: refreshing database...");
+                let da = da_clone.clone();
+                let fa = fa_clone.clone();
+                Box::pin(async move {
+                    let _ = da.refresh_db(fa).await;
+                })
+            }) {
+                if let Ok(job_id) = scheduler.add(job).await {
+                    state.cron_job_id.store(std::sync::Arc::new(Some(job_id)));
                 }
-            } else {
-                fail!(400, "Invalid cron expression");
             }
         }
     }
@@ -2,10 +2,23 @@ use axum::Router;
 use axum::extract::DefaultBodyLimit;
 use axum::routing::{delete, get, post};
 
+pub mod auth;
+mod csrf;
+mod group;
+mod impersonation;
 mod item;
+mod ldap;
 mod misc;
+mod oidc;
+mod password;
+mod quota;
+mod registration;
 mod result;
+mod session;
 mod setup;
+mod tag;
+mod token;
+mod totp;
 mod types;
 mod user;
 
@@ -22,32 +35,114 @@ pub fn make_router(s: AppState) -> Router<AppState> {
         )
         .route("/finish", get(setup::finish_setup))
         .layer(axum::middleware::from_fn_with_state(
-            s,
+            s.clone(),
             setup::setup_interceptor,
         ));
 
     #[allow(unused_mut)]
     let mut r = Router::new()
         .route("/login", post(user::login))
+        .route("/login/totp", post(user::login_totp))
         .route("/logout", post(user::logout))
+        .route("/register", post(registration::register))
+        .route(
+            "/invites",
+            get(registration::get_invites).post(registration::create_invite),
+        )
+        .route("/invites/{id}", delete(registration::remove_invite))
+        .route("/invite/{code}", get(registration::check_invite))
+        .route("/user/{id}/approve", post(registration::approve_user))
+        .route("/password/forgot", post(password::forgot_password))
+        .route("/password/reset", post(password::reset_password))
+        .route("/auth/oidc/start", get(oidc::start))
+        .route("/auth/oidc/callback", get(oidc::callback))
         .route("/user-info", get(user::user_info))
+        .route("/account", delete(user::delete_account))
         .route("/code-content/{path}", get(item::get_code))
         .route("/item/{path}", post(item::create_item))
         .route("/item/{path}", delete(item::remove_item))
         .route("/item/{path}", get(item::get_item))
+        .route(
+            "/item/{path}",
+            axum::routing::put(item::update_item).patch(item::update_item),
+        )
+        .route("/item/{path}/logs", get(item::get_item_logs))
+        .route("/item/{path}/revisions", get(item::get_revisions))
+        .route("/item/{path}/revisions/{revision}", get(item::get_revision))
+        .route(
+            "/item/{path}/revisions/{revision}/restore",
+            post(item::restore_revision),
+        )
+        .route("/item/{path}/diff", get(item::diff_revisions))
         .route(
             "/file/{path}",
-            // 对于文件上传允许最大 1GB 的请求体尺寸
-            post(item::upload_file).layer(DefaultBodyLimit::max(1024 * 1024 * 1024 * 1024)),
+            // 文件上传的请求体尺寸由 upload_body_limit 按单个文件大小配额限制
+            post(item::upload_file)
+                .route_layer(axum::middleware::from_fn_with_state(
+                    s.clone(),
+                    item::upload_body_limit,
+                ))
+                .layer(DefaultBodyLimit::disable()),
         )
         .route("/items", get(item::get_user_items))
         .route("/items/all", get(item::get_all_items))
         .route("/items/img", get(item::get_user_img_items))
+        .route("/items/search", get(item::search_items))
+        .route("/items/claim", post(item::claim_items))
         .route("/users", get(user::get_users))
         .route("/user/{id}", delete(user::remove_user))
         .route("/user/{id}", get(user::get_user))
         .route("/user", post(user::create_user))
         .route("/user/{id}", axum::routing::put(user::update_user))
+        .route("/user/{id}/owner", axum::routing::put(user::set_owner))
+        .route("/user/{id}/transfer-owner", post(user::transfer_owner))
+        .route("/user/{id}/quota", axum::routing::put(user::set_user_quota))
+        .route("/user/{id}/impersonate", post(impersonation::start))
+        .route("/impersonation/end", post(impersonation::end))
+        .route("/audit-logs", get(impersonation::get_audit_logs))
+        .route("/totp/enroll", post(totp::enroll))
+        .route("/totp/confirm", post(totp::confirm))
+        .route("/totp/disable", post(totp::disable))
+        .route(
+            "/totp/recovery-codes",
+            post(totp::regenerate_recovery_codes),
+        )
+        .route("/user/{id}/totp", delete(totp::admin_reset))
+        .route(
+            "/sessions",
+            get(session::get_sessions).delete(session::remove_other_sessions),
+        )
+        .route(
+            "/sessions/{id}",
+            get(session::get_session).delete(session::remove_session),
+        )
+        .route(
+            "/user/{id}/sessions",
+            get(session::admin_get_sessions).delete(session::admin_remove_sessions),
+        )
+        .route(
+            "/user/{id}/sessions/{session_id}",
+            delete(session::admin_remove_session),
+        )
+        .route("/tags", get(tag::get_tags))
+        .route(
+            "/tags/{id}",
+            axum::routing::put(tag::rename_tag).delete(tag::remove_tag),
+        )
+        .route("/tags/{id}/merge", post(tag::merge_tag))
+        .route("/tokens", get(token::get_tokens).post(token::create_token))
+        .route("/tokens/{id}", delete(token::remove_token))
+        .route("/groups", get(group::get_groups).post(group::create_group))
+        .route(
+            "/groups/{id}",
+            get(group::get_group)
+                .put(group::update_group)
+                .delete(group::remove_group),
+        )
+        .route(
+            "/groups/{id}/members/{user_id}",
+            axum::routing::put(group::set_group_member).delete(group::remove_group_member),
+        )
         .route("/about", get(misc::get_information))
         .route("/config", get(misc::get_config))
         .route(
@@ -61,7 +156,14 @@ pub fn make_router(s: AppState) -> Router<AppState> {
         r = r.route("/db-refresh", get(trigger_db_refresh));
     }
 
-    r
+    r.layer(axum::middleware::from_fn_with_state(
+        s.clone(),
+        impersonation::audit_impersonation,
+    ))
+    .layer(axum::middleware::from_fn_with_state(
+        s,
+        csrf::csrf_protection,
+    ))
 }
 
 #[cfg(debug_assertions)]
@@ -0,0 +1,635 @@
+use crate::fail;
+use crate::service::api::result::{ApiError, ApiQuery, ApiResult};
+use crate::service::api::user::{begin_login, check_user_status, resolve_external_user};
+use crate::types::{AppState, ClientInfo, OidcConfig, User, UserPermission};
+use anyhow::anyhow;
+use axum::extract::State;
+use axum::response::{IntoResponse, Redirect};
+use axum_extra::extract::PrivateCookieJar;
+use axum_extra::extract::cookie::{Cookie, SameSite};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use cookie::time::Duration;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use tracing::instrument;
+
+// 登录流程进行中时保存 state / nonce / PKCE verifier 的 cookie
+const FLOW_COOKIE: &str = "oidc_flow";
+const FLOW_COOKIE_PATH: &str = "/api/auth/oidc";
+const AUTH_SOURCE: &str = "oidc";
+// state、nonce 与 PKCE verifier 都会出现在 URL 中，只使用不需要转义的字符
+const URL_SAFE_CHARSET: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+
+#[derive(Deserialize)]
+struct Discovery {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    #[serde(default)]
+    userinfo_endpoint: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Flow {
+    state: String,
+    nonce: String,
+    verifier: String,
+    remember: bool,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+    #[serde(default)]
+    access_token: Option<String>,
+}
+
+async fn discover(config: &OidcConfig) -> anyhow::Result<Discovery> {
+    let url = format!(
+        "{}/.well-known/openid-configuration",
+        config.issuer.trim_end_matches('/')
+    );
+    let discovery = reqwest::get(url)
+        .await?
+        .error_for_status()?
+        .json::<Discovery>()
+        .await?;
+    Ok(discovery)
+}
+
+fn redirect_uri(state: &AppState, config: &OidcConfig) -> String {
+    config.redirect_uri.clone().unwrap_or_else(|| {
+        format!(
+            "{}/api/auth/oidc/callback",
+            state.runtime_config.load().domain.trim_end_matches('/')
+        )
+    })
+}
+
+fn pkce_challenge(verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(verifier.as_bytes()))
+}
+
+// 取出 ID Token 中的声明
+// ID Token 是通过 TLS 直接从令牌端点取得的，所以这里不再校验签名，只检查其中的声明
+fn decode_id_token(id_token: &str) -> anyhow::Result<Value> {
+    let payload = id_token
+        .split('.')
+        .nth(1)
+        .ok_or_else(|| anyhow!("Malformed ID token"))?;
+    let payload = URL_SAFE_NO_PAD.decode(payload.trim_end_matches('='))?;
+    Ok(serde_json::from_slice(&payload)?)
+}
+
+fn audience_contains(claims: &Value, client_id: &str) -> bool {
+    match &claims["aud"] {
+        Value::String(aud) => aud == client_id,
+        Value::Array(aud) => aud.iter().any(|a| a.as_str() == Some(client_id)),
+        _ => false,
+    }
+}
+
+// 根据配置把身份提供方返回的用户组映射为权限
+fn descriptor_from_claims(config: &OidcConfig, claims: &Value) -> i64 {
+    let mut descriptor = UserPermission::to_descriptor(&config.default_permissions);
+    let groups: Vec<&str> = match &claims[&config.groups_claim] {
+        Value::Array(groups) => groups.iter().filter_map(|g| g.as_str()).collect(),
+        Value::String(group) => vec![group.as_str()],
+        _ => Vec::new(),
+    };
+    for group in groups {
+        if let Some(permissions) = config.group_permissions.get(group) {
+            descriptor |= UserPermission::to_descriptor(permissions);
+        }
+    }
+    descriptor
+}
+
+// 根据 ID Token 与 UserInfo 中的声明找到或创建对应的用户
+async fn resolve_user(
+    state: &AppState,
+    config: &OidcConfig,
+    claims: &Value,
+) -> Result<User, ApiError> {
+    let Some(subject) = claims["sub"].as_str() else {
+        fail!(400, "The identity provider did not return a subject");
+    };
+    let Some(email) = claims["email"].as_str() else {
+        fail!(400, "The identity provider did not return an email address");
+    };
+    let email_verified = claims["email_verified"].as_bool();
+    if email_verified == Some(false) {
+        fail!(
+            403,
+            "The email address has not been verified by the identity provider"
+        );
+    }
+    let name = claims["name"]
+        .as_str()
+        .or_else(|| claims["preferred_username"].as_str())
+        .unwrap_or(email);
+    let descriptor = descriptor_from_claims(config, claims);
+    // 只有身份提供方明确验证过邮箱时才关联同邮箱的已有用户
+    resolve_external_user(
+        &state.database_accessor,
+        AUTH_SOURCE,
+        subject,
+        name,
+        email,
+        descriptor,
+        email_verified == Some(true),
+    )
+    .await
+}
+
+#[derive(Deserialize, Debug)]
+pub struct StartQuery {
+    #[serde(default)]
+    remember: bool,
+}
+
+// 开始 OIDC 登录，重定向到身份提供方的授权页面
+#[instrument(skip(state, jar))]
+pub async fn start(
+    State(state): State<AppState>,
+    jar: PrivateCookieJar,
+    ApiQuery(query): ApiQuery<StartQuery>,
+) -> ApiResult {
+    let Some(config) = state.oidc.clone() else {
+        fail!(404, "OIDC login is not enabled");
+    };
+    let discovery = discover(&config).await?;
+
+    let flow = Flow {
+        state: crate::util::random_string(32, Some(URL_SAFE_CHARSET)),
+        nonce: crate::util::random_string(32, Some(URL_SAFE_CHARSET)),
+        verifier: crate::util::random_string(64, Some(URL_SAFE_CHARSET)),
+        remember: query.remember,
+    };
+    let mut url = reqwest::Url::parse(&discovery.authorization_endpoint)
+        .map_err(|e| anyhow!("Invalid authorization endpoint: {}", e))?;
+    url.query_pairs_mut()
+        .append_pair("response_type", "code")
+        .append_pair("client_id", &config.client_id)
+        .append_pair("redirect_uri", &redirect_uri(&state, &config))
+        .append_pair("scope", &config.scopes.join(" "))
+        .append_pair("state", &flow.state)
+        .append_pair("nonce", &flow.nonce)
+        .append_pair("code_challenge", &pkce_challenge(&flow.verifier))
+        .append_pair("code_challenge_method", "S256");
+
+    let jar = jar.add(
+        Cookie::build((
+            FLOW_COOKIE,
+            serde_json::to_string(&flow).map_err(anyhow::Error::from)?,
+        ))
+        .max_age(Duration::minutes(10))
+        .http_only(true)
+        // 身份提供方重定向回来属于跨站的顶层导航，必须是 Lax 才会带上
+        .same_site(SameSite::Lax)
+        .path(FLOW_COOKIE_PATH)
+        .build(),
+    );
+    Ok((jar, Redirect::to(url.as_str())).into_response())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+// 身份提供方回调：用授权码换取令牌，登录或创建对应的用户
+#[instrument(skip(state, jar))]
+pub async fn callback(
+    State(state): State<AppState>,
+    jar: PrivateCookieJar,
+    client: ClientInfo,
+    ApiQuery(query): ApiQuery<CallbackQuery>,
+) -> ApiResult {
+    let Some(config) = state.oidc.clone() else {
+        fail!(404, "OIDC login is not enabled");
+    };
+    if let Some(error) = query.error {
+        fail!(401, "The identity provider returned an error: {}", error);
+    }
+    let Some(flow) = jar
+        .get(FLOW_COOKIE)
+        .and_then(|c| serde_json::from_str::<Flow>(c.value()).ok())
+    else {
+        fail!(400, "No OIDC login in progress");
+    };
+    let jar = jar.remove(Cookie::build(FLOW_COOKIE).path(FLOW_COOKIE_PATH));
+    let (Some(code), Some(returned_state)) = (query.code, query.state) else {
+        fail!(400, "Missing code or state");
+    };
+    if returned_state != flow.state {
+        fail!(400, "OIDC state mismatch");
+    }
+
+    let discovery = discover(&config).await?;
+    let redirect_uri = redirect_uri(&state, &config);
+    let mut form = vec![
+        ("grant_type", "authorization_code"),
+        ("code", code.as_str()),
+        ("redirect_uri", redirect_uri.as_str()),
+        ("client_id", config.client_id.as_str()),
+        ("code_verifier", flow.verifier.as_str()),
+    ];
+    if let Some(secret) = config.client_secret.as_deref() {
+        form.push(("client_secret", secret));
+    }
+    let http = reqwest::Client::new();
+    let res = http
+        .post(&discovery.token_endpoint)
+        .form(&form)
+        .send()
+        .await
+        .map_err(anyhow::Error::from)?;
+    if !res.status().is_success() {
+        fail!(401, "The identity provider rejected the authorization code");
+    }
+    let tokens = res
+        .json::<TokenResponse>()
+        .await
+        .map_err(anyhow::Error::from)?;
+
+    let mut claims = decode_id_token(&tokens.id_token)?;
+    if claims["iss"].as_str() != Some(discovery.issuer.as_str()) {
+        fail!(401, "ID token issuer mismatch");
+    }
+    if !audience_contains(&claims, &config.client_id) {
+        fail!(401, "ID token audience mismatch");
+    }
+    if claims["nonce"].as_str() != Some(flow.nonce.as_str()) {
+        fail!(401, "ID token nonce mismatch");
+    }
+    if claims["exp"].as_i64().unwrap_or(0) < chrono::Utc::now().timestamp() {
+        fail!(401, "ID token has expired");
+    }
+
+    // 用户组等声明可能只在 UserInfo 端点中返回
+    if let (Some(endpoint), Some(access_token)) =
+        (discovery.userinfo_endpoint.as_deref(), tokens.access_token)
+    {
+        let userinfo = http
+            .get(endpoint)
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status());
+        if let Ok(userinfo) = userinfo
+            && let Ok(Value::Object(extra)) = userinfo.json::<Value>().await
+            && extra.get("sub") == claims.get("sub")
+            && let Value::Object(claims) = &mut claims
+        {
+            for (key, value) in extra {
+                claims.entry(key).or_insert(value);
+            }
+        }
+    }
+
+    let user = resolve_user(&state, &config, &claims).await?;
+    check_user_status(&user)?;
+    let (jar, totp_required) = begin_login(&state, jar, &user, flow.remember, &client).await?;
+    let target = if totp_required {
+        "/dashboard/?totp_required=true"
+    } else {
+        "/dashboard/"
+    };
+    Ok((jar, Redirect::to(target)).into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::header::{AUTHORIZATION, COOKIE, LOCATION, SET_COOKIE};
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::response::Response;
+    use axum::routing::{get, post};
+    use axum::{Form, Json, Router};
+    use axum_extra::extract::cookie::Key;
+    use serde_json::json;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::net::TcpListener;
+
+    fn config() -> OidcConfig {
+        serde_json::from_value(json!({
+            "issuer": "https://id.example.com",
+            "client_id": "spectra",
+            "default_permissions": ["Link"],
+            "group_permissions": {
+                "admins": ["Manage"],
+                "writers": ["Code", "File"],
+            },
+        }))
+        .unwrap()
+    }
+
+    fn id_token(claims: &Value) -> String {
+        let header = URL_SAFE_NO_PAD.encode(br#"{"alg":"RS256"}"#);
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims).unwrap());
+        format!("{header}.{payload}.signature")
+    }
+
+    #[test]
+    fn decodes_id_token_payload() {
+        let claims = json!({"sub": "u1", "email": "a@example.com", "aud": "spectra"});
+        assert_eq!(decode_id_token(&id_token(&claims)).unwrap(), claims);
+
+        // 部分身份提供方会保留 base64 的填充
+        let payload = base64::engine::general_purpose::URL_SAFE.encode(br#"{"sub":"u"}"#);
+        assert!(payload.ends_with('='));
+        let padded = format!("header.{payload}.signature");
+        assert_eq!(decode_id_token(&padded).unwrap()["sub"], "u");
+    }
+
+    #[test]
+    fn rejects_malformed_id_token() {
+        assert!(decode_id_token("not-a-token").is_err());
+        assert!(decode_id_token("a.!!!.c").is_err());
+        assert!(decode_id_token(&format!("a.{}.c", URL_SAFE_NO_PAD.encode("{"))).is_err());
+    }
+
+    #[test]
+    fn checks_audience() {
+        assert!(audience_contains(&json!({"aud": "spectra"}), "spectra"));
+        assert!(audience_contains(
+            &json!({"aud": ["other", "spectra"]}),
+            "spectra"
+        ));
+        assert!(!audience_contains(&json!({"aud": "other"}), "spectra"));
+        assert!(!audience_contains(&json!({"aud": ["other"]}), "spectra"));
+        assert!(!audience_contains(&json!({}), "spectra"));
+    }
+
+    #[test]
+    fn maps_group_claims_to_permissions() {
+        let config = config();
+        let link = UserPermission::to_descriptor(&[UserPermission::Link]);
+        assert_eq!(descriptor_from_claims(&config, &json!({})), link);
+        assert_eq!(
+            descriptor_from_claims(&config, &json!({"groups": ["writers", "unknown"]})),
+            UserPermission::to_descriptor(&[
+                UserPermission::Link,
+                UserPermission::Code,
+                UserPermission::File,
+            ])
+        );
+        // 只有一个组时部分身份提供方返回字符串
+        assert_eq!(
+            descriptor_from_claims(&config, &json!({"groups": "admins"})),
+            UserPermission::to_descriptor(&[UserPermission::Link, UserPermission::Manage])
+        );
+        // 组名区分大小写
+        assert_eq!(
+            descriptor_from_claims(&config, &json!({"groups": ["Admins"]})),
+            link
+        );
+    }
+
+    // 测试用的身份提供方，授权请求中的 PKCE challenge 与 nonce 由测试从重定向地址中取出后设置
+    #[derive(Default)]
+    struct MockIdp {
+        issuer: String,
+        challenge: String,
+        nonce: String,
+        // 令牌端点收到的 PKCE verifier
+        verifier: Option<String>,
+    }
+
+    type Idp = Arc<Mutex<MockIdp>>;
+
+    async fn idp_discovery(State(idp): State<Idp>) -> Json<Value> {
+        let issuer = idp.lock().unwrap().issuer.clone();
+        Json(json!({
+            "issuer": issuer,
+            "authorization_endpoint": format!("{issuer}/authorize"),
+            "token_endpoint": format!("{issuer}/token"),
+            "userinfo_endpoint": format!("{issuer}/userinfo"),
+        }))
+    }
+
+    async fn idp_token(
+        State(idp): State<Idp>,
+        Form(form): Form<HashMap<String, String>>,
+    ) -> Response {
+        let mut idp = idp.lock().unwrap();
+        let verifier = form.get("code_verifier").cloned().unwrap_or_default();
+        if form.get("code").map(String::as_str) != Some("code-1")
+            || form.get("client_id").map(String::as_str) != Some("spectra")
+            || pkce_challenge(&verifier) != idp.challenge
+        {
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+        idp.verifier = Some(verifier);
+        let claims = json!({
+            "iss": idp.issuer,
+            "aud": "spectra",
+            "sub": "subject-1",
+            "nonce": idp.nonce,
+            "exp": chrono::Utc::now().timestamp() + 300,
+        });
+        Json(json!({"id_token": id_token(&claims), "access_token": "access-1"})).into_response()
+    }
+
+    // 邮箱与用户组只在 UserInfo 中返回
+    async fn idp_userinfo(headers: HeaderMap) -> Response {
+        if headers.get(AUTHORIZATION).and_then(|x| x.to_str().ok()) != Some("Bearer access-1") {
+            return StatusCode::UNAUTHORIZED.into_response();
+        }
+        Json(json!({
+            "sub": "subject-1",
+            "email": "alice@example.com",
+            "email_verified": true,
+            "name": "Alice",
+            "groups": ["writers"],
+        }))
+        .into_response()
+    }
+
+    async fn mock_idp() -> Idp {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let idp = Arc::new(Mutex::new(MockIdp {
+            issuer: format!("http://{}", listener.local_addr().unwrap()),
+            ..Default::default()
+        }));
+        let app = Router::new()
+            .route("/.well-known/openid-configuration", get(idp_discovery))
+            .route("/token", post(idp_token))
+            .route("/userinfo", get(idp_userinfo))
+            .with_state(idp.clone());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        idp
+    }
+
+    async fn oidc_state(idp: &Idp) -> AppState {
+        let mut state = AppState::temporary().await;
+        let mut config = config();
+        config.issuer = idp.lock().unwrap().issuer.clone();
+        state.oidc = Some(Arc::new(config));
+        state
+    }
+
+    fn key(state: &AppState) -> Key {
+        (**state.cookie_key.load()).clone()
+    }
+
+    fn ok(result: ApiResult) -> Response {
+        match result {
+            Ok(res) => res,
+            Err(ApiError::Anyhow(e)) => panic!("unexpected error: {e}"),
+            Err(ApiError::Other((status, message))) => panic!("unexpected {status}: {message}"),
+        }
+    }
+
+    fn status(result: ApiResult) -> StatusCode {
+        match result {
+            Ok(res) => panic!("unexpected success: {}", res.status()),
+            Err(ApiError::Anyhow(e)) => panic!("unexpected error: {e}"),
+            Err(ApiError::Other((status, _))) => status,
+        }
+    }
+
+    // 浏览器在下一次请求中带上响应设置的 cookie
+    fn cookies(res: &Response, key: Key) -> PrivateCookieJar {
+        let cookie = res
+            .headers()
+            .get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|x| x.to_str().ok()?.split(';').next())
+            .collect::<Vec<_>>()
+            .join("; ");
+        let mut headers = HeaderMap::new();
+        headers.insert(COOKIE, cookie.parse().unwrap());
+        PrivateCookieJar::from_headers(&headers, key)
+    }
+
+    // 发起登录并模拟身份提供方的授权页面，返回授权地址的参数和浏览器的 cookie
+    async fn start_flow(
+        state: &AppState,
+        idp: &Idp,
+    ) -> (HashMap<String, String>, PrivateCookieJar) {
+        let res = ok(start(
+            State(state.clone()),
+            PrivateCookieJar::new(key(state)),
+            ApiQuery(StartQuery { remember: false }),
+        )
+        .await);
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+        let location = reqwest::Url::parse(res.headers()[LOCATION].to_str().unwrap()).unwrap();
+        let params: HashMap<String, String> = location.query_pairs().into_owned().collect();
+        let mut idp = idp.lock().unwrap();
+        assert_eq!(
+            location.as_str().split('?').next(),
+            Some(format!("{}/authorize", idp.issuer).as_str())
+        );
+        idp.challenge = params["code_challenge"].clone();
+        idp.nonce = params["nonce"].clone();
+        (params, cookies(&res, key(state)))
+    }
+
+    async fn finish_flow(
+        state: &AppState,
+        jar: PrivateCookieJar,
+        returned_state: &str,
+    ) -> ApiResult {
+        callback(
+            State(state.clone()),
+            jar,
+            ClientInfo::default(),
+            ApiQuery(CallbackQuery {
+                code: Some("code-1".to_string()),
+                state: Some(returned_state.to_string()),
+                error: None,
+            }),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn logs_in_through_identity_provider() {
+        let idp = mock_idp().await;
+        let state = oidc_state(&idp).await;
+        let (params, jar) = start_flow(&state, &idp).await;
+        assert_eq!(params["response_type"], "code");
+        assert_eq!(params["client_id"], "spectra");
+        assert_eq!(params["code_challenge_method"], "S256");
+        assert_eq!(
+            params["redirect_uri"],
+            "http://127.0.0.1:3000/api/auth/oidc/callback"
+        );
+
+        let res = ok(finish_flow(&state, jar, &params["state"]).await);
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+        assert_eq!(res.headers()[LOCATION], "/dashboard/");
+        // 令牌端点收到的 verifier 与授权请求中的 challenge 对应
+        let verifier = idp.lock().unwrap().verifier.clone().unwrap();
+        assert_eq!(pkce_challenge(&verifier), params["code_challenge"]);
+
+        // 邮箱、名称和用户组来自 UserInfo
+        let da = &state.database_accessor;
+        let user = da
+            .get_user_by_email("alice@example.com")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.name, "Alice");
+        assert_eq!(user.auth_source, AUTH_SOURCE);
+        assert_eq!(user.external_id.as_deref(), Some("subject-1"));
+        assert_eq!(
+            user.descriptor,
+            UserPermission::to_descriptor(&[
+                UserPermission::Link,
+                UserPermission::Code,
+                UserPermission::File,
+            ])
+        );
+
+        let jar = cookies(&res, key(&state));
+        let token = jar.get("token").unwrap();
+        let session = da.get_active_session(token.value()).await.unwrap().unwrap();
+        assert_eq!(session.user_id, user.id);
+        assert!(jar.get(FLOW_COOKIE).is_none());
+    }
+
+    #[tokio::test]
+    async fn rejects_state_mismatch() {
+        let idp = mock_idp().await;
+        let state = oidc_state(&idp).await;
+        let (_, jar) = start_flow(&state, &idp).await;
+        assert_eq!(
+            status(finish_flow(&state, jar, "forged").await),
+            StatusCode::BAD_REQUEST
+        );
+        // 没有进行中的登录流程
+        let jar = PrivateCookieJar::new(key(&state));
+        assert_eq!(
+            status(finish_flow(&state, jar, "forged").await),
+            StatusCode::BAD_REQUEST
+        );
+        assert!(idp.lock().unwrap().verifier.is_none());
+    }
+
+    #[tokio::test]
+    async fn rejects_nonce_mismatch() {
+        let idp = mock_idp().await;
+        let state = oidc_state(&idp).await;
+        let (params, jar) = start_flow(&state, &idp).await;
+        idp.lock().unwrap().nonce = "replayed".to_string();
+        assert_eq!(
+            status(finish_flow(&state, jar, &params["state"]).await),
+            StatusCode::UNAUTHORIZED
+        );
+        let user = state
+            .database_accessor
+            .get_user_by_email("alice@example.com")
+            .await
+            .unwrap();
+        assert!(user.is_none());
+    }
+}
@@ -0,0 +1,214 @@
+use crate::limiter::LimitKey;
+use crate::service::api::result::{ApiJson, ApiResult};
+use crate::types::{AppState, ClientInfo, PasswordReset};
+use crate::{fail, success};
+use axum::extract::State;
+use serde::Deserialize;
+use tracing::{error, info, instrument};
+
+#[derive(Deserialize)]
+pub struct ForgotPasswordPayload {
+    email: String,
+}
+
+#[derive(Deserialize)]
+pub struct ResetPasswordPayload {
+    token: String,
+    password: String,
+}
+
+// 申请重置密码，向用户的邮箱发送重置链接
+// 无论邮箱是否存在都返回成功，避免泄露哪些邮箱注册过
+#[instrument(skip(state, payload))]
+pub async fn forgot_password(
+    State(state): State<AppState>,
+    client: ClientInfo,
+    ApiJson(payload): ApiJson<ForgotPasswordPayload>,
+) -> ApiResult {
+    let smtp = state.runtime_config.load().smtp.clone();
+    if !smtp.enabled {
+        fail!(503, "Password reset by email is not available");
+    }
+    // 每次请求都计入限制，防止被用来大量发送邮件
+    let limit_keys = LimitKey::PasswordReset(&payload.email).with_ip(client.ip_address.as_deref());
+    if let Err(wait) = state.login_limiter.check(&limit_keys) {
+        fail!(429, "Too many requests, try again in {} seconds", wait);
+    }
+    state.login_limiter.record_failure(&limit_keys);
+
+    let user = state
+        .database_accessor
+        .get_user_by_email(&payload.email)
+        .await?;
+    // 外部身份源的用户没有本地密码，不能在这里重置
+    let Some(user) = user.filter(|x| x.auth_source == "local") else {
+        success!(());
+    };
+
+    let token = crate::util::random_string(
+        48,
+        Some("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ1234567890"),
+    );
+    state
+        .database_accessor
+        .create_password_reset(&token, &user.id)
+        .await?;
+    let link = format!(
+        "{}/dashboard/?reset_token={}",
+        state.runtime_config.load().domain.trim_end_matches('/'),
+        token
+    );
+    let body = format!(
+        "Hello {},\n\n\
+         Someone requested a password reset for your Spectra account. \
+         Open the link below within {} minutes to set a new password:\n\n\
+         {}\n\n\
+         If you did not request this, you can ignore this email.\n",
+        user.name,
+        PasswordReset::lifetime().num_minutes(),
+        link
+    );
+    // 在后台发送，响应时间不会因为邮箱是否存在而不同
+    tokio::spawn(async move {
+        match crate::mail::send_mail(&smtp, &user.email, "Reset your Spectra password", body).await
+        {
+            Ok(()) => info!("Sent password reset email to user {}", user.id),
+            Err(e) => error!("Failed to send password reset email: {}", e),
+        }
+    });
+    success!(())
+}
+
+// 使用邮件中的令牌设置新密码，并吊销该用户的所有会话
+#[instrument(skip(state, payload))]
+pub async fn reset_password(
+    State(state): State<AppState>,
+    ApiJson(payload): ApiJson<ResetPasswordPayload>,
+) -> ApiResult {
+    if payload.password.is_empty() {
+        fail!(400, "Password cannot be empty");
+    }
+    let da = &state.database_accessor;
+    let Some(reset) = da.use_password_reset(&payload.token).await? else {
+        fail!(400, "Invalid or expired reset token");
+    };
+    let Some(user) = da
+        .get_user_by_id(&reset.user_id)
+        .await?
+        .filter(|x| x.auth_source == "local")
+    else {
+        fail!(400, "Invalid or expired reset token");
+    };
+
+    let password_hash = crate::util::hash_password(&payload.password).await?;
+    da.change_user_password(&user.id, &password_hash).await?;
+    let revoked = da.remove_user_sessions(&user.id, None).await?;
+    // 之前因为猜错密码而被限制的账号，重置后可以立即登录
+    state
+        .login_limiter
+        .record_success(&[LimitKey::Account(&user.email)]);
+    info!(
+        "User {} reset their password, {} sessions revoked",
+        user.id, revoked
+    );
+    success!(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::service::api::result::ApiError;
+    use axum::http::StatusCode;
+
+    async fn reset(state: &AppState, token: &str, password: &str) -> ApiResult {
+        reset_password(
+            State(state.clone()),
+            ApiJson(ResetPasswordPayload {
+                token: token.to_string(),
+                password: password.to_string(),
+            }),
+        )
+        .await
+    }
+
+    fn status(result: ApiResult) -> StatusCode {
+        match result {
+            Ok(res) => res.status(),
+            Err(ApiError::Anyhow(e)) => panic!("unexpected error: {e}"),
+            Err(ApiError::Other((status, _))) => status,
+        }
+    }
+
+    #[tokio::test]
+    async fn resets_password_and_revokes_sessions() {
+        let state = AppState::temporary().await;
+        let da = &state.database_accessor;
+        let client = ClientInfo::default();
+        let user = da
+            .create_user(
+                "user-1",
+                "Alice",
+                "alice@example.com",
+                "old-password",
+                0,
+                None,
+            )
+            .await
+            .unwrap();
+        da.create_session("session-1", &user.id, false, false, &client)
+            .await
+            .unwrap();
+        da.create_session("session-2", &user.id, false, true, &client)
+            .await
+            .unwrap();
+        da.create_password_reset("token-1", &user.id).await.unwrap();
+
+        assert_eq!(
+            status(reset(&state, "token-1", "new-password").await),
+            StatusCode::OK
+        );
+        let user = da.get_user_by_id(&user.id).await.unwrap().unwrap();
+        let (matched, _) = crate::util::verify_password("new-password", &user.password)
+            .await
+            .unwrap();
+        assert!(matched);
+        assert!(da.get_active_session("session-1").await.unwrap().is_none());
+        assert!(da.get_active_session("session-2").await.unwrap().is_none());
+        // 令牌只能使用一次
+        assert_eq!(
+            status(reset(&state, "token-1", "other-password").await),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_to_reset_external_accounts() {
+        let state = AppState::temporary().await;
+        let da = &state.database_accessor;
+        let user = da
+            .create_user(
+                "user-1",
+                "Alice",
+                "alice@example.com",
+                "old-password",
+                0,
+                None,
+            )
+            .await
+            .unwrap();
+        da.link_external_user(&user.id, "oidc", "subject-1", "Alice", 0)
+            .await
+            .unwrap();
+        da.create_password_reset("token-1", &user.id).await.unwrap();
+
+        assert_eq!(
+            status(reset(&state, "token-1", "new-password").await),
+            StatusCode::BAD_REQUEST
+        );
+        let user = da.get_user_by_id(&user.id).await.unwrap().unwrap();
+        let (matched, _) = crate::util::verify_password("old-password", &user.password)
+            .await
+            .unwrap();
+        assert!(matched);
+    }
+}
@@ -0,0 +1,78 @@
+use crate::service::api::result::ApiError;
+use crate::types::{AppState, QuotaConfig};
+use chrono::Local;
+use sqlx::types::chrono::NaiveDateTime;
+
+// 获取用户生效的配额，游客没有用户记录，使用默认配额
+pub async fn get_quota(state: &AppState, user_id: &str) -> Result<QuotaConfig, ApiError> {
+    let default = state.runtime_config.load().quota.clone();
+    Ok(
+        match state.database_accessor.get_user_by_id(user_id).await? {
+            Some(user) => default.for_user(&user),
+            None => default,
+        },
+    )
+}
+
+// 设置了最长有效期时，项目必须在期限内过期
+pub fn check_expiry(
+    quota: &QuotaConfig,
+    expires_at: Option<NaiveDateTime>,
+) -> Result<(), ApiError> {
+    let Some(limit) = quota.expiry else {
+        return Ok(());
+    };
+    let latest = Local::now().naive_local() + chrono::Duration::seconds(limit);
+    if expires_at.is_none_or(|x| x > latest) {
+        return Err(ApiError::new(
+            403,
+            format!("Items must expire within {} seconds", limit),
+        ));
+    }
+    Ok(())
+}
+
+// 检查再增加 added 个有效项目后是否超出配额
+pub async fn check_items(
+    state: &AppState,
+    user_id: &str,
+    quota: &QuotaConfig,
+    added: i64,
+) -> Result<(), ApiError> {
+    let Some(limit) = quota.items else {
+        return Ok(());
+    };
+    let (_, items) = state.database_accessor.get_user_usage(user_id).await?;
+    if items + added > limit {
+        return Err(ApiError::new(403, "Item quota exceeded".to_string()));
+    }
+    Ok(())
+}
+
+pub fn check_file_size(quota: &QuotaConfig, size: i64) -> Result<(), ApiError> {
+    if quota.file_size.is_some_and(|x| size > x) {
+        return Err(ApiError::new(
+            413,
+            "File exceeds the size limit".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+// 检查写入 size 字节后是否超出配额，replaced 为被替换的旧数据的大小
+pub async fn check_storage(
+    state: &AppState,
+    user_id: &str,
+    quota: &QuotaConfig,
+    size: i64,
+    replaced: i64,
+) -> Result<(), ApiError> {
+    let Some(limit) = quota.storage else {
+        return Ok(());
+    };
+    let (used, _) = state.database_accessor.get_user_usage(user_id).await?;
+    if used - replaced + size > limit {
+        return Err(ApiError::new(413, "Storage quota exceeded".to_string()));
+    }
+    Ok(())
+}
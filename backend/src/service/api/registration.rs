@@ -0,0 +1,205 @@
+use crate::service::api::auth::{ManageUsers, RequirePermission};
+use crate::service::api::result::{ApiError, ApiJson, ApiPath, ApiQuery, ApiResult};
+use crate::service::api::types::{
+    ApiInviteCreate, ApiInviteCreated, ApiInviteInfo, ApiList, ApiRegister, ApiUser,
+};
+use crate::service::api::user::begin_login;
+use crate::types::{AppState, ClientInfo, User, UserPermission};
+use crate::{fail, success};
+use axum::extract::State;
+use axum_extra::extract::PrivateCookieJar;
+use std::collections::HashMap;
+use tracing::{info, instrument};
+use uuid::Uuid;
+
+#[instrument(skip(state, manager))]
+pub async fn create_invite(
+    State(state): State<AppState>,
+    manager: RequirePermission<ManageUsers>,
+    ApiJson(body): ApiJson<ApiInviteCreate>,
+) -> ApiResult {
+    manager.check_grantable(&body.descriptor)?;
+    let expires_at = if let Some(x) = body.expires_at {
+        let expires_at = x
+            .parse::<chrono::DateTime<chrono::Utc>>()
+            .map_err(|e| ApiError::new(400, e.to_string()))?
+            .with_timezone(&chrono::Local)
+            .naive_local();
+        if expires_at <= chrono::Local::now().naive_local() {
+            fail!(400, "Expiration time must be in the future");
+        }
+        Some(expires_at)
+    } else {
+        None
+    };
+    if body.max_uses.is_some_and(|x| x < 1) {
+        fail!(400, "Maximum uses must be at least 1");
+    }
+
+    let code = crate::util::random_string(
+        32,
+        Some("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ1234567890"),
+    );
+    let invite = state
+        .database_accessor
+        .create_invite(
+            &code,
+            &manager.user.id,
+            UserPermission::to_descriptor(&body.descriptor),
+            expires_at,
+            body.max_uses,
+        )
+        .await?;
+    let link = format!(
+        "{}/dashboard/?invite={}",
+        state.runtime_config.load().domain.trim_end_matches('/'),
+        code
+    );
+    info!("User {} created invite {}", manager.user.id, invite.id);
+    success!(ApiInviteCreated {
+        code,
+        link,
+        info: ApiInviteInfo::from(invite),
+    })
+}
+
+#[instrument(skip(state, _manager))]
+pub async fn get_invites(
+    State(state): State<AppState>,
+    _manager: RequirePermission<ManageUsers>,
+) -> ApiResult {
+    let items = state
+        .database_accessor
+        .get_invites()
+        .await?
+        .into_iter()
+        .map(ApiInviteInfo::from)
+        .collect::<Vec<_>>();
+    success!(ApiList {
+        total: items.len() as i64,
+        items,
+    })
+}
+
+#[instrument(skip(state, manager))]
+pub async fn remove_invite(
+    ApiPath(id): ApiPath<String>,
+    State(state): State<AppState>,
+    manager: RequirePermission<ManageUsers>,
+) -> ApiResult {
+    if !state.database_accessor.remove_invite(&id).await? {
+        fail!(404, "Invite not found");
+    }
+    info!("User {} revoked invite {}", manager.user.id, id);
+    success!(())
+}
+
+// 检查邀请码是否可用，供注册页面展示
+#[instrument(skip(state, code))]
+pub async fn check_invite(
+    ApiPath(code): ApiPath<String>,
+    State(state): State<AppState>,
+) -> ApiResult {
+    let invite = state.database_accessor.get_invite_by_code(&code).await?;
+    let Some(invite) = invite.filter(|x| x.is_usable()) else {
+        fail!(404, "Invalid or expired invite");
+    };
+    success!(ApiInviteInfo::from(invite))
+}
+
+// 注册新用户：提供邀请码时使用邀请中的权限，否则按开放注册的配置处理
+#[instrument(skip(state, jar, body))]
+pub async fn register(
+    State(state): State<AppState>,
+    jar: PrivateCookieJar,
+    client: ClientInfo,
+    ApiQuery(query): ApiQuery<HashMap<String, String>>,
+    ApiJson(body): ApiJson<ApiRegister>,
+) -> ApiResult {
+    let name = body.name.trim();
+    let email = body.email.trim();
+    if name.is_empty() || email.is_empty() || !email.contains('@') {
+        fail!(400, "Invalid name or email");
+    }
+    if body.password.is_empty() {
+        fail!(400, "Password cannot be empty");
+    }
+    let da = &state.database_accessor;
+    if da.get_user_by_email(email).await?.is_some() {
+        fail!(409, "Email already registered");
+    }
+
+    let (descriptor, status) = if let Some(code) = body.invite.as_deref() {
+        let Some(invite) = da.use_invite(code).await? else {
+            fail!(400, "Invalid or expired invite");
+        };
+        info!("Invite {} used by {}", invite.id, email);
+        (invite.descriptor, "active")
+    } else {
+        let rt = state.runtime_config.load();
+        if !rt.registration.enabled {
+            fail!(403, "Registration is disabled");
+        }
+        if !rt.registration.allows_email(email) {
+            fail!(403, "Registration is not allowed for this email domain");
+        }
+        // 开放注册同样需要通过 Turnstile 验证（如果启用）
+        if rt.turnstile.enabled {
+            let Some(token) = query.get("turnstile-token") else {
+                fail!(401, "Turnstile verification required");
+            };
+            let resp = crate::util::check_turnstile(&rt.turnstile.secret_key, token).await?;
+            if !resp.0 {
+                fail!(422, "Turnstile error: {}", resp.1.join(", "));
+            }
+        }
+        let status = if rt.registration.approval {
+            "pending"
+        } else {
+            "active"
+        };
+        (rt.registration.descriptor, status)
+    };
+
+    let id = Uuid::new_v4().to_string();
+    let password_hash = crate::util::hash_password(&body.password).await?;
+    let user = da
+        .register_user(&id, name, email, &password_hash, descriptor, status)
+        .await?;
+    info!("User {} registered with status {}", user.id, user.status);
+
+    // 不需要审核时直接登录
+    if user.is_active() {
+        let (jar, _) = begin_login(&state, jar, &user, false, &client).await?;
+        let api_user = ApiUser::from_user(user, &state).await?;
+        success!(api_user, jar);
+    }
+    let api_user = ApiUser::from_user(user, &state).await?;
+    success!(api_user)
+}
+
+// 管理员审核通过等待中的用户
+#[instrument(skip(state, manager))]
+pub async fn approve_user(
+    ApiPath(id): ApiPath<String>,
+    State(state): State<AppState>,
+    manager: RequirePermission<ManageUsers>,
+) -> ApiResult {
+    let Some(user) = state.database_accessor.get_user_by_id(&id).await? else {
+        fail!(404, "User not found");
+    };
+    if user.is_active() {
+        fail!(409, "User is already active");
+    }
+    state
+        .database_accessor
+        .set_user_status(&id, "active")
+        .await?;
+    info!("User {} approved user {}", manager.user.id, id);
+    let user = User {
+        status: "active".to_string(),
+        ..user
+    };
+    let api_user = ApiUser::from_user(user, &state).await?;
+    success!(api_user)
+}
@@ -67,7 +67,14 @@ impl From<QueryRejection> for ApiError {
 impl From<MultipartError> for ApiError {
     fn from(err: MultipartError) -> Self {
         info!("Multipart error: {}", err);
-        Self::Other((StatusCode::BAD_REQUEST, err.to_string()))
+        // 请求体超出 upload_body_limit 的限制时为 413
+        match err.status() {
+            StatusCode::PAYLOAD_TOO_LARGE => Self::Other((
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "File exceeds the size limit".to_string(),
+            )),
+            _ => Self::Other((StatusCode::BAD_REQUEST, err.to_string())),
+        }
     }
 }
 
@@ -0,0 +1,169 @@
+use crate::service::api::auth::{CurrentUser, ManageUsers, RequirePermission};
+use crate::service::api::result::{ApiError, ApiPath, ApiResult};
+use crate::service::api::types::{ApiList, ApiSession};
+use crate::types::{AppState, Session};
+use crate::{fail, success};
+use axum::extract::State;
+use serde::Serialize;
+use tracing::{info, instrument};
+
+#[derive(Serialize)]
+struct RevokedSessions {
+    revoked: u64,
+}
+
+async fn list_sessions(state: &AppState, user_id: &str, current: Option<&Session>) -> ApiResult {
+    let current_id = current.map(|x| x.id.as_str());
+    let items = state
+        .database_accessor
+        .get_user_sessions(user_id)
+        .await?
+        .into_iter()
+        .map(|x| ApiSession::from_session(x, current_id))
+        .collect::<Vec<_>>();
+    success!(ApiList {
+        total: items.len() as i64,
+        items,
+    })
+}
+
+// 找到属于指定用户的有效会话
+// 不区分会话不存在和不属于该用户，避免泄露其他用户的会话 ID
+async fn find_user_session(
+    state: &AppState,
+    user_id: &str,
+    session_id: &str,
+) -> Result<Session, ApiError> {
+    let session = state
+        .database_accessor
+        .get_session_by_id(session_id)
+        .await?
+        .filter(|x| x.user_id == user_id && !x.temporary && !x.pending && !x.is_expired());
+    match session {
+        Some(session) => Ok(session),
+        None => fail!(404, "Session not found"),
+    }
+}
+
+// 管理员只能管理权限不超过自己的用户的会话
+async fn check_managed_user(
+    state: &AppState,
+    manager: &CurrentUser,
+    user_id: &str,
+) -> Result<(), ApiError> {
+    let Some(user) = state.database_accessor.get_user_by_id(user_id).await? else {
+        fail!(404, "User not found");
+    };
+    manager.check_manageable(state, &user).await
+}
+
+// 列出当前用户的登录会话
+#[instrument(skip(state, current))]
+pub async fn get_sessions(State(state): State<AppState>, current: CurrentUser) -> ApiResult {
+    list_sessions(&state, &current.user.id, current.session()).await
+}
+
+#[instrument(skip(state, current))]
+pub async fn get_session(
+    ApiPath(id): ApiPath<String>,
+    State(state): State<AppState>,
+    current: CurrentUser,
+) -> ApiResult {
+    let session = find_user_session(&state, &current.user.id, &id).await?;
+    success!(ApiSession::from_session(
+        session,
+        current.session().map(|x| x.id.as_str())
+    ))
+}
+
+// 吊销当前用户的一个会话
+#[instrument(skip(state, current))]
+pub async fn remove_session(
+    ApiPath(id): ApiPath<String>,
+    State(state): State<AppState>,
+    current: CurrentUser,
+) -> ApiResult {
+    current.deny_impersonation()?;
+    current.deny_api_token()?;
+    let session = find_user_session(&state, &current.user.id, &id).await?;
+    state
+        .database_accessor
+        .remove_session_by_id(&session.id)
+        .await?;
+    info!("User {} revoked session {}", current.user.id, session.id);
+    success!(ApiSession::from_session(
+        session,
+        current.session().map(|x| x.id.as_str())
+    ))
+}
+
+// 吊销当前用户除本次请求所用会话以外的所有会话
+#[instrument(skip(state, current))]
+pub async fn remove_other_sessions(
+    State(state): State<AppState>,
+    current: CurrentUser,
+) -> ApiResult {
+    current.deny_impersonation()?;
+    current.deny_api_token()?;
+    let revoked = state
+        .database_accessor
+        .remove_user_sessions(&current.user.id, current.session().map(|x| x.id.as_str()))
+        .await?;
+    info!(
+        "User {} revoked {} other sessions",
+        current.user.id, revoked
+    );
+    success!(RevokedSessions { revoked })
+}
+
+// 管理员查看指定用户的登录会话
+#[instrument(skip(state, manager))]
+pub async fn admin_get_sessions(
+    ApiPath(user_id): ApiPath<String>,
+    State(state): State<AppState>,
+    manager: RequirePermission<ManageUsers>,
+) -> ApiResult {
+    check_managed_user(&state, &manager, &user_id).await?;
+    list_sessions(&state, &user_id, manager.session()).await
+}
+
+// 管理员吊销指定用户的所有会话（管理员自己发起请求的会话除外）
+#[instrument(skip(state, manager))]
+pub async fn admin_remove_sessions(
+    ApiPath(user_id): ApiPath<String>,
+    State(state): State<AppState>,
+    manager: RequirePermission<ManageUsers>,
+) -> ApiResult {
+    manager.deny_api_token()?;
+    check_managed_user(&state, &manager, &user_id).await?;
+    let revoked = state
+        .database_accessor
+        .remove_user_sessions(&user_id, manager.session().map(|x| x.id.as_str()))
+        .await?;
+    info!(
+        "User {} revoked {} sessions of user {}",
+        manager.user.id, revoked, user_id
+    );
+    success!(RevokedSessions { revoked })
+}
+
+// 管理员吊销指定用户的一个会话
+#[instrument(skip(state, manager))]
+pub async fn admin_remove_session(
+    ApiPath((user_id, id)): ApiPath<(String, String)>,
+    State(state): State<AppState>,
+    manager: RequirePermission<ManageUsers>,
+) -> ApiResult {
+    manager.deny_api_token()?;
+    check_managed_user(&state, &manager, &user_id).await?;
+    let session = find_user_session(&state, &user_id, &id).await?;
+    state
+        .database_accessor
+        .remove_session_by_id(&session.id)
+        .await?;
+    info!(
+        "User {} revoked session {} of user {}",
+        manager.user.id, session.id, user_id
+    );
+    success!(ApiSession::from_session(session, None))
+}
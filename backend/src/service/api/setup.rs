@@ -1,33 +1,68 @@
 use crate::fail;
+use crate::limiter::LimitKey;
+use crate::service::api::quota::{check_file_size, check_storage, get_quota};
 use crate::service::api::result::ApiError;
 use crate::service::api::result::ApiResult;
 use crate::types::AppRuntimeConfig;
 use crate::types::AppState;
+use crate::types::ClientInfo;
 use axum::body::Body;
 use axum::extract::Request;
+use axum::http::HeaderMap;
 use axum::middleware::Next;
 use axum::response::IntoResponse;
 use axum::response::Response;
 use axum::{Json, extract::State};
 use serde::Deserialize;
-use sha2::{Digest, Sha256};
 use std::str::FromStr;
 
+// 检查 X-Setup-Code 头中的设置代码，按 IP 地址限制猜测的次数
+pub fn check_setup_code(
+    state: &AppState,
+    client: &ClientInfo,
+    headers: &HeaderMap,
+) -> Result<(), ApiError> {
+    let limit_keys = client.ip_address.as_deref().map(LimitKey::Ip);
+    let limit_keys = limit_keys.as_slice();
+    if let Err(wait) = state.login_limiter.check(limit_keys) {
+        return Err(ApiError::new(
+            429,
+            format!("Too many failed attempts, try again in {} seconds", wait),
+        ));
+    }
+    let code = headers
+        .get("X-Setup-Code")
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.trim());
+    let Some(code) = code else {
+        return Err(ApiError::new(401, "Setup code required".to_string()));
+    };
+    if !crate::util::constant_time_eq(code.as_bytes(), state.setup_code.as_bytes()) {
+        state.login_limiter.record_failure(limit_keys);
+        return Err(ApiError::new(401, "Invalid setup code".to_string()));
+    }
+    Ok(())
+}
+
+// 设置接口只在完成初始设置之前可用，且必须在 X-Setup-Code 头中提供启动时输出的设置代码
 pub async fn setup_interceptor(
     State(state): State<AppState>,
+    client: ClientInfo,
     request: Request<Body>,
     next: Next,
 ) -> Response {
     if state.runtime_config.load().setup {
-        ApiError::new(
+        return ApiError::new(
             410,
             "This server is already set up. Access to setup api endpoints is forbidden."
                 .to_string(),
         )
-        .into_response()
-    } else {
-        next.run(request).await
+        .into_response();
+    }
+    if let Err(e) = check_setup_code(&state, &client, request.headers()) {
+        return e.into_response();
     }
+    next.run(request).await
 }
 
 pub async fn get_existing_config() -> ApiResult {
@@ -157,16 +192,18 @@ pub async fn admin_setup(
         );
     }
 
-    let root_id = "00000000-0000-0000-0000-000000000000";
+    // 设置向导配置的是初始化时创建的管理员，即最早的所有者
     let existing_user = state
         .database_accessor
-        .get_user_by_id(root_id)
+        .get_owners()
         .await
-        .unwrap_or(None);
+        .unwrap_or_default()
+        .into_iter()
+        .next();
 
     if let Some(user) = existing_user {
         let password_hash = if let Some(p) = payload.password {
-            format!("{:x}", Sha256::digest(p.as_bytes()))
+            crate::util::hash_password(&p).await?
         } else {
             user.password
         };
@@ -174,7 +211,7 @@ pub async fn admin_setup(
         match state
             .database_accessor
             .update_user_info(
-                root_id,
+                &user.id,
                 &payload.name,
                 &payload.email,
                 payload.avatar,
@@ -184,12 +221,12 @@ pub async fn admin_setup(
         {
             Ok(_) => crate::success!(()),
             Err(e) => {
-                tracing::error!("Failed to update root user: {}", e);
+                tracing::error!("Failed to update admin user: {}", e);
                 crate::fail!(500, "Database error")
             }
         }
     } else {
-        crate::fail!(404, "Root user not found")
+        crate::fail!(404, "Admin user not found")
     }
 }
 
@@ -240,6 +277,22 @@ pub async fn upload_setup_avatar(
         }
     };
 
+    // 头像属于最早的所有者，计入其配额
+    let owner = state
+        .database_accessor
+        .get_owners()
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .next()
+        .map(|x| x.id);
+    let size = data.len() as i64;
+    if let Some(owner) = owner.as_deref() {
+        let quota = get_quota(&state, owner).await?;
+        check_file_size(&quota, size)?;
+        check_storage(&state, owner, &quota, size, 0).await?;
+    }
+
     let filename_id = uuid::Uuid::now_v7().as_hyphenated().to_string();
     let filename = format!("{}.{}", filename_id, ext);
     let fa_clone = state.file_accessor.clone();
@@ -259,7 +312,7 @@ pub async fn upload_setup_avatar(
             None,
             None,
             Some(&original_filename),
-            Some("00000000-0000-0000-0000-000000000000"),
+            owner.as_deref(),
         )
         .await
     {
@@ -274,6 +327,10 @@ pub async fn upload_setup_avatar(
         .database_accessor
         .update_item_img(&item.id, true)
         .await;
+    let _ = state
+        .database_accessor
+        .update_item_size(&item.id, size)
+        .await;
 
     #[derive(serde::Serialize)]
     struct UploadResult {
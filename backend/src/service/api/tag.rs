@@ -0,0 +1,119 @@
+use crate::service::api::auth::CurrentUser;
+use crate::service::api::result::{ApiError, ApiJson, ApiPath, ApiResult};
+use crate::service::api::types::{ApiList, ApiTag, ApiTagMerge, ApiTagRename};
+use crate::types::{AppState, Tag};
+use crate::{fail, success};
+use axum::extract::State;
+use tracing::{info, instrument};
+
+const MAX_TAG_LENGTH: usize = 32;
+const MAX_ITEM_TAGS: usize = 20;
+
+// 检查标签名称并去除首尾空白
+fn normalize_tag(name: &str) -> Result<String, ApiError> {
+    let name = name.trim();
+    if name.is_empty()
+        || name.chars().count() > MAX_TAG_LENGTH
+        || name.chars().any(char::is_control)
+    {
+        return Err(ApiError::new(400, "Invalid tag name".to_string()));
+    }
+    Ok(name.to_string())
+}
+
+// 检查项目的标签列表，去掉重复的标签（不区分大小写）
+pub fn normalize_tags(names: &[String]) -> Result<Vec<String>, ApiError> {
+    let mut tags: Vec<String> = Vec::new();
+    for name in names {
+        let name = normalize_tag(name)?;
+        if !tags.iter().any(|x| x.to_lowercase() == name.to_lowercase()) {
+            tags.push(name);
+        }
+    }
+    if tags.len() > MAX_ITEM_TAGS {
+        return Err(ApiError::new(400, "Too many tags".to_string()));
+    }
+    Ok(tags)
+}
+
+// 用户只能管理自己的标签
+async fn find_tag(state: &AppState, current: &CurrentUser, id: &str) -> Result<Tag, ApiError> {
+    match state.database_accessor.get_tag(id).await? {
+        Some(tag) if tag.user_id == current.user.id => Ok(tag),
+        _ => Err(ApiError::new(404, "Tag not found".to_string())),
+    }
+}
+
+#[instrument(skip(state, current))]
+pub async fn get_tags(State(state): State<AppState>, current: CurrentUser) -> ApiResult {
+    let items = state
+        .database_accessor
+        .get_user_tags(&current.user.id)
+        .await?
+        .into_iter()
+        .map(|(tag, items)| ApiTag::new(tag, items))
+        .collect::<Vec<_>>();
+    success!(ApiList {
+        total: items.len() as i64,
+        items,
+    })
+}
+
+// 重命名标签，新名称已被另一个标签使用时需要改用合并
+#[instrument(skip(state, current))]
+pub async fn rename_tag(
+    ApiPath(id): ApiPath<String>,
+    State(state): State<AppState>,
+    current: CurrentUser,
+    ApiJson(body): ApiJson<ApiTagRename>,
+) -> ApiResult {
+    let tag = find_tag(&state, &current, &id).await?;
+    let name = normalize_tag(&body.name)?;
+    let da = &state.database_accessor;
+    if da
+        .get_tag_by_name(&current.user.id, &name)
+        .await?
+        .is_some_and(|x| x.id != tag.id)
+    {
+        fail!(409, "Tag already exists");
+    }
+    let tag = da.rename_tag(&tag.id, &name).await?;
+    let items = da.count_tag_items(&tag.id).await?;
+    info!("User {} renamed tag {}", current.user.id, tag.id);
+    success!(ApiTag::new(tag, items))
+}
+
+// 把一个标签合并到另一个标签，原标签下的项目改为带有目标标签
+#[instrument(skip(state, current))]
+pub async fn merge_tag(
+    ApiPath(id): ApiPath<String>,
+    State(state): State<AppState>,
+    current: CurrentUser,
+    ApiJson(body): ApiJson<ApiTagMerge>,
+) -> ApiResult {
+    let tag = find_tag(&state, &current, &id).await?;
+    let into = find_tag(&state, &current, &body.into).await?;
+    if tag.id == into.id {
+        fail!(400, "Cannot merge a tag into itself");
+    }
+    let da = &state.database_accessor;
+    da.merge_tags(&tag.id, &into.id).await?;
+    let items = da.count_tag_items(&into.id).await?;
+    info!(
+        "User {} merged tag {} into {}",
+        current.user.id, tag.id, into.id
+    );
+    success!(ApiTag::new(into, items))
+}
+
+#[instrument(skip(state, current))]
+pub async fn remove_tag(
+    ApiPath(id): ApiPath<String>,
+    State(state): State<AppState>,
+    current: CurrentUser,
+) -> ApiResult {
+    let tag = find_tag(&state, &current, &id).await?;
+    state.database_accessor.remove_tag(&tag.id).await?;
+    info!("User {} removed tag {}", current.user.id, tag.id);
+    success!(())
+}
@@ -0,0 +1,102 @@
+use crate::service::api::auth::CurrentUser;
+use crate::service::api::result::{ApiError, ApiJson, ApiPath, ApiResult};
+use crate::service::api::types::{ApiList, ApiTokenCreate, ApiTokenCreated, ApiTokenInfo};
+use crate::types::{AppState, ToPermission};
+use crate::{fail, success};
+use axum::extract::State;
+use tracing::{info, instrument};
+
+#[instrument(skip(state, current))]
+pub async fn create_token(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    ApiJson(body): ApiJson<ApiTokenCreate>,
+) -> ApiResult {
+    current.deny_impersonation()?;
+    current.deny_api_token()?;
+    let user = current.user;
+    let name = body.name.trim();
+    if name.is_empty() {
+        fail!(400, "Token name cannot be empty");
+    }
+
+    // 令牌只能携带创建者自身拥有的权限
+    let mut descriptor = 0_i64;
+    for permission in body.descriptor {
+        if !user.descriptor.contains(permission.clone()) {
+            fail!(403, "Cannot grant permission {:?} to a token", permission);
+        }
+        descriptor |= permission.into_i64();
+    }
+
+    let expires_at = if let Some(x) = body.expires_at {
+        let expires_at = x
+            .parse::<chrono::DateTime<chrono::Utc>>()
+            .map_err(|e| ApiError::new(400, e.to_string()))?
+            .with_timezone(&chrono::Local)
+            .naive_local();
+        if expires_at <= chrono::Local::now().naive_local() {
+            fail!(400, "Expiration time must be in the future");
+        }
+        Some(expires_at)
+    } else {
+        None
+    };
+
+    let token = format!(
+        "spt_{}",
+        crate::util::random_string(
+            48,
+            Some("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ1234567890"),
+        )
+    );
+    let api_token = state
+        .database_accessor
+        .create_api_token(&token, &user.id, name, descriptor, expires_at)
+        .await?;
+    info!("User {} created API token {}", user.id, api_token.id);
+    success!(ApiTokenCreated {
+        token,
+        info: ApiTokenInfo::from(api_token),
+    })
+}
+
+#[instrument(skip(state, user))]
+pub async fn get_tokens(
+    State(state): State<AppState>,
+    CurrentUser { user, .. }: CurrentUser,
+) -> ApiResult {
+    let items = state
+        .database_accessor
+        .get_user_api_tokens(&user.id)
+        .await?
+        .into_iter()
+        .map(ApiTokenInfo::from)
+        .collect::<Vec<_>>();
+    success!(ApiList {
+        total: items.len() as i64,
+        items,
+    })
+}
+
+#[instrument(skip(state, current))]
+pub async fn remove_token(
+    ApiPath(id): ApiPath<String>,
+    State(state): State<AppState>,
+    current: CurrentUser,
+) -> ApiResult {
+    current.deny_impersonation()?;
+    current.deny_api_token()?;
+    let user = current.user;
+    let api_token = state.database_accessor.get_api_token(&id).await?;
+    // 不区分令牌不存在和不属于当前用户，避免泄露其他用户的令牌 ID
+    let Some(api_token) = api_token.filter(|x| x.user_id == user.id) else {
+        fail!(404, "Token not found");
+    };
+    state
+        .database_accessor
+        .remove_api_token(&api_token.id)
+        .await?;
+    info!("User {} revoked API token {}", user.id, api_token.id);
+    success!(ApiTokenInfo::from(api_token))
+}
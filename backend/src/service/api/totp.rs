@@ -0,0 +1,215 @@
+use crate::service::api::auth::{CurrentUser, ManageUsers, RequirePermission};
+use crate::service::api::result::{ApiJson, ApiPath, ApiResult};
+use crate::types::{AppState, User};
+use crate::{fail, success};
+use axum::extract::State;
+use serde::{Deserialize, Serialize};
+use totp_rs::{Algorithm, Secret, TOTP};
+use tracing::{info, instrument};
+
+const TOTP_ISSUER: &str = "Spectra";
+const RECOVERY_CODE_COUNT: usize = 10;
+
+fn build_totp(secret: &str, account_name: &str) -> anyhow::Result<TOTP> {
+    let secret = Secret::Encoded(secret.to_string())
+        .to_bytes()
+        .map_err(|e| anyhow::anyhow!("Invalid TOTP secret: {:?}", e))?;
+    // otpauth URI 的标签中不能出现冒号
+    let account_name = account_name.replace(':', "");
+    TOTP::new(
+        Algorithm::SHA1,
+        6,
+        1,
+        30,
+        secret,
+        Some(TOTP_ISSUER.to_string()),
+        account_name,
+    )
+    .map_err(|e| anyhow::anyhow!("Failed to build TOTP: {:?}", e))
+}
+
+fn generate_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let charset = "abcdefghijkmnpqrstuvwxyz23456789";
+            format!(
+                "{}-{}",
+                crate::util::random_string(5, Some(charset)),
+                crate::util::random_string(5, Some(charset))
+            )
+        })
+        .collect()
+}
+
+// 检查用户提交的 TOTP 验证码
+fn check_totp_code(user: &User, code: &str) -> anyhow::Result<bool> {
+    let Some(secret) = user.totp_secret.as_deref() else {
+        return Ok(false);
+    };
+    let totp = build_totp(secret, &user.email)?;
+    Ok(totp.check_current(code.trim())?)
+}
+
+// 校验第二因素：可以是当前的 TOTP 验证码，也可以是一个未使用过的恢复码
+pub async fn verify_second_factor(
+    state: &AppState,
+    user: &User,
+    code: &str,
+) -> anyhow::Result<bool> {
+    if check_totp_code(user, code)? {
+        return Ok(true);
+    }
+    let code = code.trim().to_lowercase();
+    state
+        .database_accessor
+        .use_recovery_code(&user.id, &code)
+        .await
+}
+
+#[derive(Deserialize)]
+pub struct TotpCodePayload {
+    pub code: String,
+}
+
+#[derive(Serialize)]
+pub struct TotpEnrollment {
+    secret: String,
+    otpauth_uri: String,
+}
+
+#[derive(Serialize)]
+pub struct RecoveryCodes {
+    recovery_codes: Vec<String>,
+}
+
+// 开始启用两步验证：生成新的密钥，在确认之前不会生效
+#[instrument(skip(state, current))]
+pub async fn enroll(State(state): State<AppState>, current: CurrentUser) -> ApiResult {
+    current.deny_impersonation()?;
+    current.deny_api_token()?;
+    let user = current.user;
+    if user.totp_enabled {
+        fail!(409, "Two-factor authentication is already enabled");
+    }
+
+    let secret = Secret::generate_secret().to_encoded().to_string();
+    let totp = build_totp(&secret, &user.email)?;
+    state
+        .database_accessor
+        .set_user_totp(&user.id, Some(&secret), false)
+        .await?;
+    info!("User {} started two-factor enrollment", user.id);
+    success!(TotpEnrollment {
+        secret,
+        otpauth_uri: totp.get_url(),
+    })
+}
+
+// 用第一个验证码确认启用两步验证，返回一次性恢复码
+#[instrument(skip(state, current, payload))]
+pub async fn confirm(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    ApiJson(payload): ApiJson<TotpCodePayload>,
+) -> ApiResult {
+    current.deny_impersonation()?;
+    current.deny_api_token()?;
+    let user = current.user;
+    if user.totp_enabled {
+        fail!(409, "Two-factor authentication is already enabled");
+    }
+    if user.totp_secret.is_none() {
+        fail!(400, "Two-factor enrollment has not been started");
+    }
+    if !check_totp_code(&user, &payload.code)? {
+        fail!(401, "Invalid verification code");
+    }
+
+    let recovery_codes = generate_recovery_codes();
+    state
+        .database_accessor
+        .set_user_totp(&user.id, user.totp_secret.as_deref(), true)
+        .await?;
+    state
+        .database_accessor
+        .replace_recovery_codes(&user.id, &recovery_codes)
+        .await?;
+    info!("User {} enabled two-factor authentication", user.id);
+    success!(RecoveryCodes { recovery_codes })
+}
+
+// 关闭两步验证，需要提供验证码或恢复码
+#[instrument(skip(state, current, payload))]
+pub async fn disable(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    ApiJson(payload): ApiJson<TotpCodePayload>,
+) -> ApiResult {
+    current.deny_impersonation()?;
+    current.deny_api_token()?;
+    let user = current.user;
+    if !user.totp_enabled {
+        fail!(409, "Two-factor authentication is not enabled");
+    }
+    if !verify_second_factor(&state, &user, &payload.code).await? {
+        fail!(401, "Invalid verification code");
+    }
+    state
+        .database_accessor
+        .set_user_totp(&user.id, None, false)
+        .await?;
+    state
+        .database_accessor
+        .remove_recovery_codes(&user.id)
+        .await?;
+    info!("User {} disabled two-factor authentication", user.id);
+    success!(())
+}
+
+// 重新生成恢复码，旧的恢复码全部作废
+#[instrument(skip(state, current, payload))]
+pub async fn regenerate_recovery_codes(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    ApiJson(payload): ApiJson<TotpCodePayload>,
+) -> ApiResult {
+    current.deny_impersonation()?;
+    current.deny_api_token()?;
+    let user = current.user;
+    if !user.totp_enabled {
+        fail!(409, "Two-factor authentication is not enabled");
+    }
+    if !check_totp_code(&user, &payload.code)? {
+        fail!(401, "Invalid verification code");
+    }
+    let recovery_codes = generate_recovery_codes();
+    state
+        .database_accessor
+        .replace_recovery_codes(&user.id, &recovery_codes)
+        .await?;
+    success!(RecoveryCodes { recovery_codes })
+}
+
+// 管理员重置用户的两步验证，用于用户丢失设备和恢复码的情况
+#[instrument(skip(state, manager))]
+pub async fn admin_reset(
+    ApiPath(id): ApiPath<String>,
+    State(state): State<AppState>,
+    manager: RequirePermission<ManageUsers>,
+) -> ApiResult {
+    manager.deny_api_token()?;
+    let Some(user) = state.database_accessor.get_user_by_id(&id).await? else {
+        fail!(404, "User not found");
+    };
+    manager.check_manageable(&state, &user).await?;
+    state
+        .database_accessor
+        .set_user_totp(&id, None, false)
+        .await?;
+    state.database_accessor.remove_recovery_codes(&id).await?;
+    info!(
+        "User {} reset two-factor authentication of user {}",
+        manager.user.id, id
+    );
+    success!(())
+}
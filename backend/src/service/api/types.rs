@@ -1,5 +1,9 @@
 use crate::data::FileAccessor;
-use crate::types::{Item, ItemType, User, UserPermission};
+use crate::types::{
+    AccessLog, ApiToken, AppState, AuditLog, Group, GroupMember, Invite, Item, ItemDisposal,
+    ItemFilter, ItemRevision, ItemSort, ItemType, OperationType, QuotaConfig, Session, SortOrder,
+    Tag, User, UserPermission,
+};
 use chrono::{DateTime, Local, TimeZone, Utc};
 use serde::{Deserialize, Serialize};
 
@@ -12,6 +16,9 @@ pub struct ItemSimplified {
     pub created_at: DateTime<Utc>,
     pub creator: Option<String>,
     pub available: bool,
+    pub group_id: Option<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub tags: Vec<String>,
 }
 
 impl From<Item> for ItemSimplified {
@@ -27,6 +34,11 @@ impl From<Item> for ItemSimplified {
                 .with_timezone(&Utc),
             creator: item.creator,
             available: item.available,
+            group_id: item.group_id,
+            expires_at: item
+                .expires_at
+                .map(|x| Local.from_local_datetime(&x).unwrap().with_timezone(&Utc)),
+            tags: Vec::new(),
         }
     }
 }
@@ -40,11 +52,75 @@ pub struct ApiUser {
     pub created_at: DateTime<Utc>,
     pub descriptor: Vec<UserPermission>,
     pub item_count: i64,
+    pub totp_enabled: bool,
+    pub auth_source: String,
+    pub status: String,
+    pub owner: bool,
+    pub quota: ApiQuota,
+}
+
+// /user-info 的返回值，模拟登录期间附带模拟的信息
+#[derive(Serialize)]
+pub struct ApiUserInfo {
+    #[serde(flatten)]
+    pub user: ApiUser,
+    pub impersonation: Option<ApiImpersonation>,
+}
+
+#[derive(Serialize)]
+pub struct ApiImpersonation {
+    pub impersonator_id: String,
+    pub impersonator_name: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl ApiImpersonation {
+    pub async fn from_session(state: &AppState, session: &Session) -> anyhow::Result<Self> {
+        let impersonator_id = session.impersonator_id.clone().unwrap_or_default();
+        let impersonator_name = state
+            .database_accessor
+            .get_user_by_id(&impersonator_id)
+            .await?
+            .map(|x| x.name)
+            .unwrap_or_default();
+        Ok(Self {
+            impersonator_id,
+            impersonator_name,
+            expires_at: Local
+                .from_local_datetime(&session.expires_at)
+                .unwrap()
+                .with_timezone(&Utc),
+        })
+    }
+}
+
+// 用户当前的占用和配额
+#[derive(Serialize)]
+pub struct ApiQuota {
+    pub storage_used: i64,
+    pub items_used: i64,
+    // 实际生效的配额
+    pub limits: QuotaConfig,
+    // 用户自己的配额，为空时使用默认配额，负数表示不限制
+    pub custom: QuotaConfig,
 }
 
 impl ApiUser {
-    pub async fn from_user(user: User, db: &crate::data::DatabaseAccessor) -> anyhow::Result<Self> {
+    pub async fn from_user(user: User, state: &AppState) -> anyhow::Result<Self> {
+        let db = &state.database_accessor;
         let item_count = db.count_user_items(&user.id).await?;
+        let (storage_used, items_used) = db.get_user_usage(&user.id).await?;
+        let quota = ApiQuota {
+            storage_used,
+            items_used,
+            limits: state.runtime_config.load().quota.for_user(&user),
+            custom: QuotaConfig {
+                storage: user.quota_storage,
+                items: user.quota_items,
+                file_size: user.quota_file_size,
+                expiry: user.quota_expiry,
+            },
+        };
         Ok(Self {
             id: user.id,
             name: user.name,
@@ -56,6 +132,11 @@ impl ApiUser {
                 .with_timezone(&Utc),
             descriptor: UserPermission::from_i64(user.descriptor),
             item_count,
+            totp_enabled: user.totp_enabled,
+            auth_source: user.auth_source,
+            status: user.status,
+            owner: user.owner,
+            quota,
         })
     }
 }
@@ -69,12 +150,31 @@ pub struct ApiUserCreate {
     pub avatar: Option<String>,
 }
 
+#[derive(Deserialize, Debug)]
+pub struct ApiUserRemoval {
+    #[serde(default)]
+    pub items: ItemDisposal,
+    // items 为 reassign 时接收项目的用户
+    pub reassign_to: Option<String>,
+}
+
+// 用户删除自己的账户，需要再次验证身份
+#[derive(Deserialize)]
+pub struct ApiAccountDeletion {
+    pub password: Option<String>,
+    // 启用了两步验证时需要提供验证码或恢复码
+    pub code: Option<String>,
+    #[serde(default)]
+    pub items: ItemDisposal,
+}
+
 #[derive(Serialize)]
 pub struct ApiCode {
     pub id: String,
     pub path: String,
     pub content: String,
     pub language: Option<String>,
+    pub revision: i64,
 }
 
 impl ApiCode {
@@ -89,6 +189,7 @@ impl ApiCode {
             path: item.short_path,
             content,
             language: item.extra_data,
+            revision: item.revision,
         }
     }
 }
@@ -101,6 +202,145 @@ pub struct ApiItemUpload {
     pub item_type: ItemType,
     pub max_visits: Option<i64>,
     pub password: Option<String>,
+    pub group_id: Option<String>,
+}
+
+// 修改项目时未提供的字段保持不变，提供 null 表示清除该字段
+#[derive(Deserialize, Debug, Default)]
+pub struct ApiItemUpdate {
+    pub data: Option<String>,
+    pub short_path: Option<String>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub password: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub expires_at: Option<Option<String>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub max_visits: Option<Option<i64>>,
+    #[serde(default, deserialize_with = "nullable")]
+    pub extra_data: Option<Option<String>>,
+    // 代码项目最多保留的历史版本数，不能超过全站设置
+    #[serde(default, deserialize_with = "nullable")]
+    pub max_revisions: Option<Option<i64>>,
+    // 改名时是否在原路径保留跳转
+    #[serde(default)]
+    pub keep_redirect: bool,
+    // 替换项目的全部标签
+    pub tags: Option<Vec<String>>,
+}
+
+// 代码项目的一个版本，current 表示项目当前的内容
+#[derive(Serialize)]
+pub struct ApiRevision {
+    pub revision: i64,
+    pub size: i64,
+    pub created_at: DateTime<Utc>,
+    pub created_by: Option<String>,
+    pub current: bool,
+}
+
+impl ApiRevision {
+    pub fn from_item(item: &Item) -> Self {
+        Self {
+            revision: item.revision,
+            size: item.size.unwrap_or(0),
+            created_at: Local
+                .from_local_datetime(&item.updated_at.unwrap_or(item.created_at))
+                .unwrap()
+                .with_timezone(&Utc),
+            created_by: item.updated_by.clone().or(item.creator.clone()),
+            current: true,
+        }
+    }
+}
+
+impl From<ItemRevision> for ApiRevision {
+    fn from(revision: ItemRevision) -> Self {
+        Self {
+            revision: revision.revision,
+            size: revision.size,
+            created_at: Local
+                .from_local_datetime(&revision.created_at)
+                .unwrap()
+                .with_timezone(&Utc),
+            created_by: revision.created_by,
+            current: false,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ApiRevisionContent {
+    #[serde(flatten)]
+    pub info: ApiRevision,
+    pub content: String,
+}
+
+// 两个版本之间的差异（统一格式）
+#[derive(Serialize)]
+pub struct ApiRevisionDiff {
+    pub from: i64,
+    pub to: i64,
+    pub diff: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ApiRevisionDiffQuery {
+    pub from: i64,
+    // 缺省为当前版本
+    pub to: Option<i64>,
+}
+
+// 区分字段缺失（None）和字段为 null（Some(None)）
+fn nullable<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+// 搜索结果，snippet 是经过 HTML 转义的匹配片段，匹配的部分用 <mark> 标出
+#[derive(Serialize)]
+pub struct ApiSearchResult {
+    #[serde(flatten)]
+    pub item: ItemSimplified,
+    pub snippet: String,
+}
+
+impl ApiSearchResult {
+    // 数据库返回的片段中匹配的部分用 \u{1} 和 \u{2} 包围
+    pub fn new(item: Item, snippet: &str) -> Self {
+        let mut html = String::with_capacity(snippet.len());
+        for c in snippet.chars() {
+            match c {
+                '\u{1}' => html.push_str("<mark>"),
+                '\u{2}' => html.push_str("</mark>"),
+                '&' => html.push_str("&amp;"),
+                '<' => html.push_str("&lt;"),
+                '>' => html.push_str("&gt;"),
+                '"' => html.push_str("&quot;"),
+                '\'' => html.push_str("&#39;"),
+                c => html.push(c),
+            }
+        }
+        Self {
+            item: ItemSimplified::from(item),
+            snippet: html,
+        }
+    }
+}
+
+// 游客创建的项目，管理密钥只在创建时返回一次
+#[derive(Serialize)]
+pub struct ApiGuestItem {
+    #[serde(flatten)]
+    pub item: ItemSimplified,
+    pub manage_key: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ApiItemClaim {
+    pub keys: Vec<String>,
 }
 
 #[derive(Serialize)]
@@ -116,6 +356,8 @@ pub struct ApiItemFull {
     pub extra_data: Option<String>,
     pub creator: Option<String>,
     pub available: bool,
+    pub group_id: Option<String>,
+    pub tags: Vec<String>,
 }
 
 impl From<Item> for ApiItemFull {
@@ -137,6 +379,8 @@ impl From<Item> for ApiItemFull {
             extra_data: item.extra_data,
             creator: item.creator,
             available: item.available,
+            group_id: item.group_id,
+            tags: Vec::new(),
         }
     }
 }
@@ -146,3 +390,349 @@ pub struct ApiList<T> {
     pub total: i64,
     pub items: Vec<T>,
 }
+
+// 项目列表的查询参数：筛选、排序和分页
+// 传入上一页返回的 cursor 时从该位置继续，否则使用 offset
+#[derive(Deserialize, Debug, Default)]
+pub struct ApiItemQuery {
+    pub user: Option<String>,
+    pub group: Option<String>,
+    pub item_type: Option<ItemType>,
+    pub available: Option<bool>,
+    pub password: Option<bool>,
+    pub expiry: Option<bool>,
+    pub img: Option<bool>,
+    pub tag: Option<String>,
+    pub created_after: Option<DateTime<Utc>>,
+    pub created_before: Option<DateTime<Utc>>,
+    pub expires_after: Option<DateTime<Utc>>,
+    pub expires_before: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub sort: ItemSort,
+    #[serde(default)]
+    pub order: SortOrder,
+    pub cursor: Option<String>,
+    pub offset: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+impl ApiItemQuery {
+    // 转换为数据库筛选条件，创建者和组由调用方根据权限设置
+    pub fn filter(&self) -> ItemFilter {
+        let local = |x: &DateTime<Utc>| x.with_timezone(&Local).naive_local();
+        ItemFilter {
+            creator: None,
+            group_id: None,
+            item_type: self.item_type,
+            available: self.available,
+            password: self.password,
+            expiry: self.expiry,
+            img: self.img,
+            tag: self.tag.clone(),
+            created_after: self.created_after.as_ref().map(local),
+            created_before: self.created_before.as_ref().map(local),
+            expires_after: self.expires_after.as_ref().map(local),
+            expires_before: self.expires_before.as_ref().map(local),
+            sort: self.sort,
+            order: self.order,
+        }
+    }
+}
+
+// 项目列表的一页，next_cursor 为空时表示没有更多项目
+#[derive(Serialize)]
+pub struct ApiItemPage {
+    pub total: i64,
+    pub items: Vec<ItemSimplified>,
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ApiTokenCreate {
+    pub name: String,
+    pub descriptor: Vec<UserPermission>,
+    pub expires_at: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ApiTokenInfo {
+    pub id: String,
+    pub name: String,
+    pub descriptor: Vec<UserPermission>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl From<ApiToken> for ApiTokenInfo {
+    fn from(token: ApiToken) -> Self {
+        Self {
+            id: token.id,
+            name: token.name,
+            descriptor: UserPermission::from_i64(token.descriptor),
+            created_at: Local
+                .from_local_datetime(&token.created_at)
+                .unwrap()
+                .with_timezone(&Utc),
+            expires_at: token
+                .expires_at
+                .map(|x| Local.from_local_datetime(&x).unwrap().with_timezone(&Utc)),
+            last_used_at: token
+                .last_used_at
+                .map(|x| Local.from_local_datetime(&x).unwrap().with_timezone(&Utc)),
+        }
+    }
+}
+
+// 创建令牌时返回的信息，明文令牌只在这里出现一次
+#[derive(Serialize)]
+pub struct ApiTokenCreated {
+    pub token: String,
+    #[serde(flatten)]
+    pub info: ApiTokenInfo,
+}
+
+// 登录会话的信息，不包含令牌本身
+#[derive(Serialize)]
+pub struct ApiSession {
+    pub id: String,
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub remember: bool,
+    // 管理员模拟登录时创建的会话，记录发起模拟的管理员
+    pub impersonator_id: Option<String>,
+    // 是否是发起本次请求的会话
+    pub current: bool,
+}
+
+impl ApiSession {
+    pub fn from_session(session: Session, current_id: Option<&str>) -> Self {
+        let to_utc = |x| Local.from_local_datetime(&x).unwrap().with_timezone(&Utc);
+        Self {
+            current: current_id == Some(session.id.as_str()),
+            id: session.id,
+            created_at: to_utc(session.created_at),
+            last_used_at: to_utc(session.last_used_at),
+            expires_at: to_utc(session.expires_at),
+            ip_address: session.ip_address,
+            user_agent: session.user_agent,
+            remember: session.remember,
+            impersonator_id: session.impersonator_id,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ApiInviteCreate {
+    pub descriptor: Vec<UserPermission>,
+    pub expires_at: Option<String>,
+    pub max_uses: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct ApiInviteInfo {
+    pub id: String,
+    pub created_by: String,
+    pub descriptor: Vec<UserPermission>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub max_uses: Option<i64>,
+    pub uses: i64,
+    pub usable: bool,
+}
+
+impl From<Invite> for ApiInviteInfo {
+    fn from(invite: Invite) -> Self {
+        let to_utc = |x| Local.from_local_datetime(&x).unwrap().with_timezone(&Utc);
+        Self {
+            usable: invite.is_usable(),
+            id: invite.id,
+            created_by: invite.created_by,
+            descriptor: UserPermission::from_i64(invite.descriptor),
+            created_at: to_utc(invite.created_at),
+            expires_at: invite.expires_at.map(to_utc),
+            max_uses: invite.max_uses,
+            uses: invite.uses,
+        }
+    }
+}
+
+// 创建邀请时返回的信息，明文邀请码只在这里出现一次
+#[derive(Serialize)]
+pub struct ApiInviteCreated {
+    pub code: String,
+    pub link: String,
+    #[serde(flatten)]
+    pub info: ApiInviteInfo,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ApiRegister {
+    pub name: String,
+    pub email: String,
+    pub password: String,
+    // 使用邀请码注册；不提供时按开放注册处理
+    pub invite: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct ApiAccessLog {
+    pub id: String,
+    pub accessed_at: DateTime<Utc>,
+    pub path: String,
+    pub operation: OperationType,
+    pub success: bool,
+    pub ip_address: String,
+    pub initiator: Option<String>,
+}
+
+impl From<AccessLog> for ApiAccessLog {
+    fn from(log: AccessLog) -> Self {
+        Self {
+            id: log.id,
+            accessed_at: Local
+                .from_local_datetime(&log.accessed_at)
+                .unwrap()
+                .with_timezone(&Utc),
+            path: log.path,
+            operation: log.operation,
+            success: log.success,
+            ip_address: log.ip_address,
+            initiator: log.initiator,
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ApiAuditLog {
+    pub id: String,
+    pub actor_id: String,
+    pub user_id: String,
+    pub action: String,
+    pub detail: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<AuditLog> for ApiAuditLog {
+    fn from(log: AuditLog) -> Self {
+        Self {
+            id: log.id,
+            actor_id: log.actor_id,
+            user_id: log.user_id,
+            action: log.action,
+            detail: log.detail,
+            ip_address: log.ip_address,
+            created_at: Local
+                .from_local_datetime(&log.created_at)
+                .unwrap()
+                .with_timezone(&Utc),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ApiGroup {
+    pub id: String,
+    pub name: String,
+    pub descriptor: Vec<UserPermission>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<Group> for ApiGroup {
+    fn from(group: Group) -> Self {
+        Self {
+            id: group.id,
+            name: group.name,
+            descriptor: UserPermission::from_i64(group.descriptor),
+            created_at: Local
+                .from_local_datetime(&group.created_at)
+                .unwrap()
+                .with_timezone(&Utc),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ApiGroupMember {
+    pub user_id: String,
+    pub name: String,
+    pub email: String,
+    pub role: String,
+    pub joined_at: DateTime<Utc>,
+}
+
+impl ApiGroupMember {
+    pub fn new(member: GroupMember, user: &User) -> Self {
+        Self {
+            user_id: member.user_id,
+            name: user.name.clone(),
+            email: user.email.clone(),
+            role: member.role,
+            joined_at: Local
+                .from_local_datetime(&member.created_at)
+                .unwrap()
+                .with_timezone(&Utc),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ApiGroupFull {
+    #[serde(flatten)]
+    pub group: ApiGroup,
+    pub members: Vec<ApiGroupMember>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ApiGroupCreate {
+    pub name: String,
+    #[serde(default)]
+    pub descriptor: Vec<UserPermission>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ApiGroupUpdate {
+    pub name: Option<String>,
+    pub descriptor: Option<Vec<UserPermission>>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ApiGroupMemberUpdate {
+    pub role: String,
+}
+
+#[derive(Serialize)]
+pub struct ApiTag {
+    pub id: String,
+    pub name: String,
+    pub items: i64, // 带有该标签的项目数量
+    pub created_at: DateTime<Utc>,
+}
+
+impl ApiTag {
+    pub fn new(tag: Tag, items: i64) -> Self {
+        Self {
+            id: tag.id,
+            name: tag.name,
+            items,
+            created_at: Local
+                .from_local_datetime(&tag.created_at)
+                .unwrap()
+                .with_timezone(&Utc),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ApiTagRename {
+    pub name: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ApiTagMerge {
+    pub into: String, // 合并到的标签 ID
+}
@@ -1,21 +1,32 @@
-use crate::service::api::result::{ApiError, ApiResult};
-use crate::service::api::types::{ApiList, ApiUser, ApiUserCreate};
-use crate::types::{AppState, ToPermission, Token, UserPermission};
+use crate::data::DatabaseAccessor;
+use crate::limiter::LimitKey;
+use crate::service::api::auth::{CurrentUser, Manage, ManageUsers, RequirePermission};
+use crate::service::api::impersonation;
+use crate::service::api::ldap;
+use crate::service::api::result::{ApiError, ApiJson, ApiPath, ApiQuery, ApiResult};
+use crate::service::api::totp::{TotpCodePayload, verify_second_factor};
+use crate::service::api::types::{
+    ApiAccountDeletion, ApiImpersonation, ApiList, ApiUser, ApiUserCreate, ApiUserInfo,
+    ApiUserRemoval,
+};
+use crate::types::{
+    AppState, ClientInfo, ItemDisposal, QuotaConfig, Session, ToPermission, User, UserPermission,
+};
 use crate::{fail, success};
 use axum::Json;
-use axum::extract::{Path, State};
+use axum::extract::State;
 use axum_extra::extract::PrivateCookieJar;
-use axum_extra::extract::cookie::Cookie;
+use axum_extra::extract::cookie::{Cookie, SameSite};
 use cookie::time::Duration;
 use serde_json::Value;
-use sha2::{Digest, Sha256};
-use tracing::{debug, instrument};
+use tracing::{info, instrument, warn};
 use uuid::Uuid;
 
 #[instrument(skip(state, jar))]
 pub async fn login(
     State(state): State<AppState>,
     jar: PrivateCookieJar,
+    client: ClientInfo,
     Json(payload): Json<Value>,
 ) -> ApiResult {
     if payload.is_object() && payload.get("email").is_some() && payload.get("password").is_some() {
@@ -27,91 +38,292 @@ pub async fn login(
             .get("password")
             .and_then(|v| v.as_str())
             .ok_or_else(|| ApiError::new(400, "Invalid password format".to_string()))?;
+        // “记住我”是可选的，缺省时按普通会话处理
+        let remember = payload
+            .get("remember")
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false);
+        let limit_keys = LimitKey::Account(email).with_ip(client.ip_address.as_deref());
+        if let Err(wait) = state.login_limiter.check(&limit_keys) {
+            fail!(
+                429,
+                "Too many failed attempts, try again in {} seconds",
+                wait
+            );
+        }
+        // 配置了 LDAP 时优先使用目录认证，目录中没有该用户或目录不可用时回退到本地密码
+        // 空密码在 LDAP 中会被当作匿名绑定，必须直接排除
+        if let Some(config) = state.ldap.clone()
+            && !password.is_empty()
+        {
+            match ldap::authenticate(&config, email, password).await {
+                Ok(Some(entry)) => {
+                    state.login_limiter.record_success(&limit_keys);
+                    let user = ldap::resolve_user(&state, &config, entry).await?;
+                    check_user_status(&user)?;
+                    let (jar, totp_required) =
+                        begin_login(&state, jar, &user, remember, &client).await?;
+                    if totp_required {
+                        success!(LoginChallenge { totp_required }, jar);
+                    }
+                    let api_user = ApiUser::from_user(user, &state).await?;
+                    success!(api_user, jar);
+                }
+                Ok(None) => {}
+                Err(e) => warn!("LDAP authentication failed: {}", e),
+            }
+        }
         let user = state.database_accessor.get_user_by_email(email).await?;
-        debug!("{:?}", user);
-        if user.as_ref().is_some_and(|user| {
-            user.password == format!("{:x}", Sha256::digest(password.as_bytes()))
-        }) {
+        let (matched, needs_rehash) = match user.as_ref() {
+            Some(user) => crate::util::verify_password(password, &user.password).await?,
+            None => (false, false),
+        };
+        if matched {
+            state.login_limiter.record_success(&limit_keys);
             let user = user.unwrap();
-            let token = crate::util::random_password();
-            let _ = state
-                .user_tokens
-                .insert(token.clone(), Token::new(user.id.clone(), false));
-            let jar = jar.remove("token").add(
-                Cookie::build(("token", token.clone()))
-                    .max_age(Duration::new(600, 0))
-                    .http_only(true)
-                    .path("/")
-                    .build(),
-            );
-            let api_user = ApiUser::from_user(user, &state.database_accessor).await?;
+            check_user_status(&user)?;
+            if needs_rehash {
+                // 旧的 SHA-256 哈希或参数过时的哈希，在登录成功时透明地升级
+                let new_hash = crate::util::hash_password(password).await?;
+                state
+                    .database_accessor
+                    .change_user_password(&user.id, &new_hash)
+                    .await?;
+            }
+            let (jar, totp_required) = begin_login(&state, jar, &user, remember, &client).await?;
+            if totp_required {
+                success!(LoginChallenge { totp_required }, jar);
+            }
+            let api_user = ApiUser::from_user(user, &state).await?;
             success!(api_user, jar);
         }
+        state.login_limiter.record_failure(&limit_keys);
         fail!(401, "Invalid email or password");
     }
     fail!(400, "Invalid request")
 }
 
-pub async fn logout(State(state): State<AppState>, jar: PrivateCookieJar) -> ApiResult {
-    // 在服务器端的 hashmap 中移除 token（如果有）
-    let token = jar.get("token").map(|c| c.value().to_string());
-    if token.is_some() {
-        state.user_tokens.remove(&token.unwrap());
+// 检查用户是否可以登录
+pub fn check_user_status(user: &User) -> Result<(), ApiError> {
+    if user.status == "pending" {
+        fail!(403, "Account is awaiting approval");
     }
+    if !user.is_active() {
+        fail!(403, "Account is not active");
+    }
+    Ok(())
+}
 
-    // 移除用户的 token cookie
-    let jar = jar.remove("token");
-    success!((), jar)
+#[derive(serde::Serialize)]
+struct LoginChallenge {
+    totp_required: bool,
 }
 
-#[instrument(skip(state, jar))]
-pub async fn user_info(State(state): State<AppState>, jar: PrivateCookieJar) -> ApiResult {
-    let token = jar.get("token").map(|c| c.value().to_string());
-    if token.is_none() {
-        fail!(401, "Unauthorized");
+// 用户已通过第一步验证（密码或外部身份源）后调用
+// 开启了两步验证时只发放一个只能用于 /api/login/totp 的短期会话，并返回 true
+pub async fn begin_login(
+    state: &AppState,
+    jar: PrivateCookieJar,
+    user: &User,
+    remember: bool,
+    client: &ClientInfo,
+) -> anyhow::Result<(PrivateCookieJar, bool)> {
+    if !user.totp_enabled {
+        let jar = start_session(state, jar, &user.id, remember, client).await?;
+        return Ok((jar, false));
+    }
+    let token = crate::util::random_string(64, None);
+    state
+        .database_accessor
+        .create_pending_session(&token, &user.id, remember, client)
+        .await?;
+    let lifetime = Session::pending_lifetime();
+    let jar = jar.remove("pending_token").add(
+        Cookie::build(("pending_token", token))
+            .max_age(Duration::seconds(lifetime.num_seconds()))
+            .http_only(true)
+            .same_site(SameSite::Strict)
+            .path("/api/login")
+            .build(),
+    );
+    Ok((jar, true))
+}
+
+// 找到外部身份源用户对应的本地用户：先按外部 ID 查找并同步信息，
+// link_by_email 为真时再按邮箱关联尚未关联外部身份的已有用户，都没有时创建新用户
+// 关联后的用户和其他外部用户一样，名称和权限由身份源同步，原有的密码仍然可以使用
+pub async fn resolve_external_user(
+    da: &DatabaseAccessor,
+    auth_source: &str,
+    external_id: &str,
+    name: &str,
+    email: &str,
+    descriptor: i64,
+    link_by_email: bool,
+) -> Result<User, ApiError> {
+    if let Some(user) = da.get_user_by_external_id(auth_source, external_id).await? {
+        return Ok(da
+            .update_external_user(&user.id, name, email, descriptor)
+            .await?);
+    }
+    if let Some(user) = da.get_user_by_email(email).await? {
+        if !link_by_email || user.external_id.is_some() {
+            info!(
+                "Refused to link user {} to {} identity {}",
+                user.id, auth_source, external_id
+            );
+            fail!(409, "An account with this email address already exists");
+        }
+        let user = da
+            .link_external_user(&user.id, auth_source, external_id, name, descriptor)
+            .await?;
+        info!(
+            "Linked user {} to {} identity {}",
+            user.id, auth_source, external_id
+        );
+        return Ok(user);
     }
-    let token = token.unwrap();
-    let user = state.user_tokens.get(&token);
-    if user.is_none() {
-        fail!(401, "Unauthorized");
+    let id = Uuid::new_v4().to_string();
+    let user = da
+        .create_external_user(&id, name, email, descriptor, auth_source, external_id)
+        .await?;
+    info!(
+        "Provisioned user {} from {} identity {}",
+        id, auth_source, external_id
+    );
+    Ok(user)
+}
+
+// 为用户创建新的登录会话，并把令牌写入 cookie
+async fn start_session(
+    state: &AppState,
+    jar: PrivateCookieJar,
+    user_id: &str,
+    remember: bool,
+    client: &ClientInfo,
+) -> anyhow::Result<PrivateCookieJar> {
+    let token = crate::util::random_string(64, None);
+    state
+        .database_accessor
+        .create_session(&token, user_id, false, remember, client)
+        .await?;
+    Ok(jar.remove("token").add(session_cookie(token, remember)))
+}
+
+// 保存会话令牌的 cookie
+pub fn session_cookie(token: String, remember: bool) -> Cookie<'static> {
+    // Lax：从其他网站点击链接打开时仍保持登录，但跨站的 POST 等请求不会带上 cookie
+    let mut cookie = Cookie::build(("token", token))
+        .http_only(true)
+        .same_site(SameSite::Lax)
+        .path("/")
+        .build();
+    // 未勾选“记住我”时使用浏览器会话 cookie，关闭浏览器即失效
+    if remember {
+        let lifetime = Session::lifetime(false, true);
+        cookie.set_max_age(Duration::seconds(lifetime.num_seconds()));
+    }
+    cookie
+}
+
+// 两步验证的第二步：用 TOTP 验证码或恢复码换取正式的会话
+#[instrument(skip(state, jar, payload))]
+pub async fn login_totp(
+    State(state): State<AppState>,
+    jar: PrivateCookieJar,
+    client: ClientInfo,
+    ApiJson(payload): ApiJson<TotpCodePayload>,
+) -> ApiResult {
+    let Some(pending_token) = jar.get("pending_token").map(|c| c.value().to_string()) else {
+        fail!(401, "No pending login");
+    };
+    let session = state
+        .database_accessor
+        .get_pending_session(&pending_token)
+        .await?;
+    if session.is_none() {
+        fail!(401, "Pending login expired");
     }
-    let token = user.unwrap();
+    let session = session.unwrap();
     let user = state
         .database_accessor
-        .get_user_by_id(&token.user_id)
+        .get_user_by_id(&session.user_id)
         .await?;
     if user.is_none() {
         fail!(401, "Unauthorized");
     }
-    let api_user = ApiUser::from_user(user.unwrap(), &state.database_accessor).await?;
-    success!(api_user)
-}
+    let user = user.unwrap();
+    let limit_keys = LimitKey::Account(&user.email).with_ip(client.ip_address.as_deref());
+    if let Err(wait) = state.login_limiter.check(&limit_keys) {
+        fail!(
+            429,
+            "Too many failed attempts, try again in {} seconds",
+            wait
+        );
+    }
+    if !verify_second_factor(&state, &user, &payload.code).await? {
+        state.login_limiter.record_failure(&limit_keys);
+        fail!(401, "Invalid verification code");
+    }
+    state.login_limiter.record_success(&limit_keys);
 
-#[instrument(skip(state, jar))]
-pub async fn get_users(State(state): State<AppState>, jar: PrivateCookieJar) -> ApiResult {
-    let token = jar.get("token").map(|c| c.value().to_string());
-    if token.is_none() {
-        fail!(401, "Unauthorized");
-    }
-    let token = state.user_tokens.get(&token.unwrap());
-    if token.is_none() {
-        fail!(401, "Unauthorized");
-    }
-    let current_user = state
+    state
         .database_accessor
-        .get_user_by_id(&token.unwrap().user_id)
+        .remove_session(&pending_token)
         .await?;
-    if current_user.is_none() {
-        fail!(401, "Unauthorized");
+    let jar = jar.remove(Cookie::build("pending_token").path("/api/login"));
+    let jar = start_session(&state, jar, &user.id, session.remember, &client).await?;
+    let api_user = ApiUser::from_user(user, &state).await?;
+    success!(api_user, jar)
+}
+
+pub async fn logout(
+    State(state): State<AppState>,
+    jar: PrivateCookieJar,
+    client: ClientInfo,
+) -> ApiResult {
+    // 模拟登录期间退出时只结束模拟，回到管理员自己的会话
+    if let Some(jar) = impersonation::end_impersonation(&state, jar.clone(), &client).await? {
+        success!((), jar)
     }
-    let current_user = current_user.unwrap();
-    if !current_user.descriptor.contains(UserPermission::Manage) {
-        fail!(403, "Forbidden");
+
+    // 在数据库中移除对应的会话（如果有）
+    if let Some(token) = jar.get("token") {
+        state
+            .database_accessor
+            .remove_session(token.value())
+            .await?;
     }
+
+    // 移除用户的 token cookie
+    let jar = jar.remove("token");
+    success!((), jar)
+}
+
+#[instrument(skip(state))]
+pub async fn user_info(State(state): State<AppState>, current: CurrentUser) -> ApiResult {
+    let impersonation = match current.session() {
+        Some(session) if session.impersonator_id.is_some() => {
+            Some(ApiImpersonation::from_session(&state, session).await?)
+        }
+        _ => None,
+    };
+    let user = ApiUser::from_user(current.user, &state).await?;
+    success!(ApiUserInfo {
+        user,
+        impersonation
+    })
+}
+
+#[instrument(skip(state, _manager))]
+pub async fn get_users(
+    State(state): State<AppState>,
+    _manager: RequirePermission<ManageUsers>,
+) -> ApiResult {
     let raw_users = state.database_accessor.get_all_users().await?;
     let mut users = Vec::with_capacity(raw_users.len());
     for user in raw_users {
-        users.push(ApiUser::from_user(user, &state.database_accessor).await?);
+        users.push(ApiUser::from_user(user, &state).await?);
     }
     success!(ApiList {
         total: users.len() as i64,
@@ -119,102 +331,120 @@ pub async fn get_users(State(state): State<AppState>, jar: PrivateCookieJar) ->
     })
 }
 
-#[instrument(skip(state, jar))]
+// 删除用户，并删除、转交或停用其项目
+async fn delete_user(
+    state: &AppState,
+    user: &User,
+    disposal: ItemDisposal,
+    reassign_to: Option<&str>,
+) -> Result<(), ApiError> {
+    let da = &state.database_accessor;
+    if user.owner && da.count_owners().await? <= 1 {
+        fail!(409, "Cannot remove the last owner");
+    }
+    let files = da.remove_user(&user.id, disposal, reassign_to).await?;
+    for file in files {
+        state.file_accessor.remove_file(&file).await?;
+    }
+    info!("Removed user {}, items: {:?}", user.id, disposal);
+    Ok(())
+}
+
+// 管理员删除用户，用户删除自己的账户需要使用 delete_account
+#[instrument(skip(state, manager))]
 pub async fn remove_user(
     State(state): State<AppState>,
-    jar: PrivateCookieJar,
-    Path(id): Path<String>,
+    manager: RequirePermission<ManageUsers>,
+    ApiPath(id): ApiPath<String>,
+    ApiQuery(query): ApiQuery<ApiUserRemoval>,
 ) -> ApiResult {
-    let token = jar.get("token").map(|c| c.value().to_string());
-    if token.is_none() {
-        fail!(401, "Unauthorized");
-    }
-    let token = state.user_tokens.get(&token.unwrap());
-    if token.is_none() {
-        fail!(401, "Unauthorized");
-    }
-    let current_user = state
-        .database_accessor
-        .get_user_by_id(&token.unwrap().user_id)
-        .await?;
-    if current_user.is_none() {
-        fail!(401, "Unauthorized");
-    }
-    let current_user = current_user.unwrap();
-    if !current_user.descriptor.contains(UserPermission::Manage) && current_user.id != id {
-        fail!(403, "No sufficient permissions");
-    }
-    if id == "00000000-0000-0000-0000-000000000000" {
-        // 不可以删除管理用户
-        fail!(403, "Cannot remove root user");
-    }
     let user = state.database_accessor.get_user_by_id(&id).await?;
     if user.is_none() {
         fail!(404, "User not found");
     }
     let user = user.unwrap();
-    state.database_accessor.remove_user(&user.id).await?;
-    let api_user = ApiUser::from_user(user, &state.database_accessor).await?;
+    // 所有者只能由所有者删除
+    if user.owner && !manager.user.owner {
+        fail!(403, "Only owners can remove an owner");
+    }
+    manager.check_manageable(&state, &user).await?;
+    let reassign_to = match query.items {
+        ItemDisposal::Reassign => {
+            let Some(to) = query.reassign_to else {
+                fail!(400, "No user to reassign items to");
+            };
+            if to == id {
+                fail!(400, "Cannot reassign items to the removed user");
+            }
+            if state.database_accessor.get_user_by_id(&to).await?.is_none() {
+                fail!(404, "User to reassign items to not found");
+            }
+            Some(to)
+        }
+        _ => None,
+    };
+    delete_user(&state, &user, query.items, reassign_to.as_deref()).await?;
+    let api_user = ApiUser::from_user(user, &state).await?;
     success!(api_user)
 }
 
-#[instrument(skip(state, jar))]
-pub async fn get_user(
+// 用户删除自己的账户，本地账户需要密码，启用了两步验证时还需要验证码
+#[instrument(skip(state, current, jar, payload))]
+pub async fn delete_account(
     State(state): State<AppState>,
+    current: CurrentUser,
     jar: PrivateCookieJar,
-    Path(id): Path<String>,
+    ApiJson(payload): ApiJson<ApiAccountDeletion>,
 ) -> ApiResult {
-    let token = jar.get("token").map(|c| c.value().to_string());
-    if token.is_none() {
-        fail!(401, "Unauthorized");
-    }
-    let token = state.user_tokens.get(&token.unwrap());
-    if token.is_none() {
-        fail!(401, "Unauthorized");
+    current.deny_impersonation()?;
+    current.deny_api_token()?;
+    let user = current.user;
+    if payload.items == ItemDisposal::Reassign {
+        fail!(400, "Items can only be deleted or archived");
+    }
+    if user.auth_source == "local" {
+        let password = payload.password.as_deref().unwrap_or("");
+        let (matched, _) = crate::util::verify_password(password, &user.password).await?;
+        if !matched {
+            fail!(401, "Invalid password");
+        }
     }
-    let current_user = state
-        .database_accessor
-        .get_user_by_id(&token.unwrap().user_id)
-        .await?;
-    if current_user.is_none() {
-        fail!(401, "Unauthorized");
+    if user.totp_enabled {
+        let code = payload.code.as_deref().unwrap_or("");
+        if !verify_second_factor(&state, &user, code).await? {
+            fail!(401, "Invalid verification code");
+        }
     }
-    let current_user = current_user.unwrap();
-    if !current_user.descriptor.contains(UserPermission::Manage) && current_user.id != id {
+    delete_user(&state, &user, payload.items, None).await?;
+    let jar = jar.remove("token");
+    let api_user = ApiUser::from_user(user, &state).await?;
+    success!(api_user, jar)
+}
+
+#[instrument(skip(state, current))]
+pub async fn get_user(
+    State(state): State<AppState>,
+    current: CurrentUser,
+    ApiPath(id): ApiPath<String>,
+) -> ApiResult {
+    if !current.can_access(&id) {
         fail!(403, "No sufficient permissions");
     }
     let user = state.database_accessor.get_user_by_id(&id).await?;
     if user.is_none() {
         fail!(404, "User not found");
     }
-    let api_user = ApiUser::from_user(user.unwrap(), &state.database_accessor).await?;
+    let api_user = ApiUser::from_user(user.unwrap(), &state).await?;
     success!(api_user)
 }
 
+#[instrument(skip(state, manager, user))]
 pub async fn create_user(
     State(state): State<AppState>,
-    jar: PrivateCookieJar,
-    Json(user): Json<ApiUserCreate>,
+    manager: RequirePermission<ManageUsers>,
+    ApiJson(user): ApiJson<ApiUserCreate>,
 ) -> ApiResult {
-    let token = jar.get("token").map(|c| c.value().to_string());
-    if token.is_none() {
-        fail!(401, "Unauthorized");
-    }
-    let token = state.user_tokens.get(&token.unwrap());
-    if token.is_none() {
-        fail!(401, "Unauthorized");
-    }
-    let current_user = state
-        .database_accessor
-        .get_user_by_id(&token.unwrap().user_id)
-        .await?;
-    if current_user.is_none() {
-        fail!(401, "Unauthorized");
-    }
-    let current_user = current_user.unwrap();
-    if !current_user.descriptor.contains(UserPermission::Manage) {
-        fail!(403, "Forbidden");
-    }
+    manager.check_grantable(&user.descriptor)?;
     let descriptor_sum: i64 = user
         .descriptor
         .into_iter()
@@ -232,7 +462,7 @@ pub async fn create_user(
             user.avatar,
         )
         .await?;
-    let api_user = ApiUser::from_user(user, &state.database_accessor).await?;
+    let api_user = ApiUser::from_user(user, &state).await?;
     success!(api_user)
 }
 
@@ -244,37 +474,14 @@ pub struct UserUpdatePayload {
     password: Option<String>,
 }
 
-#[instrument(skip(state, jar))]
+#[instrument(skip(state, current))]
 pub async fn update_user(
     State(state): State<AppState>,
-    jar: PrivateCookieJar,
-    axum::extract::Path(id): axum::extract::Path<String>,
-    Json(payload): Json<UserUpdatePayload>,
+    current: CurrentUser,
+    ApiPath(id): ApiPath<String>,
+    ApiJson(payload): ApiJson<UserUpdatePayload>,
 ) -> ApiResult {
-    let token = jar.get("token").map(|c| c.value().to_string());
-    if token.is_none() {
-        crate::fail!(401, "Unauthorized");
-    }
-    let token = state.user_tokens.get(&token.unwrap());
-    if token.is_none() {
-        crate::fail!(401, "Unauthorized");
-    }
-    let current_user = state
-        .database_accessor
-        .get_user_by_id(&token.unwrap().user_id)
-        .await
-        .unwrap_or(None);
-    if current_user.is_none() {
-        crate::fail!(401, "Unauthorized");
-    }
-    let current_user = current_user.unwrap();
-
-    let root_id = "00000000-0000-0000-0000-000000000000";
-    if id == root_id && current_user.id != root_id {
-        crate::fail!(403, "Cannot modify root user");
-    }
-
-    if current_user.id != id && !current_user.descriptor.contains(UserPermission::Manage) {
+    if !current.can_access(&id) {
         crate::fail!(403, "No sufficient permissions");
     }
 
@@ -287,6 +494,19 @@ pub async fn update_user(
         crate::fail!(404, "Target user not found");
     }
     let target_user = target_user.unwrap();
+    if target_user.owner && target_user.id != current.user.id && !current.user.owner {
+        crate::fail!(403, "Only owners can modify an owner");
+    }
+    current.check_manageable(&state, &target_user).await?;
+    if payload.password.is_some()
+        || payload
+            .email
+            .as_ref()
+            .is_some_and(|x| *x != target_user.email)
+    {
+        current.deny_impersonation()?;
+        current.deny_api_token()?;
+    }
 
     let name = payload.name.unwrap_or(target_user.name);
     let email = payload.email.unwrap_or(target_user.email);
@@ -295,8 +515,15 @@ pub async fn update_user(
     } else {
         target_user.avatar
     };
+    if payload.password.is_some() && target_user.auth_source != "local" {
+        crate::fail!(
+            400,
+            "Cannot set a password for an externally managed account"
+        );
+    }
+    let password_changed = payload.password.is_some();
     let password_hash = if let Some(p) = payload.password {
-        format!("{:x}", Sha256::digest(p.as_bytes()))
+        crate::util::hash_password(&p).await?
     } else {
         target_user.password
     };
@@ -307,7 +534,19 @@ pub async fn update_user(
         .await
     {
         Ok(updated_user) => {
-            if let Ok(api_user) = ApiUser::from_user(updated_user, &state.database_accessor).await {
+            if password_changed {
+                // 修改密码后吊销该用户的其他会话，用户自己修改时保留当前会话
+                let current = current.session().filter(|x| x.user_id == id);
+                let revoked = state
+                    .database_accessor
+                    .remove_user_sessions(&id, current.map(|x| x.id.as_str()))
+                    .await?;
+                info!(
+                    "Revoked {} sessions of user {} after password change",
+                    revoked, id
+                );
+            }
+            if let Ok(api_user) = ApiUser::from_user(updated_user, &state).await {
                 crate::success!(api_user)
             } else {
                 crate::fail!(500, "Internal error converting user")
@@ -319,3 +558,270 @@ pub async fn update_user(
         }
     }
 }
+
+#[derive(serde::Deserialize, Debug)]
+pub struct OwnerUpdatePayload {
+    owner: bool,
+}
+
+// 只有 Manage 权限的用户可以成为所有者
+async fn get_owner_candidate(state: &AppState, id: &str) -> Result<User, ApiError> {
+    let Some(user) = state.database_accessor.get_user_by_id(id).await? else {
+        return Err(ApiError::new(404, "User not found".to_string()));
+    };
+    if !user.is_active() || !user.descriptor.contains(UserPermission::Manage) {
+        return Err(ApiError::new(
+            400,
+            "Only active administrators can become owners".to_string(),
+        ));
+    }
+    Ok(user)
+}
+
+// 授予或取消所有者身份，仅所有者可以操作
+#[instrument(skip(state, manager))]
+pub async fn set_owner(
+    State(state): State<AppState>,
+    manager: RequirePermission<Manage>,
+    ApiPath(id): ApiPath<String>,
+    ApiJson(payload): ApiJson<OwnerUpdatePayload>,
+) -> ApiResult {
+    if !manager.user.owner {
+        fail!(403, "Only owners can manage owners");
+    }
+    let da = &state.database_accessor;
+    if payload.owner {
+        get_owner_candidate(&state, &id).await?;
+        da.grant_owner(&id).await?;
+    } else {
+        let Some(user) = da.get_user_by_id(&id).await? else {
+            fail!(404, "User not found");
+        };
+        if user.owner && !da.revoke_owner(&id).await? {
+            fail!(409, "Cannot remove the last owner");
+        }
+    }
+    info!(
+        "User {} set owner of user {} to {}",
+        manager.user.id, id, payload.owner
+    );
+    let Some(user) = da.get_user_by_id(&id).await? else {
+        fail!(404, "User not found");
+    };
+    let api_user = ApiUser::from_user(user, &state).await?;
+    success!(api_user)
+}
+
+// 将自己的所有者身份转移给另一个管理员
+#[instrument(skip(state, manager))]
+pub async fn transfer_owner(
+    State(state): State<AppState>,
+    manager: RequirePermission<Manage>,
+    ApiPath(id): ApiPath<String>,
+) -> ApiResult {
+    if !manager.user.owner {
+        fail!(403, "Only owners can transfer ownership");
+    }
+    if id == manager.user.id {
+        fail!(400, "Cannot transfer ownership to yourself");
+    }
+    get_owner_candidate(&state, &id).await?;
+    let da = &state.database_accessor;
+    da.transfer_owner(&manager.user.id, &id).await?;
+    info!(
+        "User {} transferred ownership to user {}",
+        manager.user.id, id
+    );
+    let Some(user) = da.get_user_by_id(&id).await? else {
+        fail!(404, "User not found");
+    };
+    let api_user = ApiUser::from_user(user, &state).await?;
+    success!(api_user)
+}
+
+// 设置用户自己的配额，字段为空时使用默认配额，负数表示不限制
+#[instrument(skip(state, manager))]
+pub async fn set_user_quota(
+    State(state): State<AppState>,
+    manager: RequirePermission<ManageUsers>,
+    ApiPath(id): ApiPath<String>,
+    ApiJson(payload): ApiJson<QuotaConfig>,
+) -> ApiResult {
+    let da = &state.database_accessor;
+    let Some(user) = da.get_user_by_id(&id).await? else {
+        fail!(404, "User not found");
+    };
+    if user.owner && user.id != manager.user.id && !manager.user.owner {
+        fail!(403, "Only owners can modify an owner");
+    }
+    manager.check_manageable(&state, &user).await?;
+    let user = da.update_user_quota(&id, &payload).await?;
+    info!(
+        "User {} set quota of user {} to {:?}",
+        manager.user.id, id, payload
+    );
+    let api_user = ApiUser::from_user(user, &state).await?;
+    success!(api_user)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::StatusCode;
+
+    fn ok(result: Result<User, ApiError>) -> User {
+        match result {
+            Ok(user) => user,
+            Err(ApiError::Anyhow(e)) => panic!("unexpected error: {e}"),
+            Err(ApiError::Other((status, message))) => panic!("unexpected {status}: {message}"),
+        }
+    }
+
+    fn status(result: Result<User, ApiError>) -> StatusCode {
+        match result {
+            Err(ApiError::Other((status, _))) => status,
+            Err(ApiError::Anyhow(e)) => panic!("unexpected error: {e}"),
+            Ok(user) => panic!("unexpected user: {user:?}"),
+        }
+    }
+
+    #[tokio::test]
+    async fn provisions_and_updates_external_users() {
+        let da = DatabaseAccessor::temporary().await;
+        let user =
+            ok(
+                resolve_external_user(&da, "oidc", "sub-1", "Alice", "a@example.com", 2, true)
+                    .await,
+            );
+        assert_eq!(user.auth_source, "oidc");
+        assert_eq!(user.external_id.as_deref(), Some("sub-1"));
+        assert_eq!(user.descriptor, 2);
+
+        // 之后按外部 ID 找到同一个用户，并同步名称、邮箱和权限
+        let again =
+            ok(
+                resolve_external_user(&da, "oidc", "sub-1", "Alice B", "b@example.com", 6, false)
+                    .await,
+            );
+        assert_eq!(again.id, user.id);
+        assert_eq!(again.name, "Alice B");
+        assert_eq!(again.email, "b@example.com");
+        assert_eq!(again.descriptor, 6);
+    }
+
+    #[tokio::test]
+    async fn links_existing_user_by_email_when_allowed() {
+        let da = DatabaseAccessor::temporary().await;
+        let local = da
+            .create_user("local-1", "Bob", "bob@example.com", "password", 1, None)
+            .await
+            .unwrap();
+        let linked =
+            ok(
+                resolve_external_user(&da, "ldap", "uuid-1", "Bob", "bob@example.com", 4, true)
+                    .await,
+            );
+        assert_eq!(linked.id, local.id);
+        assert_eq!(linked.auth_source, "ldap");
+        assert_eq!(linked.external_id.as_deref(), Some("uuid-1"));
+        // 关联后权限由身份源决定
+        assert_eq!(linked.descriptor, 4);
+        let found = da
+            .get_user_by_external_id("ldap", "uuid-1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(found.id, local.id);
+    }
+
+    #[tokio::test]
+    async fn refuses_to_link_untrusted_email() {
+        let da = DatabaseAccessor::temporary().await;
+        da.create_user("local-1", "Bob", "bob@example.com", "password", 1, None)
+            .await
+            .unwrap();
+        let result =
+            resolve_external_user(&da, "oidc", "sub-1", "Bob", "bob@example.com", 4, false).await;
+        assert_eq!(status(result), StatusCode::CONFLICT);
+        let user = da.get_user_by_id("local-1").await.unwrap().unwrap();
+        assert_eq!(user.auth_source, "local");
+        assert_eq!(user.external_id, None);
+        assert_eq!(user.descriptor, 1);
+    }
+
+    #[tokio::test]
+    async fn refuses_to_relink_user_of_another_identity() {
+        let da = DatabaseAccessor::temporary().await;
+        ok(resolve_external_user(&da, "oidc", "sub-1", "Carol", "c@example.com", 2, true).await);
+        let result =
+            resolve_external_user(&da, "oidc", "sub-2", "Carol", "c@example.com", 2, true).await;
+        assert_eq!(status(result), StatusCode::CONFLICT);
+    }
+
+    async fn update(state: &AppState, current: CurrentUser, id: &str, password: &str) -> ApiResult {
+        update_user(
+            State(state.clone()),
+            current,
+            ApiPath(id.to_string()),
+            ApiJson(UserUpdatePayload {
+                name: None,
+                email: None,
+                avatar: None,
+                password: Some(password.to_string()),
+            }),
+        )
+        .await
+    }
+
+    fn response_status(result: ApiResult) -> StatusCode {
+        match result {
+            Ok(res) => res.status(),
+            Err(ApiError::Anyhow(e)) => panic!("unexpected error: {e}"),
+            Err(ApiError::Other((status, _))) => status,
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_to_manage_user_with_group_permissions() {
+        let state = AppState::temporary().await;
+        let da = &state.database_accessor;
+        let manage_users = UserPermission::ManageUsers.as_i64();
+        da.create_user(
+            "mu",
+            "Mallory",
+            "mu@example.com",
+            "password",
+            manage_users,
+            None,
+        )
+        .await
+        .unwrap();
+        da.create_user("boss", "Boss", "boss@example.com", "password", 0, None)
+            .await
+            .unwrap();
+        da.create_user("carol", "Carol", "carol@example.com", "password", 0, None)
+            .await
+            .unwrap();
+        // boss 自身没有权限，Manage 来自所在的组
+        da.create_group("Admins", UserPermission::Manage.as_i64(), "boss")
+            .await
+            .unwrap();
+
+        let current = CurrentUser::temporary(&state, "mu").await;
+        assert_eq!(
+            response_status(update(&state, current, "boss", "new-password").await),
+            StatusCode::FORBIDDEN
+        );
+        let boss = da.get_user_by_id("boss").await.unwrap().unwrap();
+        let (matched, _) = crate::util::verify_password("password", &boss.password)
+            .await
+            .unwrap();
+        assert!(matched);
+
+        let current = CurrentUser::temporary(&state, "mu").await;
+        assert_eq!(
+            response_status(update(&state, current, "carol", "new-password").await),
+            StatusCode::OK
+        );
+    }
+}
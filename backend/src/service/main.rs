@@ -1,7 +1,8 @@
 use crate::data::DatabaseAccessor;
+use crate::service::api::auth::{ItemPasswordCheck, check_item_password};
 use crate::types::AppState;
 use axum::body::Body;
-use axum::extract::FromRequestParts;
+use axum::extract::{ConnectInfo, FromRequestParts};
 use axum::http::request::Parts;
 use axum::http::{StatusCode, header};
 use axum::response::IntoResponse;
@@ -14,7 +15,6 @@ use chrono::Local;
 use futures_util::stream::StreamExt;
 use http_body_util::BodyExt;
 use serde::Deserialize;
-use sha2::{Digest, Sha256};
 use std::net::SocketAddr;
 use tokio::io::AsyncSeekExt;
 use tokio_util::io::ReaderStream;
@@ -78,9 +78,10 @@ async fn log_access(
             success,
             &parts
                 .extensions
-                .get::<SocketAddr>()
-                .cloned()
-                .map_or("unknown".to_string(), |addr| addr.ip().to_string()),
+                .get::<ConnectInfo<SocketAddr>>()
+                .map_or("unknown".to_string(), |ConnectInfo(addr)| {
+                    addr.ip().to_string()
+                }),
             None,
         )
         .await;
@@ -129,10 +130,20 @@ pub async fn main_service(
         .await
         .unwrap();
     if item.is_none() {
-        debug!("Request to {} routed to frontend", request.uri().path());
-
         let path = request.uri().path().trim_start_matches('/');
 
+        // 项目改名后原路径上保留的跳转
+        if let Ok(Some(new_path)) = state.database_accessor.get_item_redirect(path).await {
+            debug!("Request to {} redirected to /{}", path, new_path);
+            let location = match request.uri().query() {
+                Some(query) => format!("/{}?{}", new_path, query),
+                None => format!("/{}", new_path),
+            };
+            return (StatusCode::FOUND, [(header::LOCATION, location)]).into_response();
+        }
+
+        debug!("Request to {} routed to frontend", request.uri().path());
+
         #[cfg(not(debug_assertions))]
         {
             // 防止直接访问一些不应该直接访问的前端路由
@@ -163,6 +174,10 @@ pub async fn main_service(
     debug!("Item {} queried from the database: {:?}", item.id, item);
     let item_id_clone = item.id.clone();
     let da_clone = state.database_accessor.clone();
+    // 停用的项目（如被删除用户的存档项目）不再提供访问
+    if !item.available {
+        return resp_404(next).await;
+    }
     // 检查项目是否过期
     if item.expires_at.is_some() && item.expires_at.unwrap() < Local::now().naive_local()
         || item.max_visits.is_some() && item.visits >= item.max_visits.unwrap()
@@ -176,23 +191,19 @@ pub async fn main_service(
         return resp_404(next).await;
     }
     // 尝试获取项目的创建者信息
-    let (username, avatar_url) =
-        if item.creator.is_some() && !item.creator.clone().unwrap().starts_with("guest") {
-            if let Ok(Some(user)) = state
-                .database_accessor
-                .get_user_by_id(&*item.creator.unwrap())
-                .await
-            {
+    let (username, avatar_url) = match item.creator.as_deref() {
+        Some(creator) if !creator.starts_with("guest") => {
+            if let Ok(Some(user)) = state.database_accessor.get_user_by_id(creator).await {
                 (user.name, user.avatar)
             } else {
                 ("Guest".to_string(), None)
             }
-        } else {
-            ("Guest".to_string(), None)
-        };
+        }
+        _ => ("Guest".to_string(), None),
+    };
 
     // 将下面被解析参数消耗了的 request 恢复
-    let request = if let Some(password) = item.password_hash.clone() {
+    let request = if item.password_hash.is_some() {
         #[derive(Deserialize)]
         struct PasswordForm {
             password: Option<String>,
@@ -203,7 +214,7 @@ pub async fn main_service(
         let query = axum::extract::Query::<PasswordForm>::from_request_parts(&mut parts, &()).await;
         if let Ok(form) = query {
             let input_password = form.0.password;
-            if !input_password.is_some() {
+            let Some(input_password) = input_password else {
                 // 密码未提供
                 debug!("No password provided for a protected item {}", item.id);
 
@@ -217,14 +228,30 @@ pub async fn main_service(
                             path_name: item.short_path,
                             creator_name: username,
                             creator_avatar: avatar_url,
+                            retry_after: None,
                         })
                         .unwrap_or("{}".to_string()),
                     )),
                 )
                 .await;
-            } else if format!("{:x}", Sha256::digest(input_password.unwrap())) != password {
-                // 如果上面跳到了这里的 else，说明密码已提供，但不正确
+            };
+            let ip = parts
+                .extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip().to_string());
+            let check = check_item_password(&state, &item, &input_password, ip.as_deref())
+                .await
+                .unwrap_or_else(|e| {
+                    error!("Failed to check password for item {}: {}", item.id, e);
+                    ItemPasswordCheck::Mismatched
+                });
+            if !matches!(check, ItemPasswordCheck::Matched) {
+                // 密码已提供，但不正确，或者失败次数过多被暂时锁定
                 debug!("Incorrect password provided for item {}", item.id,);
+                let retry_after = match check {
+                    ItemPasswordCheck::Limited(wait) => Some(wait),
+                    _ => None,
+                };
                 log_access(da_clone, item_id_clone, (parts, body), false).await;
                 return to_frontend(
                     next,
@@ -236,6 +263,7 @@ pub async fn main_service(
                             path_name: item.short_path,
                             creator_name: username,
                             creator_avatar: avatar_url,
+                            retry_after,
                         })
                         .unwrap_or("{}".to_string()),
                     )),
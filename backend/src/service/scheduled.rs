@@ -1,13 +1,20 @@
-use crate::types::Token;
-use dashmap::DashMap;
+use crate::data::DatabaseAccessor;
+use crate::limiter::LoginLimiter;
 use std::sync::Arc;
-use tracing::{debug, info, instrument};
+use tracing::{debug, error, info, instrument};
 
-// 清除过期的令牌，每30分钟运行一次
+// 清除内存中已经失效的登录失败记录，和过期会话的清理一起运行
+pub fn prune_login_attempts(limiter: Arc<LoginLimiter>) {
+    let count = limiter.prune();
+    debug!("{} stale login attempt record(s) removed", count);
+}
+
+// 清除数据库中过期的会话，每30分钟运行一次
 #[instrument]
-pub async fn clear_expired_token(map: Arc<DashMap<String, Token>>) -> () {
-    info!("Clearing expired tokens...");
-    let now = chrono::Local::now().naive_local();
-    map.retain(|_, v| v.expires_at > now);
-    debug!("{} token(s) remaining", map.len());
+pub async fn prune_expired_sessions(da: DatabaseAccessor) {
+    info!("Pruning expired sessions...");
+    match da.prune_sessions().await {
+        Ok(count) => debug!("{} expired session(s) removed", count),
+        Err(e) => error!("Failed to prune expired sessions: {}", e),
+    }
 }
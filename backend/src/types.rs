@@ -1,9 +1,9 @@
 use axum::extract::FromRef;
 use axum_extra::extract::cookie::Key;
 use chrono::Local;
-use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
 use sqlx::types::chrono::NaiveDateTime;
+use std::collections::HashMap;
 use std::sync::Arc;
 use strum::IntoEnumIterator;
 use strum_macros::{Display, EnumIter};
@@ -15,6 +15,76 @@ pub struct TurnstileConfig {
     pub secret_key: String,
 }
 
+// 发送邮件（如找回密码）使用的 SMTP 配置，保存在 sys_config 中
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SmtpConfig {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    pub password: String,
+    // 发件人，如 "Spectra <noreply@example.com>"
+    pub from: String,
+    // 连接加密方式：none / starttls / tls
+    pub security: String,
+}
+
+// 开放注册的配置，保存在 sys_config 中
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct RegistrationConfig {
+    pub enabled: bool,
+    // 开启时新注册的用户需要管理员审核后才能登录
+    pub approval: bool,
+    // 允许注册的邮箱域名，为空时不限制
+    pub domains: Vec<String>,
+    // 开放注册的用户拥有的权限
+    pub descriptor: i64,
+}
+
+impl RegistrationConfig {
+    pub fn allows_email(&self, email: &str) -> bool {
+        if self.domains.is_empty() {
+            return true;
+        }
+        let Some((_, domain)) = email.rsplit_once('@') else {
+            return false;
+        };
+        self.domains.iter().any(|x| x.eq_ignore_ascii_case(domain))
+    }
+}
+
+// 用户配额，字段为空时不限制。默认配额保存在 sys_config 中，用户自己的配额保存在 users 表中
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuotaConfig {
+    // 所有项目占用的总字节数
+    pub storage: Option<i64>,
+    // 有效项目的数量
+    pub items: Option<i64>,
+    // 单个文件的最大字节数
+    pub file_size: Option<i64>,
+    // 项目的最长有效期（秒），设置后不能创建永不过期的项目
+    pub expiry: Option<i64>,
+}
+
+impl QuotaConfig {
+    // 用户自己的配额覆盖默认配额，负数表示不限制
+    pub fn for_user(&self, user: &User) -> Self {
+        fn pick(own: Option<i64>, default: Option<i64>) -> Option<i64> {
+            match own {
+                Some(x) if x < 0 => None,
+                Some(x) => Some(x),
+                None => default,
+            }
+        }
+        Self {
+            storage: pick(user.quota_storage, self.storage),
+            items: pick(user.quota_items, self.items),
+            file_size: pick(user.quota_file_size, self.file_size),
+            expiry: pick(user.quota_expiry, self.expiry),
+        }
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct AppRuntimeConfig {
     pub setup: bool,
@@ -22,6 +92,98 @@ pub struct AppRuntimeConfig {
     pub refresh_time: String,
     pub domain: String,
     pub turnstile: TurnstileConfig,
+    pub smtp: SmtpConfig,
+    pub registration: RegistrationConfig,
+    pub quota: QuotaConfig,
+    pub max_revisions: i64, // 代码项目最多保留的历史版本数
+}
+
+// OpenID Connect 单点登录配置，对应配置文件中的 [oidc] 段，未配置时不启用
+#[derive(Clone, Debug, Deserialize)]
+pub struct OidcConfig {
+    pub issuer: String,
+    pub client_id: String,
+    #[serde(default)]
+    pub client_secret: Option<String>,
+    // 缺省为 {domain}/api/auth/oidc/callback
+    #[serde(default)]
+    pub redirect_uri: Option<String>,
+    #[serde(default = "OidcConfig::default_scopes")]
+    pub scopes: Vec<String>,
+    // 身份提供方返回用户组的声明名称
+    #[serde(default = "OidcConfig::default_groups_claim")]
+    pub groups_claim: String,
+    // 所有通过 OIDC 登录的用户都拥有的权限
+    #[serde(default)]
+    pub default_permissions: Vec<UserPermission>,
+    // 用户组到权限的映射，用户拥有其所在的所有组的权限之和
+    #[serde(default)]
+    pub group_permissions: HashMap<String, Vec<UserPermission>>,
+}
+
+impl OidcConfig {
+    fn default_scopes() -> Vec<String> {
+        vec![
+            "openid".to_string(),
+            "email".to_string(),
+            "profile".to_string(),
+        ]
+    }
+
+    fn default_groups_claim() -> String {
+        "groups".to_string()
+    }
+}
+
+// LDAP / AD 认证配置，对应配置文件中的 [ldap] 段，未配置时不启用
+// 登录时先用 bind_dn 搜索用户条目，再用条目的 DN 和用户输入的密码绑定
+#[derive(Clone, Debug, Deserialize)]
+pub struct LdapConfig {
+    pub url: String,
+    #[serde(default)]
+    pub starttls: bool,
+    // 用于搜索的账号，缺省时匿名搜索
+    #[serde(default)]
+    pub bind_dn: Option<String>,
+    #[serde(default)]
+    pub bind_password: Option<String>,
+    pub base_dn: String,
+    // {login} 会被替换为转义后的登录名
+    #[serde(default = "LdapConfig::default_user_filter")]
+    pub user_filter: String,
+    #[serde(default = "LdapConfig::default_email_attribute")]
+    pub email_attribute: String,
+    #[serde(default = "LdapConfig::default_name_attribute")]
+    pub name_attribute: String,
+    #[serde(default = "LdapConfig::default_group_attribute")]
+    pub group_attribute: String,
+    // 条目的邮箱与已有的本地用户相同时关联到该用户。目录中的邮箱不一定经过验证，所以默认不关联
+    #[serde(default)]
+    pub link_existing_users: bool,
+    // 所有通过 LDAP 登录的用户都拥有的权限
+    #[serde(default)]
+    pub default_permissions: Vec<UserPermission>,
+    // 组 DN 到权限的映射（不区分大小写），用户拥有其所在的所有组的权限之和
+    #[serde(default)]
+    pub group_permissions: HashMap<String, Vec<UserPermission>>,
+}
+
+impl LdapConfig {
+    fn default_user_filter() -> String {
+        "(|(mail={login})(uid={login}))".to_string()
+    }
+
+    fn default_email_attribute() -> String {
+        "mail".to_string()
+    }
+
+    fn default_name_attribute() -> String {
+        "cn".to_string()
+    }
+
+    fn default_group_attribute() -> String {
+        "memberOf".to_string()
+    }
 }
 
 // 应用状态
@@ -29,11 +191,51 @@ pub struct AppRuntimeConfig {
 pub struct AppState {
     pub database_accessor: crate::data::DatabaseAccessor,
     pub file_accessor: crate::data::FileAccessor,
-    pub user_tokens: Arc<DashMap<String, Token>>,
     pub runtime_config: Arc<arc_swap::ArcSwap<AppRuntimeConfig>>,
     pub cookie_key: Arc<arc_swap::ArcSwap<Key>>,
     pub cron_scheduler: tokio_cron_scheduler::JobScheduler,
     pub cron_job_id: Arc<arc_swap::ArcSwap<Option<uuid::Uuid>>>,
+    pub oidc: Option<Arc<OidcConfig>>,
+    pub ldap: Option<Arc<LdapConfig>>,
+    pub login_limiter: Arc<crate::limiter::LoginLimiter>,
+    // 初始设置使用的一次性代码，每次启动时重新生成，调用设置接口时需要提供
+    pub setup_code: Arc<String>,
+}
+
+#[cfg(test)]
+impl AppState {
+    // 测试用的应用状态，使用临时的数据库与数据目录，已完成初始设置，没有配置 OIDC 与 LDAP
+    pub async fn temporary() -> Self {
+        let data_dir = std::env::temp_dir().join(format!("spectra-test-{}", uuid::Uuid::now_v7()));
+        std::fs::create_dir_all(&data_dir).unwrap();
+        let runtime_config = AppRuntimeConfig {
+            setup: true,
+            cookie_key: String::new(),
+            refresh_time: "0 0 * * * *".to_string(),
+            domain: "http://127.0.0.1:3000".to_string(),
+            turnstile: TurnstileConfig {
+                enabled: false,
+                site_key: String::new(),
+                secret_key: String::new(),
+            },
+            smtp: SmtpConfig::default(),
+            registration: RegistrationConfig::default(),
+            quota: QuotaConfig::default(),
+            max_revisions: 20,
+        };
+        Self {
+            database_accessor: crate::data::DatabaseAccessor::temporary().await,
+            file_accessor: crate::data::FileAccessor::new(data_dir.display().to_string()),
+            runtime_config: Arc::new(arc_swap::ArcSwap::from_pointee(runtime_config)),
+            cookie_key: Arc::new(arc_swap::ArcSwap::from_pointee(Key::generate())),
+            cron_scheduler: tokio_cron_scheduler::JobScheduler::new().await.unwrap(),
+            cron_job_id: Arc::new(arc_swap::ArcSwap::from_pointee(None)),
+            oidc: None,
+            ldap: None,
+            login_limiter: Arc::new(crate::limiter::LoginLimiter::new(Default::default())),
+            setup_code: Arc::new(crate::util::random_string(16, None)),
+        }
+    }
 }
 
 // this impl tells `PrivateCookieJar` how to access the key from our state
@@ -59,40 +261,163 @@ pub struct PasswordInformation {
     pub path_name: String,
     pub creator_name: String,
     pub creator_avatar: Option<String>,
+    // 失败次数过多被暂时锁定时，需要等待的秒数
+    pub retry_after: Option<u64>,
 }
 
-// 用户令牌，令牌的实际表示由 State 中的哈希表键名决定
-#[derive(Debug, Clone)]
-pub struct Token {
+// 登录会话，持久化在数据库的 sessions 表中
+// 令牌本身只以 SHA-256 摘要的形式保存，明文令牌仅存在于客户端的 cookie 中
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Session {
+    pub id: String,
+    pub token_hash: String,
     pub user_id: String,
-    pub expires_at: NaiveDateTime,
     pub temporary: bool, // 临时令牌：用于游客通过 Turnstile 正确验证后上传文件的操作
+    pub remember: bool,  // 用户登录时勾选了“记住我”
+    pub created_at: NaiveDateTime,
+    pub last_used_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub pending: bool, // 已通过密码验证，等待两步验证的会话
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub impersonator_id: Option<String>, // 管理员模拟登录时创建的会话，记录发起模拟的管理员
 }
 
-impl Token {
-    pub fn new(user_id: String, temporary: bool) -> Self {
-        let expires_at = Local::now().naive_local() + chrono::Duration::minutes(10);
-        Self {
-            user_id,
-            expires_at,
-            temporary,
+// 发起请求的客户端信息，创建会话时记录下来
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl Session {
+    // 等待两步验证的会话的有效时长，不会续期
+    pub fn pending_lifetime() -> chrono::Duration {
+        chrono::Duration::minutes(5)
+    }
+
+    // 会话的有效时长。除临时令牌外，会话在每次使用时都会按此时长续期
+    pub fn lifetime(temporary: bool, remember: bool) -> chrono::Duration {
+        if temporary {
+            chrono::Duration::minutes(10)
+        } else if remember {
+            chrono::Duration::days(30)
+        } else {
+            chrono::Duration::days(1)
         }
     }
 
+    // 模拟登录会话的有效时长，不会续期
+    pub fn impersonation_lifetime() -> chrono::Duration {
+        chrono::Duration::hours(1)
+    }
+
     pub fn is_expired(&self) -> bool {
         self.expires_at < Local::now().naive_local()
     }
 }
 
+// 用户组，组的权限会授予所有成员
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Group {
+    pub id: String,
+    pub name: String,
+    pub descriptor: i64,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct GroupMember {
+    pub group_id: String,
+    pub user_id: String,
+    pub role: String, // owner 可以管理组和成员，member 只能使用组的项目
+    pub created_at: NaiveDateTime,
+}
+
+impl GroupMember {
+    pub const OWNER: &str = "owner";
+    pub const MEMBER: &str = "member";
+
+    pub fn is_owner(&self) -> bool {
+        self.role == Self::OWNER
+    }
+}
+
+// 邀请链接，受邀者可以用邀请码自行注册，注册后拥有邀请中预设的权限
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Invite {
+    pub id: String,
+    pub code_hash: String,
+    pub created_by: String,
+    pub descriptor: i64,
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+    pub max_uses: Option<i64>,
+    pub uses: i64,
+}
+
+impl Invite {
+    pub fn is_usable(&self) -> bool {
+        self.expires_at
+            .is_none_or(|x| x > Local::now().naive_local())
+            && self.max_uses.is_none_or(|x| self.uses < x)
+    }
+}
+
+// 找回密码的重置令牌
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct PasswordReset {
+    pub id: String,
+    pub user_id: String,
+    pub token_hash: String,
+    pub created_at: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+    pub used_at: Option<NaiveDateTime>,
+}
+
+impl PasswordReset {
+    pub fn lifetime() -> chrono::Duration {
+        chrono::Duration::hours(1)
+    }
+}
+
+// 个人 API 令牌，通过 Authorization: Bearer 头使用
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ApiToken {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub token_hash: String,
+    pub descriptor: i64, // 令牌可用的权限，实际生效的是它与用户权限的交集
+    pub created_at: NaiveDateTime,
+    pub expires_at: Option<NaiveDateTime>,
+    pub last_used_at: Option<NaiveDateTime>,
+}
+
+impl ApiToken {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .is_some_and(|expires_at| expires_at < Local::now().naive_local())
+    }
+}
+
 // 用户权限，以一个数字存储。
 // 下面枚举后注释中的数字是代表该权限的二进制位（从低到高）
 // 如 3 -> 0100 （从低到高第三位）
+// Manage 是完整的管理员权限，包含其他所有权限；新的权限只能追加在后面，保证已有的数值含义不变
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, EnumIter)]
 pub enum UserPermission {
-    Manage, // 1
-    Link,   // 2
-    Code,   // 3
-    File,   // 4
+    Manage,       // 1
+    Link,         // 2
+    Code,         // 3
+    File,         // 4
+    CustomPath,   // 5，在自定义的短路径上创建项目
+    ItemPassword, // 6，为项目设置密码
+    NoExpiry,     // 7，创建没有过期时间的项目
+    ViewLogs,     // 8，查看项目的访问日志
+    ManageUsers,  // 9，管理用户、邀请与会话
+    ManageConfig, // 10，修改实例配置
+    ViewAllItems, // 11，查看所有用户的项目
 }
 
 impl UserPermission {
@@ -103,6 +428,13 @@ impl UserPermission {
             Self::Link => 0b0010,
             Self::Code => 0b0100,
             Self::File => 0b1000,
+            Self::CustomPath => 0b1_0000,
+            Self::ItemPassword => 0b10_0000,
+            Self::NoExpiry => 0b100_0000,
+            Self::ViewLogs => 0b1000_0000,
+            Self::ManageUsers => 0b1_0000_0000,
+            Self::ManageConfig => 0b10_0000_0000,
+            Self::ViewAllItems => 0b100_0000_0000,
         }
     }
 
@@ -110,6 +442,12 @@ impl UserPermission {
         self.clone().into_i64()
     }
 
+    pub fn to_descriptor<'a>(permissions: impl IntoIterator<Item = &'a Self>) -> i64 {
+        permissions
+            .into_iter()
+            .fold(0, |descriptor, permission| descriptor | permission.as_i64())
+    }
+
     pub fn from_i64(i: i64) -> Vec<Self> {
         let mut result = Vec::new();
         for permission in Self::iter() {
@@ -126,11 +464,11 @@ pub trait ToPermission {
 }
 impl ToPermission for i64 {
     fn contains(self, permission: UserPermission) -> bool {
-        self & permission.into_i64() != 0
+        self & (permission.into_i64() | UserPermission::Manage.into_i64()) != 0
     }
 }
 
-#[derive(Debug, sqlx::FromRow, Serialize, Deserialize)]
+#[derive(sqlx::FromRow, Serialize, Deserialize)]
 pub struct User {
     pub id: String,
     pub name: String,
@@ -139,6 +477,51 @@ pub struct User {
     pub avatar: Option<String>,
     pub created_at: NaiveDateTime,
     pub descriptor: i64,
+    pub totp_secret: Option<String>, // Base32 编码的 TOTP 密钥，启用前也会保存以便确认
+    pub totp_enabled: bool,
+    pub auth_source: String, // 账户来源，local 或外部身份源的名称
+    pub external_id: Option<String>,
+    pub status: String, // active，或 pending（开放注册后等待管理员审核）
+    pub owner: bool,    // 所有者可以管理其他管理员，并转移所有权
+    // 用户自己的配额，为空时使用默认配额，负数表示不限制
+    pub quota_storage: Option<i64>,
+    pub quota_items: Option<i64>,
+    pub quota_file_size: Option<i64>,
+    pub quota_expiry: Option<i64>,
+}
+
+impl User {
+    pub fn is_active(&self) -> bool {
+        self.status == "active"
+    }
+}
+
+// 密码哈希和 TOTP 密钥不能出现在日志中
+impl std::fmt::Debug for User {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("User")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .field("email", &self.email)
+            .field("password", &"<redacted>")
+            .field("avatar", &self.avatar)
+            .field("created_at", &self.created_at)
+            .field("descriptor", &self.descriptor)
+            .field(
+                "totp_secret",
+                &self.totp_secret.as_ref().map(|_| "<redacted>"),
+            )
+            .field("totp_enabled", &self.totp_enabled)
+            .field("auth_source", &self.auth_source)
+            .field("external_id", &self.external_id)
+            .field("status", &self.status)
+            .field("owner", &self.owner)
+            .field("quota_storage", &self.quota_storage)
+            .field("quota_items", &self.quota_items)
+            .field("quota_file_size", &self.quota_file_size)
+            .field("quota_expiry", &self.quota_expiry)
+            .finish()
+    }
 }
 
 // 项目类型枚举
@@ -151,6 +534,16 @@ pub enum ItemType {
     File,
 }
 
+// 删除用户时对其项目的处理方式
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemDisposal {
+    Delete,   // 删除项目及其文件
+    Reassign, // 转交给另一个用户
+    #[default]
+    Archive, // 保留项目但停用，之后不会被自动清理
+}
+
 impl From<String> for ItemType {
     fn from(s: String) -> Self {
         match s.as_str() {
@@ -197,6 +590,122 @@ pub struct Item {
     pub available: bool,
     pub should_drop_at: Option<NaiveDateTime>,
     pub img: bool,
+    pub group_id: Option<String>, // 项目所属的组，组内成员都可以管理
+    pub size: Option<i64>,        // 占用的存储空间（字节），为空时尚未统计
+    pub manage_key_hash: Option<String>, // 游客项目的管理密钥
+    pub revision: i64,            // 代码项目当前内容的版本号
+    pub max_revisions: Option<i64>, // 最多保留的历史版本数，为空时使用全站设置
+    pub updated_at: Option<NaiveDateTime>,
+    pub updated_by: Option<String>,
+}
+
+// 项目列表的排序字段
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ItemSort {
+    #[default]
+    Created,
+    Visits,
+    Expires, // 没有过期时间的项目视为最晚过期
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    Asc,
+    #[default]
+    Desc,
+}
+
+// 项目列表的筛选条件，为空的条件不参与筛选
+#[derive(Clone, Debug, Default)]
+pub struct ItemFilter {
+    pub creator: Option<String>,
+    pub group_id: Option<String>,
+    pub item_type: Option<ItemType>,
+    pub available: Option<bool>,
+    pub password: Option<bool>, // 是否设置了访问密码
+    pub expiry: Option<bool>,   // 是否设置了过期时间
+    pub img: Option<bool>,
+    pub tag: Option<String>, // 带有该名称标签的项目
+    pub created_after: Option<NaiveDateTime>,
+    pub created_before: Option<NaiveDateTime>,
+    pub expires_after: Option<NaiveDateTime>,
+    pub expires_before: Option<NaiveDateTime>,
+    pub sort: ItemSort,
+    pub order: SortOrder,
+}
+
+// 游标分页的位置：上一页最后一个项目的排序字段和 ID
+// 以 (排序字段, ID) 作为键继续查询，翻页期间新增或删除项目不会导致重复或遗漏
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ItemCursor {
+    pub sort: ItemSort,
+    pub order: SortOrder,
+    pub created_at: NaiveDateTime,
+    pub visits: i64,
+    pub expires_at: Option<NaiveDateTime>,
+    pub id: String,
+}
+
+impl ItemCursor {
+    pub fn after(item: &Item, filter: &ItemFilter) -> Self {
+        Self {
+            sort: filter.sort,
+            order: filter.order,
+            created_at: item.created_at,
+            visits: item.visits,
+            expires_at: item.expires_at,
+            id: item.id.clone(),
+        }
+    }
+
+    pub fn encode(&self) -> String {
+        use base64::Engine;
+        let json = serde_json::to_vec(self).unwrap_or_default();
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(json)
+    }
+
+    pub fn decode(cursor: &str) -> Option<Self> {
+        use base64::Engine;
+        let json = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(cursor)
+            .ok()?;
+        serde_json::from_slice(&json).ok()
+    }
+}
+
+// 代码项目的历史版本，data 是保存该版本内容的文件
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct ItemRevision {
+    pub id: String,
+    pub item_id: String,
+    pub revision: i64,
+    pub data: String,
+    pub size: i64,
+    pub created_at: NaiveDateTime,
+    pub created_by: Option<String>,
+}
+
+// 项目标签，属于项目创建者
+#[derive(Debug, Clone, sqlx::FromRow)]
+pub struct Tag {
+    pub id: String,
+    pub user_id: String,
+    pub name: String,
+    pub created_at: NaiveDateTime,
+}
+
+// 审计日志，记录管理员模拟其他用户期间的操作
+#[derive(Debug, sqlx::FromRow)]
+pub struct AuditLog {
+    pub id: String,
+    pub actor_id: String, // 实际执行操作的管理员
+    pub user_id: String,  // 被模拟的用户
+    pub action: String,
+    pub detail: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: NaiveDateTime,
 }
 
 // 访问日志结构
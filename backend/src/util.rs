@@ -1,4 +1,12 @@
+use argon2::password_hash::rand_core::OsRng;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use argon2::{Algorithm, Argon2, Params, Version};
 use rand::Rng;
+use sha2::{Digest, Sha256};
+use std::sync::OnceLock;
+
+// 密码哈希使用的 Argon2id 参数，在启动时根据配置文件设置
+static ARGON2_PARAMS: OnceLock<Params> = OnceLock::new();
 
 pub fn random_string(length: usize, charset: Option<&str>) -> String {
     let charset = charset
@@ -15,6 +23,11 @@ pub fn random_password() -> String {
     random_string(16, None)
 }
 
+// 比较两个密钥是否相同，耗时与内容无关，避免通过响应时间逐位猜测
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
 pub async fn check_turnstile(
     secret_token: &str,
     token: &str,
@@ -36,3 +49,69 @@ pub async fn check_turnstile(
             .collect(),
     ))
 }
+
+pub fn init_password_hasher(
+    memory_cost: u32,
+    time_cost: u32,
+    parallelism: u32,
+) -> anyhow::Result<()> {
+    let params = Params::new(memory_cost, time_cost, parallelism, None)
+        .map_err(|e| anyhow::anyhow!("Invalid Argon2 parameters: {}", e))?;
+    let _ = ARGON2_PARAMS.set(params);
+    Ok(())
+}
+
+fn argon2() -> Argon2<'static> {
+    let params = ARGON2_PARAMS.get().cloned().unwrap_or_default();
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+}
+
+// 生成 PHC 格式的 Argon2id 哈希，计算量较大，所以放到阻塞线程池里执行
+pub async fn hash_password(password: &str) -> anyhow::Result<String> {
+    let password = password.to_string();
+    tokio::task::spawn_blocking(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        argon2()
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))
+    })
+    .await?
+}
+
+// 校验密码，返回 (是否匹配, 是否需要重新计算哈希)
+// 旧版本使用无盐的 SHA-256 十六进制摘要，匹配成功时要求调用方将其升级为 Argon2id；
+// Argon2id 哈希的参数和当前配置不一致时同样需要升级
+pub async fn verify_password(password: &str, hash: &str) -> anyhow::Result<(bool, bool)> {
+    // 外部身份源的账户没有可用的本地密码
+    if hash.starts_with('!') {
+        return Ok((false, false));
+    }
+    if !hash.starts_with('$') {
+        let legacy = format!("{:x}", Sha256::digest(password.as_bytes()));
+        let matched = legacy == hash;
+        return Ok((matched, matched));
+    }
+    let password = password.to_string();
+    let hash = hash.to_string();
+    tokio::task::spawn_blocking(move || {
+        let parsed = PasswordHash::new(&hash)
+            .map_err(|e| anyhow::anyhow!("Malformed password hash: {}", e))?;
+        let hasher = argon2();
+        if hasher
+            .verify_password(password.as_bytes(), &parsed)
+            .is_err()
+        {
+            return Ok((false, false));
+        }
+        let current = hasher.params();
+        let outdated = parsed.algorithm != argon2::ARGON2ID_IDENT
+            || Params::try_from(&parsed).is_ok_and(|p| {
+                p.m_cost() != current.m_cost()
+                    || p.t_cost() != current.t_cost()
+                    || p.p_cost() != current.p_cost()
+            });
+        Ok((true, outdated))
+    })
+    .await?
+}
@@ -0,0 +1,15 @@
+-- 登录会话表，令牌只保存 SHA-256 摘要
+CREATE TABLE IF NOT EXISTS sessions
+(
+    id           TEXT PRIMARY KEY NOT NULL,
+    token_hash   TEXT UNIQUE      NOT NULL,
+    user_id      TEXT             NOT NULL,
+    temporary    BOOLEAN          NOT NULL DEFAULT FALSE,
+    remember     BOOLEAN          NOT NULL DEFAULT FALSE,
+    created_at   DATETIME         NOT NULL,
+    last_used_at DATETIME         NOT NULL,
+    expires_at   DATETIME         NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_sessions_user_id ON sessions (user_id);
+CREATE INDEX IF NOT EXISTS idx_sessions_expires_at ON sessions (expires_at);
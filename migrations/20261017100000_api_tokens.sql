@@ -0,0 +1,15 @@
+-- 个人 API 令牌表，令牌只保存 SHA-256 摘要
+-- descriptor 是令牌可用权限的位掩码，实际生效的权限是它与所属用户权限的交集
+CREATE TABLE IF NOT EXISTS api_tokens
+(
+    id           TEXT PRIMARY KEY NOT NULL,
+    user_id      TEXT             NOT NULL,
+    name         TEXT             NOT NULL,
+    token_hash   TEXT UNIQUE      NOT NULL,
+    descriptor   INTEGER          NOT NULL DEFAULT 0,
+    created_at   DATETIME         NOT NULL,
+    expires_at   DATETIME,
+    last_used_at DATETIME
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_tokens_user_id ON api_tokens (user_id);
@@ -0,0 +1,19 @@
+ALTER TABLE users
+    ADD COLUMN totp_secret TEXT;
+ALTER TABLE users
+    ADD COLUMN totp_enabled BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- 一次性恢复码，只保存 SHA-256 摘要
+CREATE TABLE IF NOT EXISTS totp_recovery_codes
+(
+    id        TEXT PRIMARY KEY NOT NULL,
+    user_id   TEXT             NOT NULL,
+    code_hash TEXT             NOT NULL,
+    used_at   DATETIME
+);
+
+CREATE INDEX IF NOT EXISTS idx_totp_recovery_codes_user_id ON totp_recovery_codes (user_id);
+
+-- 等待第二步验证的会话，只能被 /api/login/totp 使用
+ALTER TABLE sessions
+    ADD COLUMN pending BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,8 @@
+-- 账户来源：local 为本地密码账户，其他值为外部身份源（如 oidc）
+-- external_id 是外部身份源中的唯一标识（如 OIDC 的 sub）
+ALTER TABLE users
+    ADD COLUMN auth_source TEXT NOT NULL DEFAULT 'local';
+ALTER TABLE users
+    ADD COLUMN external_id TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_users_external_id ON users (auth_source, external_id);
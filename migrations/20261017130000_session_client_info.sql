@@ -0,0 +1,3 @@
+-- 记录创建会话的客户端信息，便于用户识别自己的登录位置
+ALTER TABLE sessions ADD COLUMN ip_address TEXT;
+ALTER TABLE sessions ADD COLUMN user_agent TEXT;
@@ -0,0 +1,12 @@
+-- 找回密码的重置令牌，只保存 SHA-256 摘要，一次性使用
+CREATE TABLE IF NOT EXISTS password_resets
+(
+    id         TEXT PRIMARY KEY NOT NULL,
+    user_id    TEXT             NOT NULL,
+    token_hash TEXT UNIQUE      NOT NULL,
+    created_at DATETIME         NOT NULL,
+    expires_at DATETIME         NOT NULL,
+    used_at    DATETIME
+);
+
+CREATE INDEX IF NOT EXISTS idx_password_resets_user_id ON password_resets (user_id);
@@ -0,0 +1,15 @@
+-- 用户状态：active 正常，pending 等待管理员审核
+ALTER TABLE users ADD COLUMN status TEXT NOT NULL DEFAULT 'active';
+
+-- 邀请链接，邀请码只保存 SHA-256 摘要
+CREATE TABLE IF NOT EXISTS invites
+(
+    id         TEXT PRIMARY KEY NOT NULL,
+    code_hash  TEXT UNIQUE      NOT NULL,
+    created_by TEXT             NOT NULL,
+    descriptor INTEGER          NOT NULL,
+    created_at DATETIME         NOT NULL,
+    expires_at DATETIME,
+    max_uses   INTEGER,
+    uses       INTEGER          NOT NULL DEFAULT 0
+);
@@ -0,0 +1,8 @@
+-- 所有者：可以管理其他管理员并转移所有权，至少保留一个
+ALTER TABLE users ADD COLUMN owner BOOLEAN NOT NULL DEFAULT FALSE;
+
+-- 原先固定 ID 的管理用户成为所有者；没有时选择最早创建的管理员
+UPDATE users SET owner = TRUE WHERE id = '00000000-0000-0000-0000-000000000000';
+UPDATE users SET owner = TRUE
+WHERE id = (SELECT id FROM users WHERE descriptor & 1 != 0 ORDER BY created_at LIMIT 1)
+  AND NOT EXISTS (SELECT 1 FROM users WHERE owner = TRUE);
@@ -0,0 +1,7 @@
+-- 新增 CustomPath (16)、ItemPassword (32)、NoExpiry (64) 等权限位
+-- 之前拥有 Link/Code/File 之一的用户可以使用自定义路径、密码和永不过期，这里补上对应的权限以保持原有行为
+UPDATE users SET descriptor = descriptor | 112 WHERE descriptor & 14 != 0;
+UPDATE invites SET descriptor = descriptor | 112 WHERE descriptor & 14 != 0;
+UPDATE api_tokens SET descriptor = descriptor | 112 WHERE descriptor & 14 != 0;
+UPDATE sys_config SET value = CAST(CAST(value AS INTEGER) | 112 AS TEXT)
+WHERE key = 'registration_descriptor' AND CAST(value AS INTEGER) & 14 != 0;
@@ -0,0 +1,24 @@
+-- 用户组：组内成员共同拥有组的项目，组的权限会授予所有成员
+CREATE TABLE IF NOT EXISTS groups
+(
+    id         TEXT PRIMARY KEY NOT NULL,
+    name       TEXT             NOT NULL,
+    descriptor INTEGER          NOT NULL DEFAULT 0,
+    created_at DATETIME         NOT NULL
+);
+
+-- 组成员，role 为 owner（可以管理组和成员）或 member
+CREATE TABLE IF NOT EXISTS group_members
+(
+    group_id   TEXT     NOT NULL,
+    user_id    TEXT     NOT NULL,
+    role       TEXT     NOT NULL DEFAULT 'member',
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (group_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_group_members_user_id ON group_members (user_id);
+
+-- 项目所属的组，为空时只属于创建者
+ALTER TABLE items ADD COLUMN group_id TEXT;
+CREATE INDEX IF NOT EXISTS idx_items_group_id ON items (group_id);
@@ -0,0 +1,9 @@
+-- 用户自己的配额，为空时使用默认配额，负数表示不限制
+ALTER TABLE users ADD COLUMN quota_storage INTEGER;
+ALTER TABLE users ADD COLUMN quota_items INTEGER;
+ALTER TABLE users ADD COLUMN quota_file_size INTEGER;
+ALTER TABLE users ADD COLUMN quota_expiry INTEGER;
+
+-- 项目占用的存储空间（字节），已有文件的大小在启动时补全
+ALTER TABLE items ADD COLUMN size INTEGER;
+UPDATE items SET size = 0 WHERE item_type = 'link';
@@ -0,0 +1,3 @@
+-- 游客创建项目时得到的管理密钥（SHA-256），用于之后删除、修改或认领项目
+ALTER TABLE items ADD COLUMN manage_key_hash TEXT;
+CREATE INDEX IF NOT EXISTS idx_items_manage_key_hash ON items (manage_key_hash);
@@ -0,0 +1,17 @@
+-- 管理员以其他用户身份登录（模拟登录）时创建的会话，记录发起模拟的管理员
+ALTER TABLE sessions ADD COLUMN impersonator_id TEXT;
+
+-- 审计日志：记录模拟登录的开始、结束以及期间的所有修改操作
+CREATE TABLE IF NOT EXISTS audit_logs
+(
+    id         TEXT PRIMARY KEY NOT NULL,
+    actor_id   TEXT             NOT NULL,
+    user_id    TEXT             NOT NULL,
+    action     TEXT             NOT NULL,
+    detail     TEXT,
+    ip_address TEXT,
+    created_at DATETIME         NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_logs_created_at ON audit_logs (created_at);
+CREATE INDEX IF NOT EXISTS idx_audit_logs_actor_id ON audit_logs (actor_id);
@@ -0,0 +1,9 @@
+-- 项目改名后在原路径保留的跳转，访问原路径时跳转到项目的新路径
+CREATE TABLE IF NOT EXISTS item_redirects
+(
+    short_path TEXT PRIMARY KEY NOT NULL,
+    item_id    TEXT             NOT NULL,
+    created_at DATETIME         NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_item_redirects_item_id ON item_redirects (item_id);
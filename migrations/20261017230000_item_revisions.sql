@@ -0,0 +1,22 @@
+-- 代码项目的历史版本：修改内容时保留之前的文件，而不是覆盖
+ALTER TABLE items ADD COLUMN revision INTEGER NOT NULL DEFAULT 1;
+-- 单个项目最多保留的历史版本数，为空时使用全站设置
+ALTER TABLE items ADD COLUMN max_revisions INTEGER;
+-- 当前内容的写入时间和修改者，为空时即创建时间和创建者
+ALTER TABLE items ADD COLUMN updated_at DATETIME;
+ALTER TABLE items ADD COLUMN updated_by TEXT;
+
+CREATE TABLE IF NOT EXISTS item_revisions
+(
+    id         TEXT PRIMARY KEY NOT NULL,
+    item_id    TEXT             NOT NULL,
+    revision   INTEGER          NOT NULL,
+    data       TEXT             NOT NULL,
+    size       INTEGER          NOT NULL,
+    -- 这个版本的内容写入的时间和写入者
+    created_at DATETIME         NOT NULL,
+    created_by TEXT,
+    UNIQUE (item_id, revision)
+);
+
+CREATE INDEX IF NOT EXISTS idx_item_revisions_item_id ON item_revisions (item_id);
@@ -0,0 +1,10 @@
+-- 项目全文搜索索引，使用 trigram 分词以支持中文和任意子串搜索
+-- data 为链接的目标地址或代码的内容，extra_data 为文件名等附加数据
+CREATE VIRTUAL TABLE IF NOT EXISTS item_search USING fts5
+(
+    item_id UNINDEXED,
+    short_path,
+    data,
+    extra_data,
+    tokenize = 'trigram'
+);
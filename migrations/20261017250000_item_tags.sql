@@ -0,0 +1,18 @@
+-- 用户给项目添加的标签，每个用户的标签相互独立，名称不区分大小写
+CREATE TABLE IF NOT EXISTS tags
+(
+    id         TEXT PRIMARY KEY NOT NULL,
+    user_id    TEXT             NOT NULL,
+    name       TEXT             NOT NULL COLLATE NOCASE,
+    created_at DATETIME         NOT NULL,
+    UNIQUE (user_id, name)
+);
+
+CREATE TABLE IF NOT EXISTS item_tags
+(
+    item_id TEXT NOT NULL,
+    tag_id  TEXT NOT NULL,
+    PRIMARY KEY (item_id, tag_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_item_tags_tag_id ON item_tags (tag_id);
@@ -44,7 +44,11 @@ import {
 } from "@/components/ui/table"
 import { cn } from "@/lib/utils"
 import { wfetch } from "../../dashboard/fetch"
-import { AdminUserContext, type ApiUser } from "../context/AdminUserContext"
+import {
+    AdminUserContext,
+    type ApiUser,
+    hasPermission,
+} from "../context/AdminUserContext"
 
 const PAGE_SIZE = 25
 
@@ -71,7 +75,10 @@ type ApiResponse<T> = {
 export function SharedContent() {
     const { t } = useTranslation("admin")
     const { currentUser } = useContext(AdminUserContext)
-    const manage = !!currentUser?.descriptor.includes("Manage")
+    // 查看所有人的项目时需要用户列表来显示创建者
+    const manage =
+        hasPermission(currentUser, "ViewAllItems") &&
+        hasPermission(currentUser, "ManageUsers")
     const [items, setItems] = useState<SharedItem[]>([])
     const [loading, setLoading] = useState(false)
     const [total, setTotal] = useState(0)
@@ -2,7 +2,7 @@ import { useTranslation } from "react-i18next"
 import "../../components/i18n"
 import "../../public/style.css"
 import type { Dispatch, SetStateAction } from "react"
-import { useEffect, useMemo, useRef, useState } from "react"
+import { useContext, useEffect, useMemo, useRef, useState } from "react"
 import { toast } from "sonner"
 import { Avatar, AvatarFallback, AvatarImage } from "@/components/ui/avatar"
 import { Badge } from "@/components/ui/badge"
@@ -32,6 +32,14 @@ import {
     InputGroupInput,
 } from "@/components/ui/input-group"
 import { Label } from "@/components/ui/label"
+import {
+    Select,
+    SelectContent,
+    SelectGroup,
+    SelectItem,
+    SelectTrigger,
+    SelectValue,
+} from "@/components/ui/select"
 import { Separator } from "@/components/ui/separator"
 import {
     Table,
@@ -43,9 +51,30 @@ import {
 } from "@/components/ui/table"
 import { cn } from "@/lib/utils"
 import { wfetch } from "../../dashboard/fetch"
-
-const ROOT_USER_ID = "00000000-0000-0000-0000-000000000000"
-const USER_DESCRIPTORS = ["Link", "File", "Code", "Manage"] as const
+import { AdminUserContext } from "../context/AdminUserContext"
+
+const USER_DESCRIPTORS = [
+    "Link",
+    "File",
+    "Code",
+    "CustomPath",
+    "ItemPassword",
+    "NoExpiry",
+    "ViewLogs",
+    "ViewAllItems",
+    "ManageUsers",
+    "ManageConfig",
+    "Manage",
+] as const
+// 新用户默认拥有创建项目相关的权限
+const DEFAULT_DESCRIPTORS: readonly UserPermission[] = [
+    "Link",
+    "File",
+    "Code",
+    "CustomPath",
+    "ItemPassword",
+    "NoExpiry",
+]
 const ALLOWED_AVATAR_EXTENSIONS = new Set([
     "gif",
     "bmp",
@@ -59,6 +88,7 @@ type UserPermission = (typeof USER_DESCRIPTORS)[number]
 
 export function UserManagement() {
     const { t } = useTranslation("admin")
+    const { currentUser } = useContext(AdminUserContext)
     const [users, setUsers] = useState<ApiUser[]>([])
     const [loading, setLoading] = useState(false)
     const [search, setSearch] = useState("")
@@ -67,6 +97,7 @@ export function UserManagement() {
     const [createdCredential, setCreatedCredential] =
         useState<CreatedCredential | null>(null)
     const [deletingUserId, setDeletingUserId] = useState<string | null>(null)
+    const [updatingOwnerId, setUpdatingOwnerId] = useState<string | null>(null)
 
     useEffect(() => {
         void loadUsers()
@@ -108,20 +139,28 @@ export function UserManagement() {
         }
     }
 
-    async function handleDeleteUser(user: ApiUser) {
-        if (user.id === ROOT_USER_ID) {
+    async function handleDeleteUser(
+        user: ApiUser,
+        items: ItemDisposal,
+        reassignTo: string | null,
+    ) {
+        if (user.owner && users.filter((item) => item.owner).length <= 1) {
             toast.error(
-                t("user_management.delete_root_forbidden", {
-                    defaultValue: "Root user cannot be deleted",
+                t("user_management.delete_last_owner_forbidden", {
+                    defaultValue: "The last owner cannot be deleted",
                 }),
             )
-            return
+            return false
         }
 
         setDeletingUserId(user.id)
         try {
+            const query = new URLSearchParams({ items })
+            if (items === "reassign" && reassignTo) {
+                query.set("reassign_to", reassignTo)
+            }
             const resp = await wfetch(
-                `/api/user/${encodeURIComponent(user.id)}`,
+                `/api/user/${encodeURIComponent(user.id)}?${query}`,
                 {
                     method: "DELETE",
                 },
@@ -133,6 +172,7 @@ export function UserManagement() {
                     defaultValue: "User deleted",
                 }),
             )
+            return true
         } catch (error) {
             console.error(error)
             toast.error(
@@ -143,11 +183,70 @@ export function UserManagement() {
                     }),
                 ),
             )
+            return false
         } finally {
             setDeletingUserId(null)
         }
     }
 
+    async function handleToggleOwner(user: ApiUser) {
+        setUpdatingOwnerId(user.id)
+        try {
+            const resp = await wfetch(
+                `/api/user/${encodeURIComponent(user.id)}/owner`,
+                {
+                    method: "PUT",
+                    headers: {
+                        "Content-Type": "application/json",
+                    },
+                    body: JSON.stringify({ owner: !user.owner }),
+                },
+            )
+            const updated = await parseApiPayload<ApiUser>(resp)
+            handleUserUpdated(updated)
+            toast.success(
+                t("user_management.owner_update_success", {
+                    defaultValue: "Owner updated",
+                }),
+            )
+        } catch (error) {
+            console.error(error)
+            toast.error(
+                getErrorMessage(
+                    error,
+                    t("user_management.owner_update_failed", {
+                        defaultValue: "Failed to update owner",
+                    }),
+                ),
+            )
+        } finally {
+            setUpdatingOwnerId(null)
+        }
+    }
+
+    async function handleImpersonate(user: ApiUser) {
+        try {
+            const resp = await wfetch(
+                `/api/user/${encodeURIComponent(user.id)}/impersonate`,
+                {
+                    method: "POST",
+                },
+            )
+            await parseApiPayload<ApiUser>(resp)
+            location.href = "/"
+        } catch (error) {
+            console.error(error)
+            toast.error(
+                getErrorMessage(
+                    error,
+                    t("user_management.impersonate_failed", {
+                        defaultValue: "Failed to view as user",
+                    }),
+                ),
+            )
+        }
+    }
+
     function handleUserCreated(user: ApiUser, password: string) {
         setUsers((current) => [user, ...current])
         setCreatedCredential({
@@ -262,6 +361,16 @@ export function UserManagement() {
                                                 "flex flex-row flex-wrap gap-1"
                                             }
                                         >
+                                            {user.owner && (
+                                                <Badge
+                                                    variant="outline"
+                                                    className="bg-amber-50 text-amber-700 dark:bg-amber-950 dark:text-amber-300"
+                                                >
+                                                    {t(
+                                                        "user_management.owner",
+                                                    )}
+                                                </Badge>
+                                            )}
                                             {user.descriptor.map((d) => (
                                                 <Badge
                                                     key={d}
@@ -288,29 +397,74 @@ export function UserManagement() {
                                     </TableCell>
                                     <TableCell className="align-middle">
                                         <div className="flex flex-row items-center">
-                                            <button
-                                                type="button"
-                                                onClick={() => {
-                                                    void handleDeleteUser(user)
-                                                }}
-                                                disabled={deleting}
-                                                // 不知道为什么 disabled 对它不起作用，
-                                                // 用更显式的弹 toaster 的方式来提示
-
-                                                // disabled={user.id === ROOT_USER_ID}
-                                                className={cn(
-                                                    "button-icon",
-                                                    deleting && "opacity-50",
-                                                )}
-                                            >
-                                                <span className="material-symbols-outlined point cursor-pointer text-[1.4rem]!">
-                                                    delete
-                                                </span>
-                                            </button>
+                                            <DeleteUserDialog
+                                                user={user}
+                                                users={users}
+                                                deleting={deleting}
+                                                onConfirm={(items, reassignTo) =>
+                                                    handleDeleteUser(
+                                                        user,
+                                                        items,
+                                                        reassignTo,
+                                                    )
+                                                }
+                                            />
                                             <EditUserDialog
                                                 user={user}
                                                 onUpdated={handleUserUpdated}
                                             />
+                                            {currentUser?.owner &&
+                                                user.descriptor.includes(
+                                                    "Manage",
+                                                ) && (
+                                                    <button
+                                                        type="button"
+                                                        title={t(
+                                                            user.owner
+                                                                ? "user_management.revoke_owner"
+                                                                : "user_management.grant_owner",
+                                                        )}
+                                                        onClick={() => {
+                                                            void handleToggleOwner(
+                                                                user,
+                                                            )
+                                                        }}
+                                                        disabled={
+                                                            updatingOwnerId ===
+                                                            user.id
+                                                        }
+                                                        className="button-icon"
+                                                    >
+                                                        <span className="material-symbols-outlined point cursor-pointer text-[1.4rem]!">
+                                                            {user.owner
+                                                                ? "remove_moderator"
+                                                                : "add_moderator"}
+                                                        </span>
+                                                    </button>
+                                                )}
+                                            {currentUser?.descriptor.includes(
+                                                "Manage",
+                                            ) &&
+                                                user.id !== currentUser.id &&
+                                                (!user.owner ||
+                                                    currentUser.owner) && (
+                                                    <button
+                                                        type="button"
+                                                        title={t(
+                                                            "user_management.impersonate",
+                                                        )}
+                                                        onClick={() => {
+                                                            void handleImpersonate(
+                                                                user,
+                                                            )
+                                                        }}
+                                                        className="button-icon"
+                                                    >
+                                                        <span className="material-symbols-outlined point cursor-pointer text-[1.4rem]!">
+                                                            visibility
+                                                        </span>
+                                                    </button>
+                                                )}
                                         </div>
                                     </TableCell>
                                 </TableRow>
@@ -351,8 +505,11 @@ type ApiUser = {
     created_at: string
     descriptor: UserPermission[]
     item_count: number
+    owner: boolean
 }
 
+type ItemDisposal = "delete" | "reassign" | "archive"
+
 type ApiListPayload<T> = {
     total: number
     items: T[]
@@ -388,12 +545,7 @@ function NewUserDialog({
     const avatarInputRef = useRef<HTMLInputElement>(null)
     const [descriptors, setDescriptors] = useState<
         Record<UserPermission, boolean>
-    >({
-        Link: true,
-        File: true,
-        Code: true,
-        Manage: false,
-    })
+    >(defaultDescriptors)
 
     function resetForm() {
         setName("")
@@ -405,12 +557,7 @@ function NewUserDialog({
         if (avatarInputRef.current) {
             avatarInputRef.current.value = ""
         }
-        setDescriptors({
-            Link: true,
-            File: true,
-            Code: true,
-            Manage: false,
-        })
+        setDescriptors(defaultDescriptors())
     }
 
     function handleOpenChange(nextOpen: boolean) {
@@ -1037,6 +1184,177 @@ function EditUserDialog({
     )
 }
 
+// 删除用户前选择如何处理其项目：删除、转交给其他用户或停用存档
+function DeleteUserDialog({
+    user,
+    users,
+    deleting,
+    onConfirm,
+}: {
+    user: ApiUser
+    users: ApiUser[]
+    deleting: boolean
+    onConfirm: (
+        items: ItemDisposal,
+        reassignTo: string | null,
+    ) => Promise<boolean>
+}) {
+    const { t } = useTranslation("admin")
+    const [open, setOpen] = useState(false)
+    const [items, setItems] = useState<ItemDisposal>("archive")
+    const [reassignTo, setReassignTo] = useState<string | null>(null)
+    const candidates = users.filter((item) => item.id !== user.id)
+
+    function handleOpenChange(nextOpen: boolean) {
+        if (!nextOpen && !deleting) {
+            setItems("archive")
+            setReassignTo(null)
+        }
+        setOpen(nextOpen)
+    }
+
+    async function handleConfirm() {
+        if (items === "reassign" && !reassignTo) {
+            toast.error(
+                t("user_management.delete_user_dialog.reassign_required", {
+                    defaultValue: "Choose a user to receive the items",
+                }),
+            )
+            return
+        }
+        if (await onConfirm(items, reassignTo)) {
+            setOpen(false)
+        }
+    }
+
+    return (
+        <Dialog open={open} onOpenChange={handleOpenChange}>
+            <DialogTrigger asChild>
+                <button
+                    type="button"
+                    className={cn("button-icon", deleting && "opacity-50")}
+                    title={t("user_management.delete_user_dialog.open", {
+                        defaultValue: "Delete user",
+                    })}
+                >
+                    <span className="material-symbols-outlined point cursor-pointer text-[1.4rem]!">
+                        delete
+                    </span>
+                </button>
+            </DialogTrigger>
+            <DialogContent className="sm:max-w-sm">
+                <form
+                    onSubmit={(e) => {
+                        e.preventDefault()
+                        void handleConfirm()
+                    }}
+                >
+                    <DialogHeader>
+                        <DialogTitle>
+                            {t("user_management.delete_user_dialog.title")}
+                        </DialogTitle>
+                        <DialogDescription>
+                            {t(
+                                "user_management.delete_user_dialog.description",
+                                { name: user.name, count: user.item_count },
+                            )}
+                        </DialogDescription>
+                    </DialogHeader>
+
+                    <FieldGroup className="mt-4 mb-6 gap-4">
+                        <Field>
+                            <FieldLabel>
+                                {t(
+                                    "user_management.delete_user_dialog.items_label",
+                                )}
+                            </FieldLabel>
+                            <Select
+                                value={items}
+                                onValueChange={(value) => {
+                                    setItems(value as ItemDisposal)
+                                }}
+                            >
+                                <SelectTrigger className={"w-full"}>
+                                    <SelectValue />
+                                </SelectTrigger>
+                                <SelectContent>
+                                    <SelectGroup>
+                                        <SelectItem value="archive">
+                                            {t(
+                                                "user_management.delete_user_dialog.items_archive",
+                                            )}
+                                        </SelectItem>
+                                        <SelectItem value="reassign">
+                                            {t(
+                                                "user_management.delete_user_dialog.items_reassign",
+                                            )}
+                                        </SelectItem>
+                                        <SelectItem value="delete">
+                                            {t(
+                                                "user_management.delete_user_dialog.items_delete",
+                                            )}
+                                        </SelectItem>
+                                    </SelectGroup>
+                                </SelectContent>
+                            </Select>
+                        </Field>
+                        {items === "reassign" && (
+                            <Field>
+                                <FieldLabel>
+                                    {t(
+                                        "user_management.delete_user_dialog.reassign_to",
+                                    )}
+                                </FieldLabel>
+                                <Select
+                                    value={reassignTo ?? undefined}
+                                    onValueChange={setReassignTo}
+                                >
+                                    <SelectTrigger className={"w-full"}>
+                                        <SelectValue />
+                                    </SelectTrigger>
+                                    <SelectContent>
+                                        <SelectGroup>
+                                            {candidates.map((item) => (
+                                                <SelectItem
+                                                    value={item.id}
+                                                    key={item.id}
+                                                >
+                                                    {item.name} ({item.email})
+                                                </SelectItem>
+                                            ))}
+                                        </SelectGroup>
+                                    </SelectContent>
+                                </Select>
+                            </Field>
+                        )}
+                    </FieldGroup>
+
+                    <DialogFooter>
+                        <DialogClose asChild>
+                            <Button variant="outline" disabled={deleting}>
+                                {t("user_management.new_user_dialog.cancel")}
+                            </Button>
+                        </DialogClose>
+                        <Button
+                            type="submit"
+                            variant="destructive"
+                            disabled={deleting}
+                        >
+                            {t("user_management.delete_user_dialog.confirm")}
+                        </Button>
+                    </DialogFooter>
+                </form>
+            </DialogContent>
+        </Dialog>
+    )
+}
+
+function defaultDescriptors() {
+    return Object.fromEntries(
+        USER_DESCRIPTORS.map((d) => [d, DEFAULT_DESCRIPTORS.includes(d)]),
+    ) as Record<UserPermission, boolean>
+}
+
 function DescriptorSelection({
     descriptors,
     setDescriptors,
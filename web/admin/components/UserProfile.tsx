@@ -3,6 +3,7 @@ import { useTranslation } from "react-i18next"
 import { toast } from "sonner"
 import { Avatar, AvatarFallback, AvatarImage } from "@/components/ui/avatar"
 import { Button } from "@/components/ui/button"
+import { Checkbox } from "@/components/ui/checkbox"
 import {
     Field,
     FieldDescription,
@@ -92,6 +93,10 @@ export function UserProfile() {
     const [avatarFile, setAvatarFile] = useState<File | null>(null)
     const [removeAvatar, setRemoveAvatar] = useState(false)
     const avatarInputRef = useRef<HTMLInputElement>(null)
+    const [deletePassword, setDeletePassword] = useState("")
+    const [deleteCode, setDeleteCode] = useState("")
+    const [deleteItems, setDeleteItems] = useState(true)
+    const [deleting, setDeleting] = useState(false)
 
     useEffect(() => {
         if (currentUser) {
@@ -206,6 +211,52 @@ export function UserProfile() {
         }
     }
 
+    // 删除自己的账户，项目可以一并删除或停用保留
+    async function handleDeleteAccount() {
+        if (deleting || !currentUser) {
+            return
+        }
+        if (
+            !window.confirm(
+                t("user_profile.delete_confirm", {
+                    defaultValue:
+                        "Delete your account? This cannot be undone.",
+                }),
+            )
+        ) {
+            return
+        }
+
+        setDeleting(true)
+        try {
+            const resp = await wfetch("/api/account", {
+                method: "DELETE",
+                headers: {
+                    "Content-Type": "application/json",
+                },
+                body: JSON.stringify({
+                    password: deletePassword || null,
+                    code: deleteCode.trim() || null,
+                    items: deleteItems ? "delete" : "archive",
+                }),
+            })
+            await parseApiPayload<ApiUser>(resp)
+            localStorage.removeItem("user")
+            location.href = "/"
+        } catch (error) {
+            console.error(error)
+            toast.error(
+                error instanceof Error
+                    ? error.message
+                    : t("user_profile.delete_failed", {
+                          defaultValue: "Failed to delete account",
+                      }),
+            )
+        } finally {
+            setDeleting(false)
+        }
+    }
+
     if (!currentUser) {
         return (
             <div className="ml-8 w-full px-4 mt-4">
@@ -416,6 +467,101 @@ export function UserProfile() {
                     </Button>
                 </div>
             </form>
+
+            <div className="border-b my-8" />
+
+            <form
+                onSubmit={(e) => {
+                    e.preventDefault()
+                    void handleDeleteAccount()
+                }}
+                className="space-y-4"
+            >
+                <div>
+                    <div className="text-lg font-semibold mb-2">
+                        {t("user_profile.delete_title", {
+                            defaultValue: "Delete Account",
+                        })}
+                    </div>
+                    <div className="text-sm text-muted-foreground">
+                        {t("user_profile.delete_description", {
+                            defaultValue:
+                                "Permanently delete your account and sign out.",
+                        })}
+                    </div>
+                </div>
+                <FieldGroup className="gap-4">
+                    {currentUser.auth_source === "local" && (
+                        <Field>
+                            <FieldLabel htmlFor="delete_password">
+                                {t("user_profile.delete_password", {
+                                    defaultValue: "Current password",
+                                })}
+                            </FieldLabel>
+                            <Input
+                                id="delete_password"
+                                type="password"
+                                value={deletePassword}
+                                onInput={(e) =>
+                                    setDeletePassword(
+                                        (e.target as HTMLInputElement).value,
+                                    )
+                                }
+                            />
+                        </Field>
+                    )}
+                    {currentUser.totp_enabled && (
+                        <Field>
+                            <FieldLabel htmlFor="delete_code">
+                                {t("user_profile.delete_code", {
+                                    defaultValue:
+                                        "Verification code or recovery code",
+                                })}
+                            </FieldLabel>
+                            <Input
+                                id="delete_code"
+                                value={deleteCode}
+                                autoComplete="one-time-code"
+                                onInput={(e) =>
+                                    setDeleteCode(
+                                        (e.target as HTMLInputElement).value,
+                                    )
+                                }
+                            />
+                        </Field>
+                    )}
+                    <Field orientation="horizontal">
+                        <Checkbox
+                            id="delete_items"
+                            checked={deleteItems}
+                            onCheckedChange={(checked) =>
+                                setDeleteItems(!!checked)
+                            }
+                        />
+                        <FieldLabel htmlFor="delete_items">
+                            {t("user_profile.delete_items", {
+                                defaultValue:
+                                    "Also delete my shared items and files",
+                            })}
+                        </FieldLabel>
+                    </Field>
+                    <FieldDescription>
+                        {t("user_profile.delete_items_description", {
+                            defaultValue:
+                                "Otherwise your items are disabled but kept for the administrators.",
+                        })}
+                    </FieldDescription>
+                </FieldGroup>
+                <Button
+                    type="submit"
+                    variant="destructive"
+                    disabled={deleting}
+                >
+                    {t("user_profile.delete_button", {
+                        defaultValue: "Delete account",
+                    })}
+                </Button>
+            </form>
         </div>
     )
 }
@@ -1,12 +1,40 @@
 import { createContext } from "react"
 
+export type UserPermission =
+    | "Manage"
+    | "Link"
+    | "Code"
+    | "File"
+    | "CustomPath"
+    | "ItemPassword"
+    | "NoExpiry"
+    | "ViewLogs"
+    | "ManageUsers"
+    | "ManageConfig"
+    | "ViewAllItems"
+
 export type ApiUser = {
     id: string
     name: string
     email: string
     avatar: string | null
     created_at: string
-    descriptor: ("Manage" | "Code" | "Link" | "File")[]
+    descriptor: UserPermission[]
+    owner: boolean
+    totp_enabled: boolean
+    auth_source: string
+}
+
+// Manage 包含其他所有权限
+export function hasPermission(
+    user: ApiUser | null | undefined,
+    permission: UserPermission,
+) {
+    return (
+        !!user &&
+        (user.descriptor.includes("Manage") ||
+            user.descriptor.includes(permission))
+    )
 }
 
 export const AdminUserContext = createContext<{
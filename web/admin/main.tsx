@@ -20,13 +20,18 @@ import {
     TopBarLogo,
     TopBarRightBackButton,
 } from "../components/TopBar"
+import { csrfHeaders } from "../components/csrf"
 import { DebugInfo } from "./components/DebugInfo"
 import { Overview } from "./components/Overview"
 import { Settings } from "./components/Settings"
 import { SharedContent } from "./components/SharedContent"
 import { UserManagement } from "./components/UserManagement"
 import { UserProfile } from "./components/UserProfile"
-import { AdminUserContext, type ApiUser } from "./context/AdminUserContext"
+import {
+    AdminUserContext,
+    type ApiUser,
+    hasPermission,
+} from "./context/AdminUserContext"
 
 const root = document.getElementById("app")
 if (!root) throw new Error("Launch failed: Root element not found")
@@ -57,9 +62,9 @@ function Admin() {
                         body: JSON.stringify(user),
                         method: "POST",
                         credentials: "include",
-                        headers: {
+                        headers: csrfHeaders({
                             "Content-Type": "application/json",
-                        },
+                        }),
                     })
                     return login()
                 } else {
@@ -75,7 +80,11 @@ function Admin() {
                     return
                 }
                 setCurrentUser(data?.payload ?? null)
-                if (!data?.payload.descriptor.includes("Manage")) {
+                if (
+                    !hasPermission(data?.payload, "ManageUsers") &&
+                    !hasPermission(data?.payload, "ManageConfig") &&
+                    !hasPermission(data?.payload, "ViewAllItems")
+                ) {
                     toast.info(t("authorization.no_manage_permission"))
                 }
             }
@@ -140,7 +149,9 @@ function Admin() {
 
 function Content({ t }: { t: ReturnType<typeof useTranslation>["t"] }) {
     const { currentUser } = useContext(AdminUserContext)
-    const manage = !!currentUser?.descriptor.includes("Manage")
+    const manage = hasPermission(currentUser, "Manage")
+    const manageUsers = hasPermission(currentUser, "ManageUsers")
+    const manageConfig = hasPermission(currentUser, "ManageConfig")
     const [activeTab, setActiveTab] = useState("overview")
 
     return (
@@ -181,47 +192,49 @@ function Content({ t }: { t: ReturnType<typeof useTranslation>["t"] }) {
                     </Button>
                 </div>
 
-                {manage && (
-                    <>
-                        <div className="flex items-center">
-                            <Button
-                                variant={
-                                    activeTab === "user_management"
-                                        ? "secondary"
-                                        : "ghost"
-                                }
-                                onClick={() => setActiveTab("user_management")}
-                            >
-                                {t("button_user_management")}
-                            </Button>
-                        </div>
+                {manageUsers && (
+                    <div className="flex items-center">
+                        <Button
+                            variant={
+                                activeTab === "user_management"
+                                    ? "secondary"
+                                    : "ghost"
+                            }
+                            onClick={() => setActiveTab("user_management")}
+                        >
+                            {t("button_user_management")}
+                        </Button>
+                    </div>
+                )}
 
-                        <div className="flex items-center">
-                            <Button
-                                variant={
-                                    activeTab === "system_settings"
-                                        ? "secondary"
-                                        : "ghost"
-                                }
-                                onClick={() => setActiveTab("system_settings")}
-                            >
-                                {t("button_system_settings")}
-                            </Button>
-                        </div>
+                {manageConfig && (
+                    <div className="flex items-center">
+                        <Button
+                            variant={
+                                activeTab === "system_settings"
+                                    ? "secondary"
+                                    : "ghost"
+                            }
+                            onClick={() => setActiveTab("system_settings")}
+                        >
+                            {t("button_system_settings")}
+                        </Button>
+                    </div>
+                )}
 
-                        <div className="flex items-center">
-                            <Button
-                                variant={
-                                    activeTab === "debug_info"
-                                        ? "secondary"
-                                        : "ghost"
-                                }
-                                onClick={() => setActiveTab("debug_info")}
-                            >
-                                {t("button_debug_info")}
-                            </Button>
-                        </div>
-                    </>
+                {manage && (
+                    <div className="flex items-center">
+                        <Button
+                            variant={
+                                activeTab === "debug_info"
+                                    ? "secondary"
+                                    : "ghost"
+                            }
+                            onClick={() => setActiveTab("debug_info")}
+                        >
+                            {t("button_debug_info")}
+                        </Button>
+                    </div>
                 )}
             </div>
 
@@ -0,0 +1,16 @@
+// 服务器通过 csrf_token cookie 下发令牌，修改数据的请求需要在 X-CSRF-Token 头中带上它
+export const CSRF_HEADER = "X-CSRF-Token"
+
+export function csrfToken(): string {
+    const entry = document.cookie
+        .split("; ")
+        .find((x) => x.startsWith("csrf_token="))
+    return entry ? decodeURIComponent(entry.slice("csrf_token=".length)) : ""
+}
+
+export function csrfHeaders(headers?: HeadersInit): Headers {
+    const result = new Headers(headers)
+    const token = csrfToken()
+    if (token) result.set(CSRF_HEADER, token)
+    return result
+}
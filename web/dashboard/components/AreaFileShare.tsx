@@ -17,7 +17,9 @@ import {
 } from "@/components/ui/select"
 import { Switch } from "@/components/ui/switch"
 import { cn } from "@/lib/utils.ts"
+import { csrfHeaders } from "../../components/csrf"
 import { wfetch } from "../fetch.ts"
+import { rememberGuestItem } from "../guestItems.ts"
 import { AccountCtx } from "../main.tsx"
 import { FinishedCard } from "./FinishedCard.tsx"
 import { Turnstile } from "./Turnstile.tsx"
@@ -149,6 +151,7 @@ export function AreaFileShare() {
             return
         }
         setFailedMessage("")
+        rememberGuestItem(data.payload)
 
         setProgress(10)
         const formData = new FormData()
@@ -193,6 +196,12 @@ export function AreaFileShare() {
                     `/api/file/${encodeURIComponent(data.payload.short_path)}`,
                 )
                 xhr.withCredentials = true // 包含凭证
+                csrfHeaders().forEach((value, key) => {
+                    xhr.setRequestHeader(key, value)
+                })
+                if (data.payload.manage_key) {
+                    xhr.setRequestHeader("X-Manage-Key", data.payload.manage_key)
+                }
                 xhr.send(formData)
             })
         } catch (e) {
@@ -516,6 +525,7 @@ export function AreaFileShare() {
                                             xhrStates.url.current,
                                             {
                                                 method: "DELETE",
+                                                headers: csrfHeaders(),
                                                 credentials: "include",
                                             },
                                         )
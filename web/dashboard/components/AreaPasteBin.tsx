@@ -16,6 +16,7 @@ import {
 import { Textarea } from "@/components/ui/textarea"
 import { LANGS } from "../../components/languages.ts"
 import { wfetch } from "../fetch.ts"
+import { rememberGuestItem } from "../guestItems.ts"
 import { AccountCtx } from "../main.tsx"
 import { FinishedCard } from "./FinishedCard.tsx"
 import { Turnstile } from "./Turnstile.tsx"
@@ -67,6 +68,7 @@ export function AreaPasteBin() {
         })
         const data = await resp.json()
         if (resp.status === 200 && data.success) {
+            rememberGuestItem(data.payload)
             setFailedMessage("")
             context.sharedListUpdTrigger(context.sharedListUpd + 1)
             const url = `${window.location.origin}/${data.payload.short_path}`
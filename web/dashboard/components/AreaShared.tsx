@@ -2,6 +2,7 @@ import { useContext, useEffect, useRef, useState } from "react"
 import { useTranslation } from "react-i18next"
 import { toast } from "sonner"
 import { TransitionHeight } from "../../components/HeightTransition"
+import { csrfHeaders } from "../../components/csrf"
 import { wfetch } from "../fetch"
 import { AccountCtx } from "../main"
 
@@ -15,6 +16,7 @@ type SharedItem = {
     created_at: string
     creator: string | null
     available: boolean
+    tags: string[]
 }
 
 export function AreaShared() {
@@ -29,16 +31,19 @@ export function AreaShared() {
             created_at: "1970-01-01T00:00:00.000Z",
             creator: null,
             available: true,
+            tags: [],
         },
     ])
+    const [tag, setTag] = useState<string | null>(null)
     const offset = useRef(0)
     const [ended, setEnded] = useState(false)
     const [nothing, setNothing] = useState(false)
 
     async function get_items(currentOffset: number) {
         try {
+            const tagQuery = tag ? `&tag=${encodeURIComponent(tag)}` : ""
             const resp = await wfetch(
-                `/api/items?offset=${currentOffset}&limit=${PAGE_SIZE}`,
+                `/api/items?offset=${currentOffset}&limit=${PAGE_SIZE}${tagQuery}`,
             )
             const data: {
                 success: boolean
@@ -52,6 +57,7 @@ export function AreaShared() {
                         created_at: string
                         creator: string | null
                         available: boolean
+                        tags: string[]
                     }[]
                 }
             } = await resp.json()
@@ -100,6 +106,7 @@ export function AreaShared() {
                     `/api/item/${path.replace(`${window.location.origin}/`, "")}`,
                     {
                         method: "DELETE",
+                        headers: csrfHeaders(),
                     },
                 )
                 const data = await resp.json()
@@ -116,11 +123,53 @@ export function AreaShared() {
         }
     }
 
+    function getOnClickEditTags(id: string, path: string, tags: string[]) {
+        return async () => {
+            const input = window.prompt(
+                t("shared.edit_tags_prompt"),
+                tags.join(", "),
+            )
+            if (input === null) return
+            const newTags = input
+                .split(",")
+                .map((x) => x.trim())
+                .filter((x) => x !== "")
+            try {
+                const resp = await fetch(
+                    `/api/item/${path.replace(`${window.location.origin}/`, "")}`,
+                    {
+                        method: "PATCH",
+                        headers: {
+                            ...csrfHeaders(),
+                            "Content-Type": "application/json",
+                        },
+                        body: JSON.stringify({ tags: newTags }),
+                    },
+                )
+                const data = await resp.json()
+                if (data.success) {
+                    setItems((prev) =>
+                        prev.map((item) =>
+                            item.id === id
+                                ? { ...item, tags: data.payload.tags }
+                                : item,
+                        ),
+                    )
+                } else {
+                    toast.error(t("shared.edit_tags_failed"))
+                }
+            } catch (e) {
+                console.error(e)
+                toast.error(t("shared.edit_tags_failed"))
+            }
+        }
+    }
+
     useEffect(() => {
         let delay = 0
         if (context.sharedListUpd !== 0) delay = 100
         setTimeout(() => get_items(0), delay)
-    }, [context.sharedListUpd, i18n.language])
+    }, [context.sharedListUpd, i18n.language, tag])
     return (
         <>
             <div className={"flex flex-col items-center"}>
@@ -128,6 +177,18 @@ export function AreaShared() {
                     {t("shared.title")}
                 </div>
 
+                {tag && (
+                    <div className={"mb-4 text-sm text-neutral-500"}>
+                        {t("shared.filtered_by_tag", { tag })}
+                        <span
+                            className={"ml-2 cursor-pointer hover:underline"}
+                            onClick={() => setTag(null)}
+                        >
+                            {t("shared.clear_filter")}
+                        </span>
+                    </div>
+                )}
+
                 <TransitionHeight>
                     <table className="w-200 text-sm text-left text-neutral-700 dark:text-neutral-200">
                         <thead className="text-sm uppercase border-b border-neutral-200 dark:border-neutral-700">
@@ -169,6 +230,27 @@ export function AreaShared() {
                                         >
                                             {item.short_path}
                                         </a>
+                                        {item.tags.length > 0 && (
+                                            <div
+                                                className={
+                                                    "flex flex-wrap gap-1 mt-1"
+                                                }
+                                            >
+                                                {item.tags.map((x) => (
+                                                    <span
+                                                        key={x}
+                                                        className={
+                                                            "px-1.5 rounded text-xs bg-neutral-200 dark:bg-neutral-700 cursor-pointer"
+                                                        }
+                                                        onClick={() =>
+                                                            setTag(x)
+                                                        }
+                                                    >
+                                                        {x}
+                                                    </span>
+                                                ))}
+                                            </div>
+                                        )}
                                     </td>
                                     <td className="px-4 py-2">
                                         {item.available
@@ -182,6 +264,24 @@ export function AreaShared() {
                                         ).toLocaleString()}
                                     </td>
                                     <td className="px-4 py-2">
+                                        <button
+                                            type="button"
+                                            onClick={getOnClickEditTags(
+                                                item.id,
+                                                item.short_path,
+                                                item.tags,
+                                            )}
+                                            class={"button-icon"}
+                                            title={t("shared.edit_tags")}
+                                        >
+                                            <span
+                                                className={
+                                                    "material-symbols-outlined point cursor-pointer text-[1.4rem]!"
+                                                }
+                                            >
+                                                sell
+                                            </span>
+                                        </button>
                                         <button
                                             type="button"
                                             onClick={getOnClickDelete(
@@ -20,6 +20,7 @@ import {
 } from "@/components/ui/select"
 import { cn } from "@/lib/utils.ts"
 import { wfetch } from "../fetch.ts"
+import { rememberGuestItem } from "../guestItems.ts"
 import { AccountCtx } from "../main.tsx"
 import { FinishedCard } from "./FinishedCard.tsx"
 import { Turnstile } from "./Turnstile.tsx"
@@ -91,6 +92,7 @@ export function AreaShortUrl() {
         })
         const data = await resp.json()
         if (resp.status === 200 && data.success) {
+            rememberGuestItem(data.payload)
             context.sharedListUpdTrigger(context.sharedListUpd + 1)
             const url = `${window.location.origin}/${data.payload.short_path}`
             setFinalUrl(url)
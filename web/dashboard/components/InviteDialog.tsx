@@ -0,0 +1,106 @@
+import { useRef, useState } from "react"
+import { useTranslation } from "react-i18next"
+import { toast } from "sonner"
+import { Button } from "@/components/ui/button"
+import {
+    Dialog,
+    DialogContent,
+    DialogDescription,
+    DialogFooter,
+    DialogHeader,
+    DialogTitle,
+} from "@/components/ui/dialog"
+import { Input } from "@/components/ui/input"
+import { csrfHeaders } from "../../components/csrf"
+
+// 用户从邀请链接进入时，链接中带有 invite 参数
+export function InviteDialog() {
+    const { t } = useTranslation("dashboard")
+    const url = new URL(window.location.href)
+    const code = url.searchParams.get("invite")
+    const [open, setOpen] = useState(!!code)
+    const [submitting, setSubmitting] = useState(false)
+    const name = useRef("")
+    const email = useRef("")
+    const password = useRef("")
+
+    function close() {
+        url.searchParams.delete("invite")
+        window.history.replaceState(null, "", url.href)
+        setOpen(false)
+    }
+
+    async function submit() {
+        if (!name.current || !email.current || !password.current) return
+        setSubmitting(true)
+        const resp = await fetch("/api/register", {
+            method: "POST",
+            credentials: "include",
+            headers: csrfHeaders({
+                "Content-Type": "application/json",
+            }),
+            body: JSON.stringify({
+                name: name.current,
+                email: email.current,
+                password: password.current,
+                invite: code,
+            }),
+        })
+        setSubmitting(false)
+        if (resp.ok) {
+            toast.success(t("invite.success"))
+            close()
+            window.location.reload()
+            return
+        }
+        if (resp.status === 409) {
+            toast.error(t("invite.email_exists"))
+        } else {
+            toast.error(t("invite.failed"))
+        }
+    }
+
+    if (!code) return null
+    return (
+        <Dialog open={open} onOpenChange={(o) => !o && close()}>
+            <DialogContent>
+                <DialogHeader>
+                    <DialogTitle>{t("invite.title")}</DialogTitle>
+                    <DialogDescription>
+                        {t("invite.description")}
+                    </DialogDescription>
+                </DialogHeader>
+                <div className={"flex flex-col gap-2"}>
+                    <Input
+                        placeholder={t("invite.name")}
+                        onInput={(e) => {
+                            name.current = (e.target as HTMLInputElement).value
+                        }}
+                    />
+                    <Input
+                        type={"email"}
+                        placeholder={t("invite.email")}
+                        onInput={(e) => {
+                            email.current = (e.target as HTMLInputElement).value
+                        }}
+                    />
+                    <Input
+                        type={"password"}
+                        autocomplete={"new-password"}
+                        placeholder={t("invite.password")}
+                        onInput={(e) => {
+                            password.current = (
+                                e.target as HTMLInputElement
+                            ).value
+                        }}
+                    />
+                </div>
+                <DialogFooter>
+                    <Button disabled={submitting} onClick={submit}>
+                        {t("invite.action")}
+                    </Button>
+                </DialogFooter>
+            </DialogContent>
+        </Dialog>
+    )
+}
@@ -0,0 +1,84 @@
+import { useRef, useState } from "react"
+import { useTranslation } from "react-i18next"
+import { toast } from "sonner"
+import { Button } from "@/components/ui/button"
+import {
+    Dialog,
+    DialogContent,
+    DialogDescription,
+    DialogHeader,
+    DialogTitle,
+} from "@/components/ui/dialog"
+import { Input } from "@/components/ui/input"
+import { csrfHeaders } from "../../components/csrf"
+
+// 用户从找回密码邮件中的链接进入时，链接中带有 reset_token 参数
+export function ResetPasswordDialog() {
+    const { t } = useTranslation("dashboard")
+    const url = new URL(window.location.href)
+    const token = url.searchParams.get("reset_token")
+    const [open, setOpen] = useState(!!token)
+    const [submitting, setSubmitting] = useState(false)
+    const password = useRef("")
+
+    function close() {
+        // 从地址栏中移除令牌，避免刷新后再次弹出
+        url.searchParams.delete("reset_token")
+        window.history.replaceState(null, "", url.href)
+        setOpen(false)
+    }
+
+    async function submit() {
+        if (!password.current) return
+        setSubmitting(true)
+        const resp = await fetch("/api/password/reset", {
+            method: "POST",
+            credentials: "include",
+            headers: csrfHeaders({
+                "Content-Type": "application/json",
+            }),
+            body: JSON.stringify({ token, password: password.current }),
+        })
+        setSubmitting(false)
+        if (resp.ok) {
+            toast.success(t("reset_password.success"))
+            close()
+        } else {
+            toast.error(t("reset_password.failed"))
+        }
+    }
+
+    if (!token) return null
+    return (
+        <Dialog open={open} onOpenChange={(o) => !o && close()}>
+            <DialogContent>
+                <DialogHeader>
+                    <DialogTitle>{t("reset_password.title")}</DialogTitle>
+                    <DialogDescription>
+                        {t("reset_password.description")}
+                    </DialogDescription>
+                </DialogHeader>
+                <div className={"flex gap-2"}>
+                    <Input
+                        type={"password"}
+                        autocomplete={"new-password"}
+                        onInput={(e) => {
+                            password.current = (
+                                e.target as HTMLInputElement
+                            ).value
+                        }}
+                        onKeyDown={(e) => {
+                            if (e.key === "Enter") {
+                                e.preventDefault()
+                                submit()
+                            }
+                        }}
+                    />
+                    <Button disabled={submitting} onClick={submit}>
+                        {t("reset_password.action")}
+                    </Button>
+                </div>
+            </DialogContent>
+        </Dialog>
+    )
+}
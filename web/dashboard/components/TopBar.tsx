@@ -14,6 +14,7 @@ import {
 import { cn } from "@/lib/utils.ts"
 import { LanguageSwitcher } from "../../components/LanguageSwitcher.tsx"
 import { TopBarDiv, TopBarLogo } from "../../components/TopBar.tsx"
+import { csrfHeaders } from "../../components/csrf"
 import { AccountCtx } from "../main.tsx"
 
 export function TopBar() {
@@ -23,6 +24,7 @@ export function TopBar() {
     const logout = async () => {
         const resp = await fetch("/api/logout", {
             method: "POST",
+            headers: csrfHeaders(),
         })
         const data = await resp.json()
         if (data.success) {
@@ -45,6 +47,23 @@ export function TopBar() {
         }
     }
 
+    const endImpersonation = async () => {
+        const resp = await fetch("/api/impersonation/end", {
+            method: "POST",
+            headers: csrfHeaders(),
+        })
+        const data = await resp.json()
+        if (data.success) {
+            location.href = "/admin/"
+        } else {
+            toast.error(
+                t("top_bar.end_impersonation_failed", {
+                    reason: `${resp.status} ${data?.payload || ""}`,
+                }),
+            )
+        }
+    }
+
     return (
         <TopBarDiv>
             <TopBarLogo></TopBarLogo>
@@ -97,7 +116,21 @@ export function TopBar() {
                             " h-2 bg-black/10 rounded text-black/0",
                     )}
                 >
-                    {context.value.isLoggedIn ? (
+                    {context.value.isLoggedIn && context.value.impersonator ? (
+                        <>
+                            <div className={"text-amber-600 opacity-100"}>
+                                {t("top_bar.impersonating", {
+                                    name: context.value.impersonator,
+                                })}
+                            </div>
+                            <div
+                                className={"cursor-pointer"}
+                                onClick={endImpersonation}
+                            >
+                                {t("top_bar.end_impersonation")}
+                            </div>
+                        </>
+                    ) : context.value.isLoggedIn ? (
                         <>
                             <div className={"cursor-pointer"} onClick={logout}>
                                 {t("top_bar.logout")}
@@ -167,7 +200,11 @@ function LoginPopover() {
         const resp = await fetch("/api/user-info")
         let data: {
             success: boolean
-            payload: { name: string; avatar: string }
+            payload: {
+                name: string
+                avatar: string
+                impersonation: { impersonator_name: string } | null
+            }
         } | null = null
         try {
             data = await resp.json()
@@ -191,6 +228,8 @@ function LoginPopover() {
                 avatar_url: data?.payload.avatar || "",
                 turnstile_enabled,
                 turnstile_site_key,
+                impersonator:
+                    data?.payload.impersonation?.impersonator_name || "",
             }
             context.setValue(value)
             return
@@ -243,12 +282,13 @@ function LoginPopover() {
             body: JSON.stringify({
                 email: references.email.current,
                 password: references.password.current,
+                remember: references.remember.current,
             }),
             method: "POST",
             credentials: "include",
-            headers: {
+            headers: csrfHeaders({
                 "Content-Type": "application/json",
-            },
+            }),
         })
         const data = await resp.json()
         if (data.success) {
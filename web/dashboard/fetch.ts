@@ -1,9 +1,11 @@
 import { t } from "i18next"
 import { toast } from "sonner"
+import { csrfHeaders } from "../components/csrf"
 
 export async function wfetch(input: RequestInfo, init?: RequestInit) {
     const resp = await fetch(input, {
         ...init,
+        headers: csrfHeaders(init?.headers),
         credentials: "include",
     })
     // 1. 检测服务器是否启动
@@ -25,9 +27,9 @@ export async function wfetch(input: RequestInfo, init?: RequestInit) {
                 body: JSON.stringify(user),
                 method: "POST",
                 credentials: "include",
-                headers: {
+                headers: csrfHeaders({
                     "Content-Type": "application/json",
-                },
+                }),
             })
             return wfetch(input, init)
         }
@@ -0,0 +1,50 @@
+import { wfetch } from "./fetch"
+
+const STORAGE_KEY = "guest_items"
+
+type GuestItem = {
+    short_path: string
+    manage_key: string
+}
+
+function loadGuestItems(): GuestItem[] {
+    try {
+        return JSON.parse(localStorage.getItem(STORAGE_KEY) || "[]")
+    } catch {
+        return []
+    }
+}
+
+// 游客创建项目时保存返回的管理密钥，登录后用于认领这些项目
+export function rememberGuestItem(payload: {
+    short_path: string
+    manage_key?: string
+}) {
+    if (!payload.manage_key) return
+    const items = loadGuestItems()
+    items.push({
+        short_path: payload.short_path,
+        manage_key: payload.manage_key,
+    })
+    localStorage.setItem(STORAGE_KEY, JSON.stringify(items))
+}
+
+// 认领保存的游客项目，返回认领成功的数量
+export async function claimGuestItems() {
+    const items = loadGuestItems()
+    if (items.length === 0) return 0
+    const resp = await wfetch("/api/items/claim", {
+        method: "POST",
+        headers: {
+            "Content-Type": "application/json",
+        },
+        body: JSON.stringify({ keys: items.map((x) => x.manage_key) }),
+    })
+    const data = await resp.json()
+    if (!data.success) {
+        throw new Error(data.payload)
+    }
+    // 已删除、过期或被其他账户认领的项目无法再认领，一并清除
+    localStorage.removeItem(STORAGE_KEY)
+    return data.payload.total as number
+}
@@ -2,6 +2,7 @@ import "../public/style.css"
 import { render } from "preact"
 import { createContext, Suspense, useEffect, useState } from "react"
 import { useTranslation } from "react-i18next"
+import { toast } from "sonner"
 import { ThemeProvider } from "@/components/ThemeProvider"
 import { Toaster } from "@/components/ui/sonner.tsx"
 import { TransitionTabs } from "../components/HeightTransition"
@@ -11,8 +12,11 @@ import { AreaPasteBin } from "./components/AreaPasteBin"
 import { AreaShared } from "./components/AreaShared"
 import { AreaShortUrl } from "./components/AreaShortUrl"
 import { TopBar } from "./components/TopBar"
+import { claimGuestItems } from "./guestItems"
 import "../components/i18n"
 import { AboutDialog } from "./components/AboutDialog"
+import { InviteDialog } from "./components/InviteDialog"
+import { ResetPasswordDialog } from "./components/ResetPasswordDialog"
 
 const root = document.getElementById("app")
 if (!root) throw new Error("Launch failed: Root element not found")
@@ -25,6 +29,7 @@ export const AccountCtx = createContext({
         avatar_url: "",
         turnstile_enabled: false,
         turnstile_site_key: "",
+        impersonator: "",
     },
     setValue: (_: {
         isLoggedIn: boolean
@@ -33,6 +38,7 @@ export const AccountCtx = createContext({
         avatar_url: string
         turnstile_enabled: boolean
         turnstile_site_key: string
+        impersonator: string
     }) => {},
     sharedListUpdTrigger: (_: number) => {},
     sharedListUpd: 0,
@@ -47,6 +53,7 @@ export function Dashboard() {
         avatar_url: "",
         turnstile_enabled: false,
         turnstile_site_key: "",
+        impersonator: "",
     })
     const [sharedListUpd, sharedListUpdTrigger] = useState(0)
 
@@ -56,6 +63,23 @@ export function Dashboard() {
     }
     const { t } = useTranslation(["dashboard", "languages"])
 
+    // 登录后认领之前以游客身份创建的项目
+    useEffect(() => {
+        if (!value.isLoggedIn) return
+        claimGuestItems()
+            .then((count) => {
+                if (count > 0) {
+                    toast.success(
+                        t("dashboard.guest_items_claimed", { count }),
+                    )
+                    sharedListUpdTrigger((x) => x + 1)
+                }
+            })
+            .catch((e) => {
+                console.error(e)
+            })
+    }, [value.isLoggedIn])
+
     // 拖拽文件到页面上时，自动切换到文件传输tab
     useEffect(() => {
         const handleDragOver = (e: DragEvent) => {
@@ -122,6 +146,8 @@ export function Dashboard() {
                     {!value.loading && value.isLoggedIn && <AreaShared />}
 
                     <AboutDialog></AboutDialog>
+                    <ResetPasswordDialog />
+                    <InviteDialog />
                 </div>
                 <Toaster richColors></Toaster>
             </AccountCtx.Provider>
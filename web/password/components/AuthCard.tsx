@@ -2,7 +2,10 @@ import { useRef } from "react"
 import { useTranslation } from "react-i18next"
 import { Button } from "@/components/ui/button"
 import { Input } from "@/components/ui/input"
-export function AuthCard(props: { error: boolean }) {
+export function AuthCard(props: {
+    error: boolean
+    retryAfter: number | null
+}) {
     const url = new URL(window.location.href)
     const password = useRef(url.searchParams.get("password") || "")
     const { t } = useTranslation("password")
@@ -48,7 +51,9 @@ export function AuthCard(props: { error: boolean }) {
                 </div>
                 {props.error && (
                     <div className={"text-sm opacity-75 mt-1 text-red-700"}>
-                        {t("wrong_msg")}
+                        {props.retryAfter
+                            ? t("locked_msg", { seconds: props.retryAfter })
+                            : t("wrong_msg")}
                     </div>
                 )}
             </div>
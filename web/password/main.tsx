@@ -23,6 +23,7 @@ const backendData: {
     path_name: string
     creator_name: string
     creator_avatar: string | null
+    retry_after: number | null
 } = JSON.parse(backendDataElement.innerText)
 export function PasswordInput() {
     return (
@@ -42,7 +43,10 @@ export function PasswordInput() {
                         ></TopBarRightAvatar>
                     </TopBarRightDiv>
                 </TopBarDiv>
-                <AuthCard error={backendData.error} />
+                <AuthCard
+                    error={backendData.error}
+                    retryAfter={backendData.retry_after}
+                />
             </div>
         </ThemeProvider>
     )
@@ -26,7 +26,7 @@ import { Input } from "@/components/ui/input"
 import { Switch } from "@/components/ui/switch"
 import { TransitionHeight } from "../../components/HeightTransition"
 import type { SetupConfigPayload } from "../interface"
-import { apiRequest } from "../utils"
+import { apiRequest, getSetupCode, setSetupCode } from "../utils"
 
 hljs.registerLanguage("toml", toml)
 
@@ -43,6 +43,7 @@ export function SetupStep1({
     const [legacyConfig, setLegacyConfig] = useState("")
     const [legacyLoading, setLegacyLoading] = useState(true)
     const [legacyConfigExpanded, setLegacyConfigExpanded] = useState(false)
+    const [setupCode, setSetupCodeState] = useState(getSetupCode())
     const codeRef = useRef<HTMLElement>(null)
     const { theme: _theme } = useTheme()
     const theme =
@@ -64,6 +65,12 @@ export function SetupStep1({
     }, [legacyLoading, legacyConfigExpanded, legacyConfig])
 
     useEffect(() => {
+        if (!setupCode) {
+            setLegacyConfig(t("step1.setup_code_needed"))
+            setLegacyLoading(false)
+            return
+        }
+        setLegacyLoading(true)
         void (async () => {
             try {
                 const config = await apiRequest<string>(
@@ -80,9 +87,13 @@ export function SetupStep1({
                 setLegacyLoading(false)
             }
         })()
-    }, [t])
+    }, [t, setupCode])
 
     function goNext() {
+        if (!setupCode) {
+            toast.error(t("errors.setup_code_required"))
+            return
+        }
         try {
             new URL(setupConfig.domain)
         } catch {
@@ -112,6 +123,27 @@ export function SetupStep1({
 
                 <FieldSet className="w-full">
                     <FieldGroup>
+                        <Field>
+                            <FieldLabel htmlFor="setup_code">
+                                {t("step1.setup_code_label")}
+                            </FieldLabel>
+                            <Input
+                                id="setup_code"
+                                type="text"
+                                value={setupCode}
+                                onChange={(e) => {
+                                    const code = (e.target as HTMLInputElement)
+                                        .value
+                                    setSetupCode(code)
+                                    setSetupCodeState(getSetupCode())
+                                }}
+                                className={"font-mono"}
+                            />
+                            <FieldDescription>
+                                {t("step1.setup_code_description")}
+                            </FieldDescription>
+                        </Field>
+
                         <Field>
                             <FieldLabel htmlFor="domain">
                                 {t("step1.domain_label")}
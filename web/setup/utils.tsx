@@ -1,10 +1,25 @@
+import { csrfHeaders } from "../components/csrf"
 import type { ApiResponse } from "./interface"
 
+// 服务器启动时在控制台输出的设置代码，所有设置接口都需要在请求头中携带
+let setupCode = sessionStorage.getItem("setup_code") ?? ""
+
+export function getSetupCode() {
+    return setupCode
+}
+
+export function setSetupCode(code: string) {
+    setupCode = code.trim()
+    sessionStorage.setItem("setup_code", setupCode)
+}
+
 export async function apiRequest<T>(
     url: string,
     init?: RequestInit,
 ): Promise<T> {
-    const resp = await fetch(url, init)
+    const headers = csrfHeaders(init?.headers)
+    headers.set("X-Setup-Code", setupCode)
+    const resp = await fetch(url, { ...init, headers })
     let data: ApiResponse<T> | null = null
     try {
         data = (await resp.json()) as ApiResponse<T>